@@ -0,0 +1,53 @@
+const REFRESH_MS = 5000;
+
+function formatUptime(seconds) {
+  const h = Math.floor(seconds / 3600);
+  const m = Math.floor(seconds % 3600 / 60);
+  const s = Math.floor(seconds % 60);
+  return `${h}h ${m}m ${s}s`;
+}
+
+async function refresh() {
+  try {
+    const stats = await (await fetch("stats")).json();
+    if (stats.started_at) {
+      document.getElementById("uptime").textContent =
+        formatUptime((Date.now() - Date.parse(stats.started_at)) / 1000);
+    }
+    const history = await (await fetch("snapshots/history?limit=10")).json();
+    document.getElementById("snapshots").textContent =
+      history.length ? history[0].counter + 1 : 0;
+    document.getElementById("history").innerHTML = history.map(record => `<tr>
+      <td>${record.counter}</td>
+      <td>${new Date(record.saved_at).toLocaleTimeString()}</td>
+      <td>${record.trigger_source}</td>
+      <td>${record.path.split("/").pop()}</td></tr>`).join("");
+    document.getElementById("latest").src = `snapshot/latest?t=${Date.now()}`;
+  } catch (err) {
+    console.error("refresh failed", err);
+  }
+}
+
+// the trigger state follows the trigger events as they happen
+const events = new EventSource("events/sse");
+events.onmessage = message => {
+  const event = JSON.parse(message.data);
+  const state = document.getElementById("trigger-state");
+  if (event.Countdown !== undefined) {
+    state.textContent = `Countdown ${event.Countdown}`;
+  } else if (event.Trigger !== undefined) {
+    state.textContent = "Triggered";
+    setTimeout(refresh, 1000);
+  } else if (event === "WaitingForFace") {
+    state.textContent = "Waiting for a face";
+  } else if (event === "CountdownAborted") {
+    state.textContent = "Waiting";
+  } else if (event === "MaxTriggersReached") {
+    state.textContent = "Stopped, max triggers reached";
+  } else {
+    console.warn("unknown trigger event", event);
+  }
+};
+
+refresh();
+setInterval(refresh, REFRESH_MS);
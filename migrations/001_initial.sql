@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS snapshots (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL,
+    saved_at TEXT NOT NULL,
+    width INT NOT NULL,
+    height INT NOT NULL,
+    file_size_bytes INT NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE snapshots ADD COLUMN trigger_source TEXT NOT NULL DEFAULT '';
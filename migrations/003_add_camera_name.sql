@@ -0,0 +1 @@
+ALTER TABLE snapshots ADD COLUMN camera_name TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,5 @@
+CREATE TABLE schedules (
+    id TEXT PRIMARY KEY,
+    cron TEXT NOT NULL,
+    countdown_steps INTEGER NOT NULL
+);
@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use sysinfo::{CpuExt, System, SystemExt};
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::interval;
+use tracing::{debug, info, instrument, warn};
+
+use crate::capture_thread::Command;
+use crate::snapshot_repo::SharedSnapshotRepo;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+/// CPU usage in percent considered high, once sustained for `HIGH_SAMPLES` samples.
+const HIGH_USAGE: f32 = 90.;
+const HIGH_SAMPLES: usize = 3;
+/// CPU usage in percent below which the quality is restored.
+const NORMAL_USAGE: f32 = 70.;
+const JPEG_QUALITY_REDUCTION: u8 = 20;
+
+pub fn spawn(
+    jpeg_quality: u8,
+    video_width: u32,
+    repo: SharedSnapshotRepo,
+    capture_control_sender: mpsc::Sender<Command>,
+    exit_receiver: broadcast::Receiver<bool>,
+) -> tokio::task::JoinHandle<Result<()>> {
+    debug!("spawning adaptive quality");
+    tokio::spawn(adaptive_quality(
+        jpeg_quality,
+        video_width,
+        repo,
+        capture_control_sender,
+        exit_receiver,
+    ))
+}
+
+/// Reduce the JPEG quality and video width while the CPU is under sustained pressure.
+#[instrument(skip(repo, capture_control_sender, exit_receiver))]
+async fn adaptive_quality(
+    jpeg_quality: u8,
+    video_width: u32,
+    repo: SharedSnapshotRepo,
+    capture_control_sender: mpsc::Sender<Command>,
+    mut exit_receiver: broadcast::Receiver<bool>,
+) -> Result<()> {
+    info!("adaptive quality started");
+    let mut system = System::new();
+    let mut interval = interval(SAMPLE_INTERVAL);
+    let mut high_samples = 0;
+    let mut reduced = false;
+    loop {
+        tokio::select! {
+            _ = exit_receiver.recv() => {
+                debug!("exit received");
+                break;
+            }
+            _ = interval.tick() => {
+                system.refresh_cpu();
+                let usage = system.global_cpu_info().cpu_usage();
+                debug!(usage, "cpu usage sampled");
+                high_samples = if usage > HIGH_USAGE { high_samples + 1 } else { 0 };
+
+                let (quality, width) = if !reduced && high_samples >= HIGH_SAMPLES {
+                    reduced = true;
+                    let quality = jpeg_quality.saturating_sub(JPEG_QUALITY_REDUCTION).max(1);
+                    info!(usage, quality, width = video_width / 2, "reducing quality");
+                    (quality, video_width / 2)
+                } else if reduced && usage < NORMAL_USAGE {
+                    reduced = false;
+                    info!(usage, quality = jpeg_quality, width = video_width, "restoring quality");
+                    (jpeg_quality, video_width)
+                } else {
+                    continue;
+                };
+                repo.lock().unwrap().set_jpeg_quality(quality);
+                if capture_control_sender
+                    .send(Command::SetVideoWidth(width))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }
+    }
+    warn!("exit adaptive quality");
+    Ok(())
+}
@@ -1,10 +1,75 @@
 use anyhow::Result;
 use opencv::{
-    core::{Scalar, Vector, CV_32F},
+    core::{self, Rect, Scalar, Size, Vector, CV_32F, CV_32FC3, CV_8U},
+    imgcodecs, imgproc,
     prelude::{Mat, MatTraitConst},
 };
 use tracing::instrument;
 
+use crate::error::CameraBotError;
+use crate::pixel_buffer::{ChannelOrder, PixelBuffer};
+
+/// How an overlay is combined with the image below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Cover the image below, by the opacity of the overlay.
+    Alpha,
+    /// Lighten the image below.
+    Screen,
+    /// Multiply dark and screen light parts of the image below, increasing the contrast.
+    Overlay,
+    /// Darken the image below.
+    Multiply,
+}
+
+/// Where to place an overlay within the frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverlayPlacement {
+    /// Size of the overlay relative to the frame, `1.0` fills the frame.
+    pub scale: f32,
+    /// Horizontal position of the overlay centre, normalized to 0-1.
+    pub x: f32,
+    /// Vertical position of the overlay centre, normalized to 0-1.
+    pub y: f32,
+}
+
+impl Default for OverlayPlacement {
+    fn default() -> Self {
+        Self {
+            scale: 1.,
+            x: 0.5,
+            y: 0.5,
+        }
+    }
+}
+
+impl OverlayPlacement {
+    pub fn is_full_frame(&self) -> bool {
+        self.scale >= 1.
+    }
+
+    /// Area of a frame of `size` covered by the overlay, moved inside the frame if needed.
+    fn rect(&self, size: Size) -> Rect {
+        let width = ((size.width as f32 * self.scale).round() as i32).clamp(1, size.width);
+        let height = ((size.height as f32 * self.scale).round() as i32).clamp(1, size.height);
+        let x = (size.width as f32 * self.x).round() as i32 - width / 2;
+        let y = (size.height as f32 * self.y).round() as i32 - height / 2;
+        Rect::new(
+            x.clamp(0, size.width - width),
+            y.clamp(0, size.height - height),
+            width,
+            height,
+        )
+    }
+}
+
+/// Overlay image prepared for alpha blending.
+///
+/// `AlphaImage` is `Send` but not `Sync`, like the `Mat`s it holds: send it, or a clone of it,
+/// to the thread using it rather than sharing a reference. Known issue: a `Sync` newtype over
+/// `Mat` would not be sound, as `Mat::roi` makes a mutable `Mat` sharing the pixels of the
+/// `&Mat` it's given, so [`AlphaImage::rgb`] and [`AlphaImage::beta`] would allow writing the
+/// pixels while another thread reads them.
 #[derive(Debug, Clone)]
 pub struct AlphaImage {
     image_f: Mat,
@@ -12,41 +77,190 @@ pub struct AlphaImage {
 }
 
 impl AlphaImage {
-    pub fn new(rgba: Mat) -> Result<Self> {
+    /// Prepare a 4-channel BGRA image for alpha blending.
+    ///
+    /// ```
+    /// use camera_bot::AlphaImage;
+    /// use opencv::core::{Scalar, Size, CV_8UC4};
+    /// use opencv::prelude::*;
+    ///
+    /// let rgba = Mat::new_size_with_default(Size::new(4, 4), CV_8UC4, Scalar::all(255.))?;
+    /// let image = AlphaImage::new(rgba)?.resize(Size::new(8, 8))?;
+    /// assert_eq!(image.to_rgba_mat()?.cols(), 8);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn new(rgba: Mat) -> Result<Self, CameraBotError> {
         match prep_alpha_blend(rgba) {
             Ok((beta_f, image_f)) => Ok(Self { image_f, beta_f }),
-            Err(e) => Err(e),
+            Err(e) => Err(CameraBotError::OverlayLoad(e)),
         }
     }
 
-    pub fn resize(self, size: opencv::core::Size) -> Self {
-        if opencv::core::Size::new(self.image_f.cols(), self.image_f.rows()) != size {
-            let mut dst_image_f = Mat::default();
-            let mut dst_beta_f = Mat::default();
-            opencv::imgproc::resize(
-                &self.image_f,
-                &mut dst_image_f,
-                size,
-                0.,
-                0.,
-                opencv::imgproc::INTER_CUBIC,
-            )
-            .unwrap();
-            opencv::imgproc::resize(
-                &self.beta_f,
-                &mut dst_beta_f,
-                size,
-                0.,
-                0.,
-                opencv::imgproc::INTER_CUBIC,
-            )
-            .unwrap();
-            return Self {
-                image_f: dst_image_f,
-                beta_f: dst_beta_f,
-            };
+    /// Decode an encoded image with alpha channel, e.g. the content of a PNG file.
+    pub fn from_png_bytes(bytes: &[u8]) -> Result<Self> {
+        let rgba = imgcodecs::imdecode(
+            &Vector::<u8>::from_slice(bytes),
+            imgcodecs::IMREAD_UNCHANGED,
+        )?;
+        if rgba.empty() {
+            anyhow::bail!("unable to decode image");
+        }
+        Ok(Self::new(rgba)?)
+    }
+
+    /// Create from a 4 channel pixel buffer not necessarily originating from OpenCV.
+    pub fn from_pixel_buffer(buffer: &impl PixelBuffer) -> Result<Self> {
+        let (width, height) = (buffer.width(), buffer.height());
+        let data = buffer.as_slice();
+        if data.len() != width as usize * height as usize * 4 {
+            anyhow::bail!(
+                "pixel buffer must contain {}x{} pixels of 4 channels",
+                width,
+                height
+            );
+        }
+        let rgba = Mat::from_slice(data)?.reshape(4, i32::try_from(height)?)?;
+        match buffer.channel_order() {
+            ChannelOrder::Bgra => Ok(Self::new(rgba)?),
+            ChannelOrder::Rgba => {
+                let mut bgra = Mat::default();
+                imgproc::cvt_color(&rgba, &mut bgra, imgproc::COLOR_RGBA2BGRA, 0)?;
+                Ok(Self::new(bgra)?)
+            }
+        }
+    }
+
+    pub fn resize(self, size: Size) -> Result<Self> {
+        if self.size() == size {
+            return Ok(self);
+        }
+        self.scaled(size, 0., 0.)
+    }
+
+    /// Width and height of the image.
+    pub fn size(&self) -> Size {
+        Size::new(self.image_f.cols(), self.image_f.rows())
+    }
+
+    /// Resize both dimensions by `scale`.
+    pub fn resize_by_scale(self, scale: f64) -> Result<Self> {
+        if scale == 1. {
+            return Ok(self);
+        }
+        self.scaled(Size::default(), scale, scale)
+    }
+
+    /// Resize to `size`, or by `fx` and `fy` when `size` is empty.
+    fn scaled(&self, size: Size, fx: f64, fy: f64) -> Result<Self> {
+        let mut image_f = Mat::default();
+        let mut beta_f = Mat::default();
+        imgproc::resize(
+            &self.image_f,
+            &mut image_f,
+            size,
+            fx,
+            fy,
+            imgproc::INTER_CUBIC,
+        )?;
+        imgproc::resize(
+            &self.beta_f,
+            &mut beta_f,
+            size,
+            fx,
+            fy,
+            imgproc::INTER_CUBIC,
+        )?;
+        Ok(Self { image_f, beta_f })
+    }
+
+    /// Compose into a transparent image of `size`, scaled and positioned by `placement`.
+    pub fn place(self, size: Size, placement: OverlayPlacement) -> Result<Self> {
+        if placement.is_full_frame() {
+            return self.resize(size);
+        }
+        let rect = placement.rect(size);
+        let scaled = self.resize(rect.size())?;
+        let image_f = Mat::new_size_with_default(size, scaled.image_f.typ(), Scalar::all(0.))?;
+        let beta_f = Mat::new_size_with_default(size, scaled.beta_f.typ(), Scalar::all(1.))?;
+        // the roi shares its data with the full image
+        scaled.image_f.copy_to(&mut Mat::roi(&image_f, rect)?)?;
+        scaled.beta_f.copy_to(&mut Mat::roi(&beta_f, rect)?)?;
+        Ok(Self { image_f, beta_f })
+    }
+
+    /// Linear interpolation of two overlays resized to `size`, `None` being fully transparent.
+    ///
+    /// `t` is `0.0` for `from` and `1.0` for `to`.
+    pub fn lerp(from: Option<&Self>, to: Option<&Self>, t: f32, size: Size) -> Result<Self> {
+        let from = from.map_or_else(|| Self::transparent(size), |img| img.clone().resize(size))?;
+        let to = to.map_or_else(|| Self::transparent(size), |img| img.clone().resize(size))?;
+        let (t, s) = (f64::from(t), 1. - f64::from(t));
+        let mut image_f = Mat::default();
+        opencv::core::add_weighted(&from.image_f, s, &to.image_f, t, 0., &mut image_f, -1)?;
+        let mut beta_f = Mat::default();
+        opencv::core::add_weighted(&from.beta_f, s, &to.beta_f, t, 0., &mut beta_f, -1)?;
+        Ok(Self { image_f, beta_f })
+    }
+
+    fn transparent(size: Size) -> Result<Self> {
+        Ok(Self {
+            image_f: Mat::new_size_with_default(size, CV_32FC3, Scalar::all(0.))?,
+            beta_f: Mat::new_size_with_default(size, CV_32FC3, Scalar::all(1.))?,
+        })
+    }
+
+    /// Blend the image onto `below`, a 32-bit float image in the range 0-255, using `mode`
+    /// weighted by the opacity of the image.
+    pub fn blend_over(&self, below: &Mat, mode: BlendMode) -> Result<Mat> {
+        let no_mask = Mat::default();
+        // the part of `below` not covered by the image
+        let mut uncovered = Mat::default();
+        core::multiply(below, &self.beta_f, &mut uncovered, 1., -1)?;
+        // the image, blended with `below` and multiplied by its opacity
+        let mut covering = Mat::default();
+        match mode {
+            BlendMode::Alpha => self.image_f.copy_to(&mut covering)?,
+            BlendMode::Multiply => {
+                core::multiply(&self.image_f, below, &mut covering, 1. / 255., -1)?;
+            }
+            BlendMode::Screen => {
+                // alpha * (src + dst - src * dst)
+                let mut product = Mat::default();
+                core::multiply(&self.image_f, below, &mut product, 1. / 255., -1)?;
+                let mut alpha_below = Mat::default();
+                core::subtract(below, &uncovered, &mut alpha_below, &no_mask, -1)?;
+                let mut sum = Mat::default();
+                core::add(&self.image_f, &alpha_below, &mut sum, &no_mask, -1)?;
+                core::subtract(&sum, &product, &mut covering, &no_mask, -1)?;
+            }
+            BlendMode::Overlay => {
+                // dark parts: alpha * 2 * src * dst
+                core::multiply(&self.image_f, below, &mut covering, 2. / 255., -1)?;
+                // light parts: alpha * (1 - 2 * (1 - src) * (1 - dst))
+                let mut alpha = Mat::default();
+                self.beta_f.convert_to(&mut alpha, -1, -255., 255.)?;
+                let mut alpha_inverse_src = Mat::default();
+                core::subtract(&alpha, &self.image_f, &mut alpha_inverse_src, &no_mask, -1)?;
+                let mut inverse_below = Mat::default();
+                below.convert_to(&mut inverse_below, -1, -1., 255.)?;
+                let mut product = Mat::default();
+                core::multiply(
+                    &alpha_inverse_src,
+                    &inverse_below,
+                    &mut product,
+                    2. / 255.,
+                    -1,
+                )?;
+                let mut light = Mat::default();
+                core::subtract(&alpha, &product, &mut light, &no_mask, -1)?;
+                let mut is_light = Mat::default();
+                core::compare(below, &Scalar::all(128.), &mut is_light, core::CMP_GE)?;
+                light.copy_to_masked(&mut covering, &is_light)?;
+            }
         }
-        self
+        let mut blended = Mat::default();
+        core::add(&uncovered, &covering, &mut blended, &no_mask, -1)?;
+        Ok(blended)
     }
 
     pub const fn beta(&self) -> &Mat {
@@ -56,6 +270,45 @@ impl AlphaImage {
     pub const fn rgb(&self) -> &Mat {
         &self.image_f
     }
+
+    /// Reconstruct the 4 channel image this was created from.
+    ///
+    /// The color of fully transparent pixels can't be recovered and becomes black.
+    pub fn to_rgba_mat(&self) -> Result<Mat> {
+        let mut alpha_f32 = Mat::default();
+        opencv::core::subtract(
+            &Scalar::all(1.),
+            &self.beta_f,
+            &mut alpha_f32,
+            &Mat::default(),
+            -1,
+        )?;
+
+        let mut divisor = Mat::default();
+        opencv::core::max(
+            &alpha_f32,
+            &Scalar::all(f64::from(f32::EPSILON)),
+            &mut divisor,
+        )?;
+        let mut rgb_f32 = Mat::default();
+        opencv::core::divide2(&self.image_f, &divisor, &mut rgb_f32, 1., -1)?;
+        let mut rgb = Mat::default();
+        rgb_f32.convert_to(&mut rgb, CV_8U, 1., 0.)?;
+
+        let mut alpha_planes = Vector::<Mat>::new();
+        opencv::core::split(&alpha_f32, &mut alpha_planes)?;
+        let mut alpha = Mat::default();
+        alpha_planes
+            .get(0)?
+            .convert_to(&mut alpha, CV_8U, 255., 0.)?;
+
+        let mut planes = Vector::<Mat>::new();
+        opencv::core::split(&rgb, &mut planes)?;
+        planes.push(alpha);
+        let mut rgba = Mat::default();
+        opencv::core::merge(&planes, &mut rgba)?;
+        Ok(rgba)
+    }
 }
 
 #[test]
@@ -64,8 +317,104 @@ fn alpha_image_is_send() {
     assert::<AlphaImage>();
 }
 
+/// Fails to compile should `AlphaImage` become `Sync`, see the `AlphaImage` documentation.
+#[test]
+fn alpha_image_is_not_sync() {
+    // the item is ambiguous, as both impls apply, only for `Sync` types
+    trait AmbiguousIfSync<A> {
+        fn some_item() {}
+    }
+    impl<T: ?Sized> AmbiguousIfSync<()> for T {}
+    struct Invalid;
+    impl<T: ?Sized + Sync> AmbiguousIfSync<Invalid> for T {}
+    let _ = <AlphaImage as AmbiguousIfSync<_>>::some_item;
+}
+
+#[test]
+fn alpha_image_from_pixel_buffer() {
+    let rgba = image::RgbaImage::from_pixel(4, 2, image::Rgba([255, 0, 0, 255]));
+    let image = AlphaImage::from_pixel_buffer(&image::DynamicImage::ImageRgba8(rgba)).unwrap();
+    assert_eq!((image.rgb().cols(), image.rgb().rows()), (4, 2));
+    assert_eq!(image.rgb().channels(), 3);
+}
+
+#[test]
+fn alpha_image_resize_by_scale() {
+    let rgba = image::RgbaImage::from_pixel(4, 2, image::Rgba([255, 0, 0, 255]));
+    let image = AlphaImage::from_pixel_buffer(&image::DynamicImage::ImageRgba8(rgba)).unwrap();
+    let resized = image.resize_by_scale(2.).unwrap();
+    assert_eq!((resized.rgb().cols(), resized.rgb().rows()), (8, 4));
+    assert_eq!((resized.beta().cols(), resized.beta().rows()), (8, 4));
+}
+
+#[test]
+fn alpha_image_to_rgba_mat_round_trip() {
+    use opencv::prelude::MatTraitConstManual;
+
+    #[rustfmt::skip]
+    let pixels: [u8; 16] = [
+        10, 20, 30, 255,    200, 100, 50, 128,
+        255, 255, 255, 1,   0, 0, 0, 0,
+    ];
+    let original = Mat::from_slice(&pixels).unwrap().reshape(4, 2).unwrap();
+    let round_trip = AlphaImage::new(original.clone())
+        .unwrap()
+        .to_rgba_mat()
+        .unwrap();
+
+    assert_eq!(round_trip.typ(), original.typ());
+    let expected = original.data_bytes().unwrap();
+    let actual = round_trip.data_bytes().unwrap();
+    for (expected, actual) in expected.iter().zip(actual) {
+        assert!(
+            expected.abs_diff(*actual) <= 1,
+            "{} != {}",
+            expected,
+            actual
+        );
+    }
+}
+
+#[test]
+fn alpha_image_blend_modes() {
+    let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+    let white = AlphaImage::from_pixel_buffer(&image::DynamicImage::ImageRgba8(white)).unwrap();
+    let gray = Mat::new_rows_cols_with_default(1, 1, CV_32FC3, Scalar::all(64.)).unwrap();
+    let blend = |mode| {
+        white
+            .blend_over(&gray, mode)
+            .unwrap()
+            .at_2d::<opencv::core::Vec3f>(0, 0)
+            .unwrap()[0]
+    };
+
+    assert!((blend(BlendMode::Alpha) - 255.).abs() < 0.01);
+    assert!((blend(BlendMode::Screen) - 255.).abs() < 0.01);
+    assert!((blend(BlendMode::Multiply) - 64.).abs() < 0.01);
+    assert!((blend(BlendMode::Overlay) - 128.).abs() < 0.01);
+}
+
+#[test]
+fn alpha_image_place_in_corner() {
+    let rgba = image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 255, 255, 255]));
+    let image = AlphaImage::from_pixel_buffer(&image::DynamicImage::ImageRgba8(rgba)).unwrap();
+    let placement = OverlayPlacement {
+        scale: 0.5,
+        x: 1.,
+        y: 0.,
+    };
+    let placed = image.place(Size::new(4, 4), placement).unwrap();
+
+    assert_eq!((placed.rgb().cols(), placed.rgb().rows()), (4, 4));
+    let beta = |x, y| placed.beta().at_2d::<opencv::core::Vec3f>(y, x).unwrap()[0];
+    assert!(beta(3, 0) < 0.01);
+    assert!(beta(2, 1) < 0.01);
+    assert!((beta(0, 0) - 1.).abs() < 0.01);
+    assert!((beta(3, 3) - 1.).abs() < 0.01);
+}
+
 #[instrument]
-fn prep_alpha_blend(rgba: Mat) -> Result<(Mat, Mat)> {
+fn prep_alpha_blend(rgba: Mat) -> opencv::Result<(Mat, Mat)> {
     let (alpha_f32, rgb_f32) = {
         let mut split_planes = Vector::<Mat>::new();
         let mut alpha_planes = Vector::<Mat>::new();
@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Events streamed to clients of the `/events` websocket, and to [`EventBus`] subscribers.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum AppEvent {
+    /// A snapshot was written to `path`.
+    SnapshotSaved {
+        path: PathBuf,
+    },
+    /// Countdown step, `remaining` steps remain before the snapshot.
+    CountdownStep {
+        remaining: usize,
+    },
+    TriggerFired,
+    Error {
+        message: String,
+    },
+}
+
+pub type AppEventSender = broadcast::Sender<AppEvent>;
+
+/// The events of the bot, for applications embedding it to subscribe to.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: AppEventSender,
+}
+
+impl EventBus {
+    /// Keep up to `capacity` events for subscribers lagging behind.
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Receive the events published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<AppEvent> {
+        self.sender.subscribe()
+    }
+
+    /// The sender the events are published on.
+    pub fn sender(&self) -> AppEventSender {
+        self.sender.clone()
+    }
+}
+
+/// Publish `event`, it's fine for no client to be listening.
+pub fn publish(sender: &AppEventSender, event: AppEvent) {
+    sender.send(event).ok();
+}
@@ -1,19 +1,185 @@
-use std::{path::PathBuf, time::Duration};
+use anyhow::Result;
+use clap::{parser::ValueSource, Args as _, CommandFactory, FromArgMatches};
+use opencv::core::{Rect, Scalar};
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use tracing::{debug, error};
 
+use crate::alpha_image::{BlendMode, OverlayPlacement};
+use crate::audio::Sound;
+use crate::batch_process::Filter;
+use crate::capture_thread::CaptureBackend;
+use crate::chroma_key::parse_hex_color;
+use crate::config::Config;
+use crate::crop::Crop;
+use crate::image_adjust::ImageAdjustment;
+use crate::pip::PipPosition;
+use crate::snapshot_repo::{CollageLayout, EncodeParams, PngStrategy};
+use crate::stdin_input::{StdinFormat, StdinInput};
+use crate::zoom::Zoom;
+
+/// Outcome of [`Args::parse_with_config`].
+#[derive(Debug)]
+pub enum Parsed {
+    /// Run with the arguments.
+    Run(Box<Args>),
+    /// `--print-config` was given, with the resolved configuration as TOML to print.
+    PrintConfig(String),
+}
+
 #[derive(clap::Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 pub struct Args {
+    /// TOML config file with defaults for the command line flags
+    #[clap(long, default_value = "camera-bot.toml")]
+    pub config: PathBuf,
+
     /// Show the UI in fullscreen mode
     #[clap(short = 'F', long)]
     pub fullscreen: bool,
 
+    /// Consecutive frames the window must be reported closed before exiting, as some platforms
+    /// report it closed spuriously
+    #[clap(long, default_value_t = 2, parse(try_from_str = parse_positive))]
+    pub window_close_debounce: usize,
+
+    /// Window title, followed by the current state e.g. "Live" or "Countdown: 3"
+    #[clap(long, default_value = "camera-bot")]
+    pub window_title: String,
+
+    /// Digits to type before the snapshot key takes a mugshot
+    #[clap(long, parse(try_from_str = parse_passcode))]
+    pub passcode: Option<String>,
+
+    /// Cancel a running countdown when pressing any key but the snapshot key
+    #[clap(long)]
+    pub cancelable_countdown: bool,
+
+    /// WAV or OGG file played on each countdown step (requires the audio feature)
+    #[clap(long)]
+    pub countdown_sound: Option<PathBuf>,
+
+    /// Beep on each countdown step, unless a --countdown-sound is given (requires the audio
+    /// feature)
+    #[clap(long)]
+    pub beep: bool,
+
+    /// WAV or OGG file played when the trigger fires, e.g. a shutter click (requires the audio
+    /// feature)
+    #[clap(long)]
+    pub snapshot_sound: Option<PathBuf>,
+
+    /// Show the video as captured, rather than mirrored like a selfie camera
+    #[clap(long)]
+    pub no_flip: bool,
+
+    /// Show frames interpolated from the camera motion between the captured frames when the
+    /// camera is slow, delaying the video by a frame
+    #[clap(long)]
+    pub interpolate_frames: bool,
+
+    /// Frame rate below which frames are interpolated, and the rate they are shown at
+    #[clap(long, default_value_t = 15.)]
+    pub interpolate_threshold_fps: f32,
+
+    /// Show swatches of this many (1-8) dominant colors of the video along the bottom
+    #[clap(long, parse(try_from_str = parse_palette_colors))]
+    pub palette_overlay: Option<usize>,
+
+    /// Frames between finding the dominant colors shown by --palette-overlay
+    #[clap(long, default_value_t = 30, parse(try_from_str = parse_positive))]
+    pub palette_interval: usize,
+
+    /// Show the last saved snapshot as an inset in a corner of the live video
+    #[clap(long)]
+    pub pip_last_snapshot: bool,
+
+    /// Size of the --pip-last-snapshot inset, as a fraction of the video
+    #[clap(long, default_value_t = 0.2, parse(try_from_str = parse_unit))]
+    pub pip_scale: f32,
+
+    /// Corner of the --pip-last-snapshot inset: top-left, top-right, bottom-left or bottom-right
+    #[clap(long, parse(try_from_str = parse_pip_position), default_value = "bottom-right")]
+    pub pip_position: PipPosition,
+
+    /// Show a bar at the bottom of the video shrinking with the countdown
+    #[clap(long)]
+    pub show_countdown_bar: bool,
+
+    /// Hex color of the --show-countdown-bar
+    #[clap(long, default_value = "#FF4444", parse(try_from_str = parse_bar_color))]
+    pub countdown_bar_color: Scalar,
+
+    /// Record the snapshot latencies, exposed for Prometheus by `GET /metrics`
+    #[clap(long)]
+    pub metrics: bool,
+
+    /// Show the display frame rate in the top-left corner of the UI
+    #[clap(long)]
+    pub show_fps: bool,
+
+    /// Update the display at this frame rate, taking the time spent on each frame into account
+    #[clap(long, parse(try_from_str = parse_fps_limit))]
+    pub fps_limit: Option<f32>,
+
+    /// Write each displayed video frame to stdout as raw BGR bytes, e.g. for piping into
+    /// `ffmpeg -f rawvideo -pix_fmt bgr24 -video_size <width>x<height> -i -`
+    #[clap(long)]
+    pub stdout_output: bool,
+
+    /// Tile the most recent mugshots on screen after being idle this long, until a key is
+    /// pressed or the trigger fires
+    #[clap(long, parse(try_from_str = parse_duration))]
+    pub grid_view_after: Option<Duration>,
+
+    /// Show a slideshow of the saved snapshots after this long without a key press or trigger
+    #[clap(long, parse(try_from_str = parse_duration))]
+    pub attract_mode_after: Option<Duration>,
+
+    /// Time each snapshot of the --attract-mode-after slideshow is shown
+    #[clap(long, parse(try_from_str = parse_duration), default_value = "5s")]
+    pub attract_mode_interval: Duration,
+
+    /// Named pipe to read trigger, stop and run commands from, one per line (unix only)
+    #[clap(long)]
+    pub trigger_pipe: Option<PathBuf>,
+
+    /// Key taking a mugshot: a keycode or one of enter, space, escape, f1-f12
+    #[clap(long, default_value = "enter", parse(try_from_str = parse_key))]
+    pub key_snapshot: i32,
+
+    /// Key exiting the program: a keycode or one of enter, space, escape, f1-f12
+    #[clap(long, default_value = "escape", parse(try_from_str = parse_key))]
+    pub key_exit: i32,
+
+    /// TOML file overriding the trigger flags, reloaded when it changes
+    ///
+    /// Changes to `trigger_schedule` can't add schedules beyond those given at startup.
+    #[clap(long)]
+    pub trigger_config: Option<PathBuf>,
+
     #[clap(flatten)]
     pub video: VideoParams,
 
     #[clap(flatten)]
     pub trigger: TriggerParams,
 
+    #[clap(flatten)]
+    pub web: WebParams,
+
+    #[clap(flatten)]
+    pub log: LogParams,
+
+    #[clap(subcommand)]
+    pub command: Option<SubCommand>,
+
+    /// Name of this camera, recorded with every mugshot
+    #[clap(long, default_value = "camera")]
+    pub camera_name: String,
+
     /// Output folder for mugshots
     #[clap(short, long, default_value = "captures")]
     pub output: PathBuf,
@@ -22,20 +188,453 @@ pub struct Args {
     #[clap(short, long, default_value = "%Y-%m-%d_%H-%M-%S.jpg")]
     pub filename: String,
 
-    /// 1 or more images to use as countdown overlays
-    #[clap(short, long)]
+    /// JPEG quality (0-100) of the saved mugshots
+    #[clap(long, default_value_t = 95)]
+    pub jpeg_quality: u8,
+
+    /// PNG compression level (0-9) of mugshots saved as PNG
+    #[clap(long, default_value_t = 3, parse(try_from_str = parse_png_compression))]
+    pub png_compression: u8,
+
+    /// PNG compression strategy: default, filtered, huffman or rle
+    #[clap(long, default_value = "default", parse(try_from_str = parse_png_strategy))]
+    pub png_strategy: PngStrategy,
+
+    /// Also save the raw sensor data of each mugshot as a 16 bit TIFF, if the camera supports it
+    #[clap(long)]
+    pub save_raw: bool,
+
+    /// Reduce the JPEG quality and video width while the CPU is under sustained pressure
+    #[clap(long)]
+    pub adaptive_quality: bool,
+
+    /// Also save a thumbnail of each mugshot with this width
+    #[clap(long)]
+    pub thumbnail_width: Option<u32>,
+
+    /// JPEG quality (0-100) of the thumbnails
+    #[clap(long, default_value_t = 80)]
+    pub thumbnail_quality: u8,
+
+    /// SQLite database to record the metadata of each mugshot in
+    #[clap(long)]
+    pub db: Option<PathBuf>,
+
+    /// CSV file to append the metadata of each mugshot to
+    #[clap(long)]
+    pub csv_log: Option<PathBuf>,
+
+    /// Command printing each mugshot, with {filename} replaced by its path, e.g.
+    /// "lpr -P Canon_Selphy {filename}"
+    #[clap(long)]
+    pub print_command: Option<String>,
+
+    /// Copies to print of each mugshot
+    #[clap(long, default_value_t = 1)]
+    pub print_copies: usize,
+
+    /// Only print every n-th saved image, e.g. only the collages
+    #[clap(long, default_value_t = 1, parse(try_from_str = parse_positive))]
+    pub print_on_nth: usize,
+
+    /// Number of recent mugshots kept in memory for the web API
+    #[clap(long, default_value_t = 10)]
+    pub cache_capacity: usize,
+
+    /// 1 or more images to use as countdown overlays, files or http(s) URLs. The built-in
+    /// assets/1.png to assets/3.png by default
+    #[clap(
+        short,
+        long,
+        alias = "countdown-images",
+        conflicts_with = "overlay-bundle"
+    )]
     pub countdown: Option<Vec<PathBuf>>,
 
-    /// Image to overlay the mugshot with while frozen
-    #[clap(short, long)]
+    /// Read the countdown overlay paths from stdin, one per line, e.g.
+    /// `ls -v themes/christmas/*.png | camera-bot --countdown-from-stdin`
+    #[clap(long, conflicts_with_all = &["countdown", "overlay-bundle", "stdin-input"])]
+    pub countdown_from_stdin: bool,
+
+    /// Number of countdown steps (1-60), by default one for each countdown overlay. Fewer steps
+    /// use the first overlays, ending on overlay 1, more steps cycle back and forth through the
+    /// overlays, so with 3 overlays a countdown of 5 shows overlays 1, 2, 3, 2, 1
+    #[clap(long, parse(try_from_str = parse_countdown_steps))]
+    pub countdown_steps: Option<usize>,
+
+    /// Size of the countdown and mugshot overlays relative to the frame (0-1)
+    #[clap(long, parse(try_from_str = parse_unit), default_value = "1.0")]
+    pub overlay_scale: f32,
+
+    /// Horizontal position of the countdown and mugshot overlay centre (0-1)
+    #[clap(long, parse(try_from_str = parse_unit), default_value = "0.5")]
+    pub overlay_x: f32,
+
+    /// Vertical position of the countdown and mugshot overlay centre (0-1)
+    #[clap(long, parse(try_from_str = parse_unit), default_value = "0.5")]
+    pub overlay_y: f32,
+
+    /// How overlays are blended with the video: alpha, screen, overlay or multiply
+    #[clap(long, parse(try_from_str = parse_blend_mode), default_value = "alpha")]
+    pub blend_mode: BlendMode,
+
+    /// Duration of the fade between countdown and mugshot overlays, 0 to switch instantly
+    #[clap(long, parse(try_from_str = parse_duration_or_zero), default_value = "300ms")]
+    pub transition_duration: Duration,
+
+    /// Images stacked on top of the live video, in order
+    #[clap(long)]
+    pub overlay: Vec<PathBuf>,
+
+    /// Replace pixels of this hex color (e.g. 00ff00) by the background image
+    #[clap(long, requires = "background-image")]
+    pub chroma_key: Option<String>,
+
+    /// Allowed deviation in degrees of the hue from the chroma key color
+    #[clap(long, default_value_t = 20.0)]
+    pub chroma_tolerance: f32,
+
+    /// Background image shown in place of the chroma key color
+    #[clap(long, requires = "chroma-key")]
+    pub background_image: Option<PathBuf>,
+
+    /// Frame of the empty background, pixels looking like it are replaced by the --virtual-bg
+    #[clap(long, requires = "virtual-bg", conflicts_with = "chroma-key")]
+    pub reference_frame_path: Option<PathBuf>,
+
+    /// Background image shown in place of the --reference-frame-path background
+    #[clap(long, requires = "reference-frame-path")]
+    pub virtual_bg: Option<PathBuf>,
+
+    /// JSON file of countdown and mugshot overlay sets, used in turn for each shot
+    #[clap(long, conflicts_with_all = &["countdown", "mugshot", "overlay-bundle", "builtin-overlays"])]
+    pub per_shot_overlays: Option<PathBuf>,
+
+    /// Image to overlay the mugshot with while frozen, a file or an http(s) URL
+    #[clap(short, long, conflicts_with = "overlay-bundle")]
     pub mugshot: Option<PathBuf>,
 
+    /// ZIP archive containing the countdown overlays (1.png, 2.png, ...) and mugshot.png
+    #[clap(long)]
+    pub overlay_bundle: Option<PathBuf>,
+
+    /// Use the countdown and mugshot overlays compiled into the binary
+    #[clap(long, conflicts_with_all = &["countdown", "mugshot", "overlay-bundle"])]
+    pub builtin_overlays: bool,
+
+    /// Number of mugshots taken in a session, each time the trigger fires
+    #[clap(long, default_value_t = 1)]
+    pub session_shots: usize,
+
+    /// Duration between the mugshots of a session
+    #[clap(long, parse(try_from_str = parse_duration), default_value = "3s")]
+    pub session_interval: Duration,
+
     /// Duration showing the frozen mugshot before restarting the trigger timer
     #[clap(long, parse(try_from_str = parse_duration), default_value="3s")]
     pub freeze: Duration,
+
+    /// Delay before opening the camera, giving projectors and displays time to warm up
+    #[clap(long, parse(try_from_str = parse_duration_or_zero), default_value = "0s")]
+    pub start_delay: Duration,
+
+    /// Only show the video, for placing and focusing the camera: the auto-trigger is disabled
+    /// and triggers don't take snapshots
+    #[clap(long)]
+    pub preview_only: bool,
+
+    /// Run the whole snapshot pipeline without writing any images, printing them or calling
+    /// the webhook, e.g. to test the setup
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Also save the images shot with a camera tethered through `gphoto2 --capture-tethered`
+    #[clap(long)]
+    pub gphoto2_tether: bool,
+
+    /// Folder gphoto2 downloads the tethered images into
+    #[clap(long, default_value = "gphoto2")]
+    pub gphoto2_download_dir: PathBuf,
+
+    /// Folder of PNG images shown in order as overlay when starting, before going live
+    #[clap(long)]
+    pub startup_animation: Option<PathBuf>,
+
+    /// Duration showing each image of the startup and shutdown animations
+    #[clap(long, parse(try_from_str = parse_duration), default_value = "100ms")]
+    pub startup_frame_duration: Duration,
+
+    /// Folder of PNG images shown in order as overlay before exiting
+    #[clap(long)]
+    pub shutdown_animation: Option<PathBuf>,
+
+    /// Give up on the shutdown animation if it hasn't finished within this duration
+    #[clap(long, parse(try_from_str = parse_duration), default_value = "5s")]
+    pub shutdown_animation_timeout: Duration,
+
+    /// Save every Nth captured frame to the `background` folder of the output folder, as a
+    /// timelapse of the event
+    #[clap(long, parse(try_from_str = parse_positive))]
+    pub background_capture: Option<usize>,
+
+    /// Combine this many mugshots into a single collage, formatted as COLUMNSxROWS e.g. 2x2
+    #[clap(long, parse(try_from_str = parse_collage))]
+    pub collage: Option<CollageLayout>,
+
+    /// Also save the individual mugshots of a collage
+    #[clap(long, requires = "collage")]
+    pub collage_save_individual: bool,
+
+    /// Combine every 4 mugshots into a photo strip saved as strip_<timestamp>.jpg, besides
+    /// saving the mugshots themselves
+    #[clap(long, conflicts_with = "collage")]
+    pub photo_strip: bool,
+
+    /// Layout of the photo strip: 1x4, 4x1 or 2x2 as COLUMNSxROWS
+    #[clap(long, default_value = "1x4", parse(try_from_str = parse_strip_layout))]
+    pub strip_layout: CollageLayout,
+
+    /// White border in pixels around each mugshot of the photo strip
+    #[clap(long, default_value_t = 0)]
+    pub strip_border: u32,
+
+    /// Video clip played when a countdown starts
+    #[clap(long)]
+    pub intro_clip: Option<PathBuf>,
+
+    /// Video clip played after showing the frozen mugshot
+    #[clap(long)]
+    pub outro_clip: Option<PathBuf>,
+
+    /// Warn when the camera exposure drops below this value
+    #[clap(long)]
+    pub monitor_exposure_min: Option<f64>,
+
+    /// Warn when the camera exposure rises above this value
+    #[clap(long)]
+    pub monitor_exposure_max: Option<f64>,
+
+    /// Lock the camera exposure during the countdown, so the mugshot is as bright as the preview
+    #[clap(long)]
+    pub lock_exposure: bool,
+
+    /// URL receiving a JSON POST for every saved mugshot
+    #[clap(long)]
+    pub webhook_url: Option<String>,
+
+    /// Timeout of each webhook POST
+    #[clap(long, parse(try_from_str = parse_duration), default_value="5s")]
+    pub webhook_timeout: Duration,
+
+    /// Include the base64 encoded mugshot in the webhook POST
+    #[clap(long)]
+    pub webhook_include_image: bool,
+
+    /// Only save snapshots with a face present in the frame
+    #[clap(long)]
+    pub require_face: bool,
+
+    /// Once the timeout passes, only start the countdown when a face is in the video
+    #[clap(long)]
+    pub countdown_on_face_detect: bool,
+
+    /// Retake snapshots with a sharpness below this, as variance of the Laplacian
+    #[clap(long)]
+    pub blur_threshold: Option<f64>,
+
+    /// Delay before retaking a blurry snapshot
+    #[clap(long, parse(try_from_str = parse_duration), default_value = "500ms")]
+    pub blur_retry_delay: Duration,
+
+    /// Times to retake a blurry snapshot, before saving the sharpest one taken
+    #[clap(long, default_value_t = 3)]
+    pub blur_retry_max: usize,
+
+    /// Skip snapshots whose perceptual hash differs from a recent snapshot in fewer bits
+    #[clap(long)]
+    pub phash_dedup_threshold: Option<u32>,
+
+    /// Number of recent snapshots compared by --phash-dedup-threshold
+    #[clap(long, default_value_t = 5, parse(try_from_str = parse_positive))]
+    pub phash_history: usize,
+
+    /// Haar cascade file used for face detection
+    #[clap(long, default_value = "haarcascade_frontalface_default.xml")]
+    pub face_cascade: PathBuf,
+
+    /// Number of threads used by OpenCV for parallel processing (default: auto)
+    #[clap(long)]
+    pub opencv_threads: Option<i32>,
+
+    /// Enable or disable OpenCV optimised code paths (IPP/SIMD) (default: OpenCV default)
+    #[clap(long)]
+    pub opencv_optimize: Option<bool>,
+
+    /// Exit after running for this long, e.g. for scheduled restarts
+    #[clap(long, parse(try_from_str = parse_duration))]
+    pub max_runtime: Option<Duration>,
+
+    /// Measure the capture and display pipeline over this many frames, then exit
+    #[clap(long)]
+    pub benchmark_capture: Option<usize>,
+
+    /// Read this many frames from the camera without the UI, print the capture latencies as
+    /// JSON, then exit
+    #[clap(long, conflicts_with_all = &["benchmark-capture", "stdin-input"])]
+    pub benchmark_frames: Option<usize>,
+
+    /// Stream the captured video to this RTSP server URI, e.g. rtsp://localhost:8554/booth
+    #[clap(long)]
+    pub rtsp_output: Option<String>,
+
+    /// Write the benchmark report as JSON to this file
+    #[clap(long, requires = "benchmark-capture")]
+    pub benchmark_output: Option<PathBuf>,
+
+    /// List the available cameras with the resolution and frame rate they open with, then exit
+    #[clap(long)]
+    pub query_cameras: bool,
+
+    /// List the cameras as JSON
+    #[clap(long, requires = "query-cameras")]
+    pub query_cameras_json: bool,
+
+    /// Print the configuration resolved from the config file, environment and command line as
+    /// TOML, then exit
+    #[clap(long)]
+    pub print_config: bool,
+}
+
+impl Args {
+    /// The key bindings, one per line, as listed by the on-screen help.
+    pub fn key_help(&self) -> Vec<String> {
+        let snapshot = key_name(self.key_snapshot);
+        let mut lines = vec![match self.passcode {
+            Some(_) => format!("0-9, {}: enter passcode and take a mugshot", snapshot),
+            None => format!("{}: take a mugshot", snapshot),
+        }];
+        lines.push(format!(
+            "{}: abort countdown, or exit",
+            key_name(self.key_exit)
+        ));
+        if self.cancelable_countdown {
+            lines.push("any other key: abort countdown".to_string());
+        }
+        lines.push("?: show/hide this help".to_string());
+        lines
+    }
+
+    pub fn countdown_sound(&self) -> Option<Sound> {
+        self.countdown_sound
+            .clone()
+            .map(Sound::File)
+            .or_else(|| self.beep.then_some(Sound::Beep))
+    }
+
+    pub const fn encode_params(&self) -> EncodeParams {
+        EncodeParams {
+            jpeg_quality: self.jpeg_quality,
+            png_compression: self.png_compression,
+            png_strategy: self.png_strategy,
+        }
+    }
+
+    pub const fn overlay_placement(&self) -> OverlayPlacement {
+        OverlayPlacement {
+            scale: self.overlay_scale,
+            x: self.overlay_x,
+            y: self.overlay_y,
+        }
+    }
+
+    /// Parse the command line, taking values not given there from the `CAMERA_BOT_*` environment
+    /// variables, then from the `--config` file.
+    pub fn parse_with_config() -> Result<Parsed> {
+        Self::parse_with_config_from(std::env::args_os())
+    }
+
+    /// Like [`Args::parse_with_config`], parsing `argv` instead of the process arguments.
+    pub fn parse_with_config_from(argv: impl IntoIterator<Item = OsString>) -> Result<Parsed> {
+        let mut argv: Vec<_> = argv.into_iter().collect();
+        let command = Self::command_with_env();
+        let mut matches = command.clone().get_matches_from(&argv);
+        let args = Self::from_arg_matches(&matches)?;
+
+        if let Some(config) = Config::load(&args.config)? {
+            // before any subcommand, whose flags follow it
+            let config_args = config.to_args(|flag| {
+                command
+                    .get_arguments()
+                    .find(|arg| arg.get_long() == Some(flag))
+                    .is_none_or(|arg| {
+                        !matches!(
+                            matches.value_source(arg.get_id()),
+                            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+                        )
+                    })
+            })?;
+            argv.splice(1..1, config_args);
+            matches = command.clone().get_matches_from(argv);
+        }
+        let args = Self::from_arg_matches(&matches)?;
+        if args.print_config {
+            return Ok(Parsed::PrintConfig(
+                Config::from_matches(&command, &matches).to_toml()?,
+            ));
+        }
+        Ok(Parsed::Run(Box::new(args)))
+    }
+
+    /// The command line parser, also taking each flag from an environment variable named after
+    /// it, e.g. `CAMERA_BOT_VIDEO_WIDTH` for `--video-width`.
+    fn command_with_env() -> clap::Command<'static> {
+        let mut command = Self::command();
+        let ids: Vec<_> = command
+            .get_arguments()
+            .map(|arg| (arg.get_id(), arg.get_long()))
+            .collect();
+        // every argument is mutated, as that moves it last, to keep the order of the help
+        for (id, long) in ids {
+            let env = long
+                .filter(|long| !matches!(*long, "help" | "version"))
+                .map(|long| format!("CAMERA_BOT_{}", long.to_uppercase().replace('-', "_")));
+            command = command.mut_arg(id, |arg| match env {
+                // parsed once per run, so the names may as well live on
+                Some(env) => arg.env(Box::leak(env.into_boxed_str())),
+                None => arg,
+            });
+        }
+        command
+    }
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum SubCommand {
+    /// Apply the filters of the live video to saved images, then exit
+    Process(ProcessParams),
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ProcessParams {
+    /// Folder with the images to process
+    #[clap(long)]
+    pub input_dir: PathBuf,
+
+    /// Folder to save the processed images in, with the same filenames
+    #[clap(long)]
+    pub output_dir: PathBuf,
+
+    /// Comma separated filters to apply in order: crop, zoom, adjust, chroma-key or flip,
+    /// configured by the same flags as the live video
+    #[clap(long, use_value_delimiter = true, parse(try_from_str = parse_filter))]
+    pub post_process: Vec<Filter>,
+
+    /// Replace images already in the output folder
+    #[clap(long)]
+    pub overwrite: bool,
 }
 
-#[derive(clap::Args, Debug, Clone, Copy)]
+#[derive(clap::Args, Debug, Clone)]
 pub struct TriggerParams {
     /// Duration until start of countdown
     #[clap(short, long, parse(try_from_str = parse_duration))]
@@ -44,25 +643,329 @@ pub struct TriggerParams {
     /// Duration between each countdown
     #[clap(long, parse(try_from_str = parse_duration), default_value="1s")]
     pub timeout_between: Duration,
+
+    /// Maximum random delay added to the timeout, avoids simultaneous triggers of multiple bots
+    #[clap(long, parse(try_from_str = parse_duration_or_zero), default_value="0s")]
+    pub trigger_jitter: Duration,
+
+    /// Seed for the trigger jitter, for reproducible timing
+    #[clap(long)]
+    pub trigger_jitter_seed: Option<u64>,
+
+    /// Additional fixed interval to trigger at, may be repeated for multiple schedules
+    #[clap(long, parse(try_from_str = parse_duration))]
+    pub trigger_schedule: Vec<Duration>,
+
+    /// Stop for good after this many triggers from any source, keyboard and web included, then exit
+    /// once the last snapshot is saved
+    #[clap(long)]
+    pub max_triggers: Option<usize>,
 }
 
-#[derive(clap::Args, Debug, Clone, Copy)]
+impl TriggerParams {
+    /// Override the fields set by the TOML file at `path`, typically in a `[trigger]` section.
+    ///
+    /// Keys are the long flag names with `_` or `-`, e.g. `timeout_between = "2s"`.
+    pub fn update_from_file(&mut self, path: &Path) -> Result<()> {
+        let config = match Config::load(path)? {
+            Some(config) => config,
+            None => anyhow::bail!("trigger config file {} not found", path.display()),
+        };
+        let command = Self::augment_args(clap::Command::new("trigger-config"));
+        let matches = command.try_get_matches_from(
+            std::iter::once("trigger-config".into()).chain(config.to_args(|_| true)?),
+        )?;
+        let overrides = Self::from_arg_matches(&matches)?;
+        let is_set = |id| matches.value_source(id) == Some(ValueSource::CommandLine);
+        if is_set("timeout") {
+            self.timeout = overrides.timeout;
+        }
+        if is_set("timeout-between") {
+            self.timeout_between = overrides.timeout_between;
+        }
+        if is_set("trigger-jitter") {
+            self.trigger_jitter = overrides.trigger_jitter;
+        }
+        if is_set("trigger-jitter-seed") {
+            self.trigger_jitter_seed = overrides.trigger_jitter_seed;
+        }
+        if is_set("trigger-schedule") {
+            self.trigger_schedule = overrides.trigger_schedule;
+        }
+        if is_set("max-triggers") {
+            self.max_triggers = overrides.max_triggers;
+        }
+        debug!(?path, params = ?self, "trigger config applied");
+        Ok(())
+    }
+
+    /// Check the countdown of `countdown_steps` fits within the timeout.
+    pub fn validate(&self, countdown_steps: usize) -> Result<()> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return Ok(()),
+        };
+        if self.timeout_between >= timeout {
+            anyhow::bail!(
+                "timeout-between ({:?}) must be less than timeout ({:?})",
+                self.timeout_between,
+                timeout
+            );
+        }
+        let countdown = self.timeout_between * u32::try_from(countdown_steps)?;
+        if countdown >= timeout {
+            anyhow::bail!(
+                "countdown of {} steps of {:?} must be shorter than timeout ({:?})",
+                countdown_steps,
+                self.timeout_between,
+                timeout
+            );
+        }
+        Ok(())
+    }
+}
+
+const DEFAULT_VIDEO_WIDTH: u32 = 1920;
+const DEFAULT_SNAPSHOT_WIDTH: u32 = 1920;
+const DEFAULT_FRAME_RATE: u32 = 30;
+
+#[derive(clap::Args, Debug, Clone)]
 pub struct VideoParams {
     /// Video capture device
     #[clap(short, long, default_value_t = 0)]
     pub device: i32,
 
+    /// Video capture API opening the device: auto (v4l2, then gstreamer), v4l2, gstreamer or any
+    #[clap(long, default_value = "auto", parse(try_from_str = parse_capture_backend))]
+    pub capture_backend: CaptureBackend,
+
+    /// Read raw frames from stdin instead of a camera, e.g. piped from
+    /// `ffmpeg -i input.mp4 -f rawvideo -pix_fmt bgr24 -`
+    #[clap(long, requires_all = &["stdin-width", "stdin-height"])]
+    pub stdin_input: bool,
+
+    /// Width of the frames read from stdin
+    #[clap(long, requires = "stdin-input")]
+    pub stdin_width: Option<u32>,
+
+    /// Height of the frames read from stdin
+    #[clap(long, requires = "stdin-input")]
+    pub stdin_height: Option<u32>,
+
+    /// Pixel format of the frames read from stdin: bgr24 or yuyv
+    #[clap(long, default_value = "bgr24", parse(try_from_str = parse_stdin_format))]
+    pub stdin_format: StdinFormat,
+
+    /// Raw GStreamer pipeline used instead of the video capture device
+    #[clap(long)]
+    pub gst_pipeline: Option<String>,
+
     /// Video image width
-    #[clap(long, default_value_t = 1920)]
+    #[clap(long, default_value_t = DEFAULT_VIDEO_WIDTH)]
     pub video_width: u32,
 
+    /// Video image height (default: chosen by the camera)
+    #[clap(long)]
+    pub video_height: Option<u32>,
+
     /// Snapshot image width
-    #[clap(long, default_value_t = 1920)]
+    #[clap(long, default_value_t = DEFAULT_SNAPSHOT_WIDTH)]
     pub snapshot_width: u32,
 
+    /// Snapshot image height (default: chosen by the camera)
+    #[clap(long)]
+    pub snapshot_height: Option<u32>,
+
     /// Frame Rate
-    #[clap(long = "fps", default_value_t = 30)]
+    #[clap(long = "fps", default_value_t = DEFAULT_FRAME_RATE)]
     pub frame_rate: u32,
+
+    /// Most frames per second passed on to the UI and other receivers, --fps by default. The
+    /// camera is still read at its own rate
+    #[clap(long)]
+    pub broadcast_fps: Option<f32>,
+
+    /// Skip frames differing less than this from the previous one, as mean absolute difference
+    /// per pixel channel (0-255). 0 disables
+    #[clap(long, default_value_t = 0.0)]
+    pub frame_dedup_threshold: f64,
+
+    /// Capture this many frames for each snapshot and keep the sharpest
+    #[clap(long, default_value_t = 1, parse(try_from_str = parse_positive))]
+    pub best_of: usize,
+
+    /// Rotate snapshots clockwise by 0, 90, 180 or 270 degrees, for cameras mounted sideways.
+    /// The live video isn't rotated
+    #[clap(long, default_value_t = 0, parse(try_from_str = parse_orientation))]
+    pub orientation: u16,
+
+    /// Let the camera focus automatically, disable to avoid refocusing during the countdown
+    #[clap(long, default_value_t = true, parse(try_from_str))]
+    pub autofocus: bool,
+
+    /// Fixed focus of the camera (0-255), with --autofocus false
+    #[clap(long)]
+    pub focus: Option<u8>,
+
+    /// Frames read and discarded after opening the camera, as the first ones are often dark
+    #[clap(long, default_value_t = 5)]
+    pub warmup_frames: usize,
+
+    /// Frames buffered for slow consumers, the oldest frame is dropped when full
+    #[clap(long, default_value_t = 4, parse(try_from_str = parse_positive))]
+    pub max_capture_queue_depth: usize,
+
+    /// Region of the frame to show and save, as x,y,width,height in pixels of --video-width
+    #[clap(long, parse(try_from_str = parse_crop))]
+    pub crop: Option<Rect>,
+
+    /// Digital zoom factor, must be >= 1.0
+    #[clap(long, parse(try_from_str = parse_zoom), default_value = "1.0")]
+    pub zoom: f32,
+
+    /// Horizontal centre of the zoomed region (0-1)
+    #[clap(long, parse(try_from_str = parse_unit), default_value = "0.5")]
+    pub zoom_cx: f32,
+
+    /// Vertical centre of the zoomed region (0-1)
+    #[clap(long, parse(try_from_str = parse_unit), default_value = "0.5")]
+    pub zoom_cy: f32,
+
+    /// Brightness offset, set on the camera if supported, otherwise applied in software
+    #[clap(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    pub brightness: f32,
+
+    /// Contrast factor, set on the camera if supported, otherwise applied in software
+    #[clap(long, default_value_t = 1.0)]
+    pub contrast: f32,
+
+    /// Saturation factor, set on the camera if supported, otherwise applied in software
+    #[clap(long, default_value_t = 1.0)]
+    pub saturation: f32,
+}
+
+impl VideoParams {
+    /// Flags given a non-default value, which are ignored when a GStreamer pipeline is used.
+    pub fn ignored_by_pipeline(&self) -> Vec<&'static str> {
+        [
+            ("--video-width", self.video_width != DEFAULT_VIDEO_WIDTH),
+            ("--video-height", self.video_height.is_some()),
+            (
+                "--snapshot-width",
+                self.snapshot_width != DEFAULT_SNAPSHOT_WIDTH,
+            ),
+            ("--snapshot-height", self.snapshot_height.is_some()),
+            ("--fps", self.frame_rate != DEFAULT_FRAME_RATE),
+            ("--autofocus", !self.autofocus),
+            ("--focus", self.focus.is_some()),
+            (
+                "--capture-backend",
+                self.capture_backend != CaptureBackend::Auto,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(flag, changed)| changed.then_some(flag))
+        .collect()
+    }
+
+    pub fn stdin_input(&self) -> Option<StdinInput> {
+        match (self.stdin_input, self.stdin_width, self.stdin_height) {
+            (true, Some(width), Some(height)) => Some(StdinInput {
+                width,
+                height,
+                format: self.stdin_format,
+            }),
+            _ => None,
+        }
+    }
+
+    pub const fn adjustment(&self) -> ImageAdjustment {
+        ImageAdjustment {
+            brightness: self.brightness,
+            contrast: self.contrast,
+            saturation: self.saturation,
+        }
+    }
+
+    /// The `core::rotate` code turning snapshots upright, `None` when already upright.
+    pub fn rotate_code(&self) -> Option<i32> {
+        match self.orientation {
+            90 => Some(opencv::core::ROTATE_90_CLOCKWISE),
+            180 => Some(opencv::core::ROTATE_180),
+            270 => Some(opencv::core::ROTATE_90_COUNTERCLOCKWISE),
+            _ => None,
+        }
+    }
+
+    pub fn crop(&self) -> Option<Crop> {
+        self.crop.map(|rect| Crop {
+            rect,
+            video_width: self.video_width,
+        })
+    }
+
+    pub const fn zoom(&self) -> Zoom {
+        Zoom {
+            factor: self.zoom,
+            cx: self.zoom_cx,
+            cy: self.zoom_cy,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct WebParams {
+    /// Address the REST API listens on
+    #[clap(long, default_value = "0.0.0.0")]
+    pub web_host: String,
+
+    /// Port the REST API listens on
+    #[clap(long, default_value_t = 8080)]
+    pub web_port: u16,
+
+    /// Content-Security-Policy header added to every HTTP response
+    ///
+    /// The default only allows resources served by camera-bot itself, plus inline `data:` images.
+    /// To use the API from a WebRTC client, relax the policy to allow the signalling and media
+    /// connections, e.g. `default-src 'self'; img-src 'self' data: blob:; media-src 'self' blob:;
+    /// connect-src 'self' wss: stun: turn:`.
+    #[clap(long, default_value = "default-src 'self'; img-src 'self' data:")]
+    pub csp_policy: String,
+
+    /// Don't start the camera, only print incoming HTTP requests and answer them with 200 OK
+    #[clap(long)]
+    pub dry_run_web: bool,
+
+    /// User name required by the REST API using HTTP basic authentication
+    #[clap(long, requires = "web-password")]
+    pub web_user: Option<String>,
+
+    /// Password required by the REST API using HTTP basic authentication
+    #[clap(long, requires = "web-user")]
+    pub web_password: Option<String>,
+
+    /// Origins allowed to call the REST API from a browser, may be repeated, `*` allows any
+    #[clap(long, default_value = "*")]
+    pub cors_origins: Vec<String>,
+
+    /// Seconds browsers may cache the CORS preflight response
+    #[clap(long, default_value_t = 3600)]
+    pub cors_max_age: usize,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct LogParams {
+    /// Most verbose level logged: trace, debug, info, warn or error
+    #[clap(long, default_value = "info")]
+    pub log_level: tracing::Level,
+
+    /// Also log to this file, rotated daily with the date appended to the file name
+    #[clap(long)]
+    pub log_file: Option<PathBuf>,
+
+    /// Log structured JSON instead of human readable text
+    #[clap(long)]
+    pub log_json: bool,
 }
 
 fn parse_duration(s: &str) -> Result<Duration, &'static str> {
@@ -82,3 +985,225 @@ fn parse_duration(s: &str) -> Result<Duration, &'static str> {
         }
     }
 }
+
+fn parse_duration_or_zero(s: &str) -> Result<Duration, &'static str> {
+    match parse_duration::parse(s) {
+        Ok(d) => Ok(d),
+        Err(err) => {
+            error!(?err, "Fail parsing duration");
+            Err("Failed parsing duration")
+        }
+    }
+}
+
+fn parse_zoom(s: &str) -> Result<f32, &'static str> {
+    match s.parse::<f32>() {
+        Ok(zoom) if zoom >= 1.0 => Ok(zoom),
+        _ => Err("Must be a number >= 1.0"),
+    }
+}
+
+fn parse_png_compression(s: &str) -> Result<u8, &'static str> {
+    match s.parse::<u8>() {
+        Ok(level) if level <= 9 => Ok(level),
+        _ => Err("Must be a number within 0-9"),
+    }
+}
+
+fn parse_png_strategy(s: &str) -> Result<PngStrategy, &'static str> {
+    match s {
+        "default" => Ok(PngStrategy::Default),
+        "filtered" => Ok(PngStrategy::Filtered),
+        "huffman" => Ok(PngStrategy::HuffmanOnly),
+        "rle" => Ok(PngStrategy::Rle),
+        _ => Err("Must be one of default, filtered, huffman or rle"),
+    }
+}
+
+fn parse_palette_colors(s: &str) -> Result<usize, &'static str> {
+    match s.parse::<usize>() {
+        Ok(value) if (1..=8).contains(&value) => Ok(value),
+        _ => Err("Must be a number within 1-8"),
+    }
+}
+
+fn parse_countdown_steps(s: &str) -> Result<usize, &'static str> {
+    match s.parse::<usize>() {
+        Ok(value) if (1..=60).contains(&value) => Ok(value),
+        _ => Err("Must be a number within 1-60"),
+    }
+}
+
+fn parse_blend_mode(s: &str) -> Result<BlendMode, &'static str> {
+    match s {
+        "alpha" => Ok(BlendMode::Alpha),
+        "screen" => Ok(BlendMode::Screen),
+        "overlay" => Ok(BlendMode::Overlay),
+        "multiply" => Ok(BlendMode::Multiply),
+        _ => Err("Must be one of alpha, screen, overlay or multiply"),
+    }
+}
+
+fn parse_bar_color(s: &str) -> Result<Scalar, String> {
+    let (red, green, blue) = parse_hex_color(s).map_err(|err| err.to_string())?;
+    Ok(Scalar::new(
+        f64::from(blue),
+        f64::from(green),
+        f64::from(red),
+        0.,
+    ))
+}
+
+fn parse_pip_position(s: &str) -> Result<PipPosition, &'static str> {
+    match s {
+        "top-left" => Ok(PipPosition::TopLeft),
+        "top-right" => Ok(PipPosition::TopRight),
+        "bottom-left" => Ok(PipPosition::BottomLeft),
+        "bottom-right" => Ok(PipPosition::BottomRight),
+        _ => Err("Must be one of top-left, top-right, bottom-left or bottom-right"),
+    }
+}
+
+fn parse_filter(s: &str) -> Result<Filter, &'static str> {
+    match s {
+        "crop" => Ok(Filter::Crop),
+        "zoom" => Ok(Filter::Zoom),
+        "adjust" => Ok(Filter::Adjust),
+        "chroma-key" => Ok(Filter::ChromaKey),
+        "flip" => Ok(Filter::Flip),
+        _ => Err("Must be one of crop, zoom, adjust, chroma-key or flip"),
+    }
+}
+
+fn parse_capture_backend(s: &str) -> Result<CaptureBackend, &'static str> {
+    match s {
+        "auto" => Ok(CaptureBackend::Auto),
+        "v4l2" => Ok(CaptureBackend::V4l2),
+        "gstreamer" => Ok(CaptureBackend::GStreamer),
+        "any" => Ok(CaptureBackend::Any),
+        _ => Err("Must be one of auto, v4l2, gstreamer or any"),
+    }
+}
+
+fn parse_stdin_format(s: &str) -> Result<StdinFormat, &'static str> {
+    match s.to_lowercase().as_str() {
+        "bgr24" => Ok(StdinFormat::Bgr24),
+        "yuyv" => Ok(StdinFormat::Yuyv),
+        _ => Err("Must be one of bgr24 or yuyv"),
+    }
+}
+
+fn parse_positive(s: &str) -> Result<usize, &'static str> {
+    match s.parse::<usize>() {
+        Ok(value) if value > 0 => Ok(value),
+        _ => Err("Must be a number >= 1"),
+    }
+}
+
+fn parse_orientation(s: &str) -> Result<u16, &'static str> {
+    match s.parse::<u16>() {
+        Ok(degrees @ (0 | 90 | 180 | 270)) => Ok(degrees),
+        _ => Err("Must be one of 0, 90, 180 or 270"),
+    }
+}
+
+fn parse_fps_limit(s: &str) -> Result<f32, &'static str> {
+    match s.parse::<f32>() {
+        Ok(fps) if fps > 0. && fps <= 1000. => Ok(fps),
+        _ => Err("Must be a frame rate within 0-1000"),
+    }
+}
+
+fn parse_unit(s: &str) -> Result<f32, &'static str> {
+    match s.parse::<f32>() {
+        Ok(value) if (0.0..=1.0).contains(&value) => Ok(value),
+        _ => Err("Must be a number within 0-1"),
+    }
+}
+
+/// Keycode of the first function key as reported by `highgui::wait_key`.
+const KEY_F1: i32 = 190;
+
+/// Name of a keycode as accepted by `--key-snapshot` and `--key-exit`.
+fn key_name(key: i32) -> String {
+    match key {
+        13 => "enter".to_string(),
+        32 => "space".to_string(),
+        27 => "escape".to_string(),
+        key if (KEY_F1..KEY_F1 + 12).contains(&key) => format!("f{}", key - KEY_F1 + 1),
+        key => match u8::try_from(key).map(char::from) {
+            Ok(c) if c.is_ascii_graphic() => c.to_string(),
+            _ => format!("key {}", key),
+        },
+    }
+}
+
+fn parse_key(s: &str) -> Result<i32, &'static str> {
+    match s.to_lowercase().as_str() {
+        "enter" => Ok(13),
+        "space" => Ok(32),
+        "escape" => Ok(27),
+        key => match key.strip_prefix('f').map(str::parse::<i32>) {
+            Some(Ok(n @ 1..=12)) => Ok(KEY_F1 + n - 1),
+            Some(_) => Err("Function keys must be within f1-f12"),
+            None => key
+                .parse()
+                .map_err(|_| "Must be a keycode or one of enter, space, escape, f1-f12"),
+        },
+    }
+}
+
+fn parse_collage(s: &str) -> Result<CollageLayout, &'static str> {
+    match s
+        .split_once('x')
+        .map(|(columns, rows)| (columns.parse(), rows.parse()))
+    {
+        Some((Ok(columns), Ok(rows))) if columns > 0 && rows > 0 => {
+            Ok(CollageLayout { columns, rows })
+        }
+        _ => Err("Must be formatted as COLUMNSxROWS, e.g. 2x2"),
+    }
+}
+
+fn parse_strip_layout(s: &str) -> Result<CollageLayout, &'static str> {
+    match parse_collage(s) {
+        Ok(layout) if layout.columns * layout.rows == 4 => Ok(layout),
+        _ => Err("Must be one of 1x4, 4x1 or 2x2"),
+    }
+}
+
+fn parse_passcode(s: &str) -> Result<String, &'static str> {
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) {
+        Ok(s.to_string())
+    } else {
+        Err("Must be 1 or more digits")
+    }
+}
+
+fn parse_crop(s: &str) -> Result<Rect, &'static str> {
+    let values = s
+        .split(',')
+        .map(|value| value.trim().parse::<i32>())
+        .collect::<Result<Vec<_>, _>>();
+    match values.as_deref() {
+        Ok(&[x, y, width, height]) if x >= 0 && y >= 0 && width > 0 && height > 0 => {
+            Ok(Rect::new(x, y, width, height))
+        }
+        _ => Err("Must be formatted as x,y,width,height in pixels"),
+    }
+}
+
+#[test]
+fn print_config_is_returned_instead_of_exiting() {
+    let argv = ["app", "--config", "missing.toml", "--video-width", "640"];
+    let parsed =
+        Args::parse_with_config_from(argv.iter().chain(&["--print-config"]).map(OsString::from));
+    match parsed.unwrap() {
+        Parsed::PrintConfig(config) => assert!(config.contains("video-width = \"640\"")),
+        Parsed::Run(_) => panic!("--print-config must not run"),
+    }
+    match Args::parse_with_config_from(argv.map(OsString::from)).unwrap() {
+        Parsed::Run(args) => assert_eq!(args.video.video_width, 640),
+        Parsed::PrintConfig(_) => panic!("no --print-config given"),
+    }
+}
@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+#[cfg(feature = "audio")]
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+#[cfg(feature = "audio")]
+const BEEP_FREQUENCY: f32 = 440.;
+#[cfg(feature = "audio")]
+const BEEP_DURATION: Duration = Duration::from_millis(150);
+
+/// A sound played on countdown or trigger events.
+#[derive(Debug, Clone)]
+pub enum Sound {
+    /// WAV or OGG file.
+    File(PathBuf),
+    /// Short 440 Hz tone.
+    Beep,
+}
+
+impl Sound {
+    /// Warn if the sound can't be played, it's not fatal as the bot works without it.
+    pub fn check(&self) {
+        if !cfg!(feature = "audio") {
+            warn!(sound = ?self, "built without the audio feature, sounds are not played");
+        } else if let Self::File(path) = self {
+            if !path.is_file() {
+                warn!(?path, "sound file not found");
+            }
+        }
+    }
+}
+
+/// Play `sound` without waiting for it to finish.
+#[cfg(feature = "audio")]
+pub fn play(sound: &Sound) {
+    let sound = sound.clone();
+    tokio::task::spawn_blocking(move || {
+        debug!(?sound, "playing sound");
+        if let Err(err) = play_blocking(&sound) {
+            warn!(?err, ?sound, "unable to play sound");
+        }
+    });
+}
+
+#[cfg(feature = "audio")]
+fn play_blocking(sound: &Sound) -> anyhow::Result<()> {
+    use rodio::Source;
+
+    let (_stream, handle) = rodio::OutputStream::try_default()?;
+    let sink = rodio::Sink::try_new(&handle)?;
+    match sound {
+        Sound::File(path) => {
+            let file = std::io::BufReader::new(std::fs::File::open(path)?);
+            sink.append(rodio::Decoder::new(file)?);
+        }
+        Sound::Beep => sink.append(
+            rodio::source::SineWave::new(BEEP_FREQUENCY)
+                .take_duration(BEEP_DURATION)
+                .amplify(0.2),
+        ),
+    }
+    sink.sleep_until_end();
+    Ok(())
+}
+
+#[cfg(not(feature = "audio"))]
+pub fn play(sound: &Sound) {
+    debug!(?sound, "built without the audio feature, not playing sound");
+}
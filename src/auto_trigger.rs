@@ -1,50 +1,255 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
 use anyhow::Result;
 use async_trait::async_trait;
 use enum_dispatch::enum_dispatch;
+use opencv::prelude::Mat;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use tokio::select;
-use tokio::sync::{broadcast, mpsc};
-use tokio::time::sleep;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::{sleep_until, Instant};
 use tracing::{debug, info, instrument, warn};
 
 use crate::args::TriggerParams;
+use crate::face_detector::FaceDetector;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum EventMsg {
-    Trigger,
+    /// Take a snapshot, `schedule_index` is set when triggered by a `--trigger-schedule`.
+    Trigger {
+        schedule_index: Option<usize>,
+    },
+    /// The timeout passed, the countdown starts once a face is in the video.
+    WaitingForFace,
     Countdown(usize),
+    CountdownAborted,
+    /// The trigger fired `--max-triggers` times and its last snapshot was taken.
+    MaxTriggersReached,
 }
 
-#[derive(Debug)]
+/// Trigger parameters shared by all auto-triggers, may be changed at runtime.
+pub type SharedTriggerParams = Arc<RwLock<TriggerParams>>;
+
+/// Triggers fired so far by any source, counted by whoever takes the snapshots, against
+/// `--max-triggers`.
+#[derive(Debug, Clone, Default)]
+pub struct TriggersFired(Arc<AtomicUsize>);
+
+impl TriggersFired {
+    /// Count a trigger, returning the number of triggers fired so far.
+    pub fn fire(&self) -> usize {
+        self.0.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Whether `max_triggers` triggers were fired, never the case without a maximum.
+    pub fn reached(&self, max_triggers: Option<usize>) -> bool {
+        max_triggers.is_some_and(|max_triggers| self.0.load(Ordering::Relaxed) >= max_triggers)
+    }
+}
+
+/// How often a waiting trigger re-reads the timeout, in case it was changed.
+const PARAMS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+impl fmt::Display for EventMsg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Trigger {
+                schedule_index: Some(index),
+            } => write!(f, "Trigger (schedule {})", index),
+            Self::Trigger { .. } => write!(f, "Trigger"),
+            Self::WaitingForFace => write!(f, "Waiting for face"),
+            Self::Countdown(count) => write!(f, "Countdown: {}", count),
+            Self::CountdownAborted => write!(f, "Countdown aborted"),
+            Self::MaxTriggersReached => write!(f, "Max triggers reached"),
+        }
+    }
+}
+
+/// State the auto-trigger is in, for showing a status without following the events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TriggerState {
+    Waiting,
+    WaitingForFace,
+    Countdown { remaining: usize },
+    Trigger,
+    Stopped,
+}
+
+impl TriggerState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Waiting => "Waiting",
+            Self::WaitingForFace => "WaitingForFace",
+            Self::Countdown { .. } => "Countdown",
+            Self::Trigger => "Trigger",
+            Self::Stopped => "Stopped",
+        }
+    }
+
+    /// Countdown steps left, including the current one.
+    pub fn countdown_remaining(&self) -> Option<usize> {
+        match self {
+            Self::Countdown { remaining } => Some(*remaining),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum ControlMsg {
     Run,
+    /// Stop triggering until [`ControlMsg::Run`], cancelling a running countdown at once.
     Stop,
+    /// Cancel a running countdown at once, and start waiting for the timeout again. Unlike
+    /// [`ControlMsg::Stop`] the trigger keeps running.
+    AbortCountdown,
+    /// Like [`ControlMsg::AbortCountdown`], but let the current countdown step complete first.
+    AbortAfterStep,
+    /// Cancel a running countdown by a key press with `--cancelable-countdown`, returning to
+    /// waiting without triggering like [`ControlMsg::AbortCountdown`].
+    CancelCountdown,
+    /// Start a countdown of `steps` right away, e.g. for a schedule. Ignored unless waiting for
+    /// the timeout.
+    StartCountdown {
+        steps: usize,
+    },
 }
 
+impl fmt::Display for ControlMsg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Run => write!(f, "Run"),
+            Self::Stop => write!(f, "Stop"),
+            Self::AbortCountdown => write!(f, "Abort countdown"),
+            Self::AbortAfterStep => write!(f, "Abort countdown after step"),
+            Self::CancelCountdown => write!(f, "Cancel countdown"),
+            Self::StartCountdown { steps } => write!(f, "Start countdown of {} steps", steps),
+        }
+    }
+}
+
+/// Hold off the countdown until a face is detected in the video frames.
+#[derive(Clone)]
+pub struct FaceWait {
+    pub frame_sender: broadcast::Sender<Mat>,
+    pub face_detector: Arc<Mutex<FaceDetector>>,
+}
+
+impl fmt::Debug for FaceWait {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FaceWait").finish_non_exhaustive()
+    }
+}
+
+/// Spawn the auto-trigger for `--timeout` plus one for each `--trigger-schedule`.
+///
+/// Control messages sent on the returned sender are forwarded to all of them, the returned
+/// receiver follows the state the most recently changed trigger entered.
+pub fn spawn_all(
+    params: &SharedTriggerParams,
+    triggers_fired: &TriggersFired,
+    trigger_event_sender: &broadcast::Sender<EventMsg>,
+    exit_sender: &broadcast::Sender<bool>,
+    countdown_from: usize,
+    face_wait: Option<FaceWait>,
+) -> (
+    Vec<tokio::task::JoinHandle<Result<()>>>,
+    mpsc::Sender<ControlMsg>,
+    watch::Receiver<TriggerState>,
+) {
+    let schedules = params.read().unwrap().trigger_schedule.len();
+    let (state_sender, state_receiver) = watch::channel(TriggerState::Waiting);
+    let (mut trigger_threads, control_senders): (Vec<_>, Vec<_>) = std::iter::once(None)
+        .chain((0..schedules).map(Some))
+        .map(|schedule_index| {
+            spawn(
+                params.clone(),
+                triggers_fired.clone(),
+                trigger_event_sender.clone(),
+                exit_sender.subscribe(),
+                countdown_from,
+                face_wait.clone(),
+                state_sender.clone(),
+                schedule_index,
+            )
+        })
+        .unzip();
+
+    let (trigger_control_sender, control_receiver) = mpsc::channel(1);
+    trigger_threads.push(tokio::spawn(forward_control(
+        control_receiver,
+        control_senders,
+        exit_sender.subscribe(),
+    )));
+    (trigger_threads, trigger_control_sender, state_receiver)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn spawn(
-    params: TriggerParams,
+    params: SharedTriggerParams,
+    triggers_fired: TriggersFired,
     trigger_event_sender: broadcast::Sender<EventMsg>,
     exit_receiver: broadcast::Receiver<bool>,
     countdown_from: usize,
+    face_wait: Option<FaceWait>,
+    state_sender: watch::Sender<TriggerState>,
+    schedule_index: Option<usize>,
 ) -> (
     tokio::task::JoinHandle<Result<()>>,
     mpsc::Sender<ControlMsg>,
 ) {
-    debug!("spawning trigger");
+    debug!(?schedule_index, "spawning trigger");
     let (trigger_control_sender, control_receiver) = mpsc::channel(1);
     let trigger_thread = tokio::spawn(auto_trigger(
         params,
+        triggers_fired,
         trigger_event_sender,
         control_receiver,
         exit_receiver,
         countdown_from,
+        face_wait,
+        state_sender,
+        schedule_index,
     ));
     (trigger_thread, trigger_control_sender)
 }
 
+async fn forward_control(
+    mut control_receiver: mpsc::Receiver<ControlMsg>,
+    control_senders: Vec<mpsc::Sender<ControlMsg>>,
+    mut exit_receiver: broadcast::Receiver<bool>,
+) -> Result<()> {
+    loop {
+        select! {
+            _ = exit_receiver.recv() => break,
+            msg = control_receiver.recv() => match msg {
+                // one countdown only, run by the trigger of `--timeout`
+                Some(msg @ ControlMsg::StartCountdown { .. }) => {
+                    if let Some(sender) = control_senders.first() {
+                        sender.send(msg).await.ok();
+                    }
+                }
+                Some(msg) => {
+                    for sender in &control_senders {
+                        sender.send(msg).await.ok();
+                    }
+                }
+                None => break,
+            },
+        }
+    }
+    Ok(())
+}
+
 #[enum_dispatch]
 #[derive(Debug)]
 enum State {
     Waiting,
+    WaitingForFace,
     Countdown,
     Trigger,
     Stopped,
@@ -56,21 +261,22 @@ trait StateBehavior {
     async fn next_state(self) -> Result<Option<State>>;
 }
 
-#[instrument(skip(event_sender, control_receiver, exit_receiver))]
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(event_sender, control_receiver, exit_receiver, state_sender))]
 async fn auto_trigger(
-    params: TriggerParams,
+    params: SharedTriggerParams,
+    triggers_fired: TriggersFired,
     event_sender: broadcast::Sender<EventMsg>,
     control_receiver: mpsc::Receiver<ControlMsg>,
     exit_receiver: broadcast::Receiver<bool>,
     countdown: usize,
+    face_wait: Option<FaceWait>,
+    state_sender: watch::Sender<TriggerState>,
+    schedule_index: Option<usize>,
 ) -> Result<()> {
     info!("auto_trigger started");
 
-    if params.timeout.is_none() {
-        warn!("exit auto_trigger");
-        return Ok(());
-    }
-
+    let rng = jitter_rng(params.read().unwrap().trigger_jitter_seed);
     let mut state = State::from(Waiting {
         data: CommonData {
             params,
@@ -78,6 +284,12 @@ async fn auto_trigger(
             control_receiver,
             exit_receiver,
             countdown,
+            face_wait,
+            state_sender,
+            rng,
+            schedule_index,
+            deadline: None,
+            triggers_fired,
         },
     });
 
@@ -95,11 +307,57 @@ async fn auto_trigger(
 
 #[derive(Debug)]
 struct CommonData {
-    params: TriggerParams,
+    params: SharedTriggerParams,
     event_sender: broadcast::Sender<EventMsg>,
     control_receiver: mpsc::Receiver<ControlMsg>,
     exit_receiver: broadcast::Receiver<bool>,
     countdown: usize,
+    face_wait: Option<FaceWait>,
+    state_sender: watch::Sender<TriggerState>,
+    rng: StdRng,
+    schedule_index: Option<usize>,
+    /// Time of the next countdown. Schedules keep it while stopped, so taking snapshots doesn't
+    /// postpone them.
+    deadline: Option<Instant>,
+    triggers_fired: TriggersFired,
+}
+
+impl CommonData {
+    /// Duration until the countdown, `None` when not triggering automatically.
+    fn timeout(&self) -> Option<Duration> {
+        let params = self.params.read().unwrap();
+        match self.schedule_index {
+            Some(index) => params.trigger_schedule.get(index).copied(),
+            None => params.timeout,
+        }
+    }
+
+    fn timeout_between(&self) -> Duration {
+        self.params.read().unwrap().timeout_between
+    }
+
+    fn max_triggers_reached(&self) -> bool {
+        let max_triggers = self.params.read().unwrap().max_triggers;
+        self.triggers_fired.reached(max_triggers)
+    }
+
+    fn enter(&self, state: TriggerState) {
+        self.state_sender.send_replace(state);
+    }
+
+    fn jitter(&mut self) -> Duration {
+        let max_jitter = self.params.read().unwrap().trigger_jitter;
+        random_jitter(&mut self.rng, max_jitter)
+    }
+}
+
+/// Random generator of the jitter, reproducible when `--trigger-jitter-seed` is given.
+fn jitter_rng(seed: Option<u64>) -> StdRng {
+    seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64)
+}
+
+fn random_jitter(rng: &mut StdRng, max_jitter: Duration) -> Duration {
+    rng.gen_range(Duration::ZERO..=max_jitter)
 }
 
 #[derive(Debug)]
@@ -112,7 +370,28 @@ impl StateBehavior for Waiting {
     #[instrument(skip(self))]
     async fn next_state(mut self) -> Result<Option<State>> {
         debug!("=> Waiting");
+        self.data.enter(TriggerState::Waiting);
+        let keep_deadline = self.data.deadline.is_some() && self.data.schedule_index.is_some();
+        if !keep_deadline {
+            self.data.deadline = None;
+        }
+        let jitter = if keep_deadline {
+            Duration::ZERO
+        } else {
+            self.data.jitter()
+        };
+        let started = Instant::now();
         let next_state = loop {
+            // the timeout is re-read, as it may be changed at runtime
+            let deadline = match self.data.deadline {
+                Some(deadline) if self.data.schedule_index.is_some() => Some(deadline),
+                _ => self
+                    .data
+                    .timeout()
+                    .map(|timeout| started + timeout + jitter),
+            };
+            self.data.deadline = deadline;
+            let poll = Instant::now() + PARAMS_POLL_INTERVAL;
             select! {
                 _ = self.data.exit_receiver.recv() => {
                     debug!("exit received");
@@ -122,15 +401,28 @@ impl StateBehavior for Waiting {
                     debug!(?msg, "received control msg");
                     match msg {
                         Some(ControlMsg::Stop) => break Some(Stopped { data: self.data }.into()),
-                        Some(ControlMsg::Run) | None => continue,
+                        Some(ControlMsg::StartCountdown { steps }) if steps > 0 => {
+                            break Some(Countdown { count: steps, data: self.data }.into())
+                        },
+                        Some(_) | None => continue,
                     }
                 },
-                _ = sleep(self.data.params.timeout.unwrap()) => {
+                _ = sleep_until(deadline.map_or(poll, |deadline| deadline.min(poll))) => {
+                    if deadline.is_none_or(|deadline| deadline > Instant::now()) {
+                        continue;
+                    }
                     debug!("timeout");
-                    break Some(Countdown {
-                        count: self.data.countdown,
-                        data: self.data,
-                    }.into())
+                    self.data.deadline = None;
+                    break Some(match &self.data.face_wait {
+                        Some(face_wait) => WaitingForFace {
+                            frame_receiver: face_wait.frame_sender.subscribe(),
+                            data: self.data,
+                        }.into(),
+                        None => Countdown {
+                            count: self.data.countdown,
+                            data: self.data,
+                        }.into(),
+                    })
                 },
             };
         };
@@ -138,6 +430,70 @@ impl StateBehavior for Waiting {
     }
 }
 
+#[derive(Debug)]
+struct WaitingForFace {
+    data: CommonData,
+    frame_receiver: broadcast::Receiver<Mat>,
+}
+
+#[async_trait]
+impl StateBehavior for WaitingForFace {
+    #[instrument(skip(self))]
+    async fn next_state(mut self) -> Result<Option<State>> {
+        debug!("=> WaitingForFace");
+        self.data.enter(TriggerState::WaitingForFace);
+        self.data.event_sender.send(EventMsg::WaitingForFace)?;
+        let face_detector = match &self.data.face_wait {
+            Some(face_wait) => face_wait.face_detector.clone(),
+            None => anyhow::bail!("waiting for a face without a face detector"),
+        };
+        let next_state = loop {
+            select! {
+                _ = self.data.exit_receiver.recv() => {
+                    debug!("exit received");
+                    break None
+                },
+                msg = self.data.control_receiver.recv() => {
+                    debug!(?msg, "received control msg");
+                    match msg {
+                        Some(ControlMsg::Stop) => break Some(Stopped { data: self.data }.into()),
+                        Some(
+                            ControlMsg::AbortCountdown
+                            | ControlMsg::AbortAfterStep
+                            | ControlMsg::CancelCountdown,
+                        ) => {
+                            debug!("countdown aborted");
+                            self.data.event_sender.send(EventMsg::CountdownAborted)?;
+                            break Some(Waiting { data: self.data }.into())
+                        },
+                        Some(ControlMsg::Run | ControlMsg::StartCountdown { .. }) | None => continue,
+                    }
+                },
+                frame = self.frame_receiver.recv() => {
+                    let frame = match frame {
+                        Ok(frame) => frame,
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break None,
+                    };
+                    let face_detector = face_detector.clone();
+                    let face_found = tokio::task::spawn_blocking(move || {
+                        face_detector.lock().unwrap().detect(&frame)
+                    })
+                    .await??;
+                    if face_found {
+                        debug!("face found");
+                        break Some(Countdown {
+                            count: self.data.countdown,
+                            data: self.data,
+                        }.into())
+                    }
+                },
+            }
+        };
+        Ok(next_state)
+    }
+}
+
 #[derive(Debug)]
 struct Countdown {
     data: CommonData,
@@ -149,9 +505,14 @@ impl StateBehavior for Countdown {
     #[instrument(skip(self))]
     async fn next_state(mut self) -> Result<Option<State>> {
         debug!(index=?self.count, "=> Countdown");
+        self.data.enter(TriggerState::Countdown {
+            remaining: self.count,
+        });
         self.data
             .event_sender
             .send(EventMsg::Countdown(self.count))?;
+        let step_end = Instant::now() + self.data.timeout_between();
+        let mut aborted = false;
         let next_state = loop {
             select! {
                 _ = self.data.exit_receiver.recv() => {
@@ -162,10 +523,24 @@ impl StateBehavior for Countdown {
                     debug!(?msg, "received control msg");
                     match msg {
                         Some(ControlMsg::Stop) => break Some(Stopped{ data:self.data }.into()),
-                        Some(ControlMsg::Run) | None => continue,
+                        Some(ControlMsg::AbortCountdown | ControlMsg::CancelCountdown) => {
+                            debug!(?msg, "countdown aborted");
+                            self.data.event_sender.send(EventMsg::CountdownAborted)?;
+                            break Some(Waiting{ data:self.data }.into())
+                        },
+                        Some(ControlMsg::AbortAfterStep) => {
+                            debug!("aborting countdown after this step");
+                            aborted = true;
+                        },
+                        Some(ControlMsg::Run | ControlMsg::StartCountdown { .. }) | None => continue,
                     }
                 },
-                _ = sleep(self.data.params.timeout_between) => {
+                _ = sleep_until(step_end) => {
+                    if aborted {
+                        debug!("countdown aborted");
+                        self.data.event_sender.send(EventMsg::CountdownAborted)?;
+                        break Some(Waiting{ data:self.data }.into())
+                    }
                     debug!("timeout");
                     self.count -= 1;
                     break Some(
@@ -190,9 +565,12 @@ struct Trigger {
 #[async_trait]
 impl StateBehavior for Trigger {
     #[instrument(skip(self))]
-    async fn next_state(self) -> Result<Option<State>> {
+    async fn next_state(mut self) -> Result<Option<State>> {
         debug!("=> Triggering!!!");
-        self.data.event_sender.send(EventMsg::Trigger)?;
+        self.data.enter(TriggerState::Trigger);
+        self.data.event_sender.send(EventMsg::Trigger {
+            schedule_index: self.data.schedule_index,
+        })?;
         Ok(Some(Waiting { data: self.data }.into()))
     }
 }
@@ -207,6 +585,8 @@ impl StateBehavior for Stopped {
     #[instrument(skip(self))]
     async fn next_state(mut self) -> Result<Option<State>> {
         debug!("=> Stopped");
+        self.data.enter(TriggerState::Stopped);
+        let mut announced = false;
         let next_state = loop {
             select! {
                 _ = self.data.exit_receiver.recv() => {
@@ -216,8 +596,18 @@ impl StateBehavior for Stopped {
                 msg = self.data.control_receiver.recv() => {
                     debug!(?msg, "received control msg");
                     match msg {
+                        // the coordinator resumes once the snapshot is saved, the last one
+                        // when the max is reached
+                        Some(ControlMsg::Run) if self.data.max_triggers_reached() => {
+                            if !announced {
+                                info!("max triggers reached");
+                                self.data.event_sender.send(EventMsg::MaxTriggersReached)?;
+                                announced = true;
+                            }
+                            continue
+                        },
                         Some(ControlMsg::Run) => break Some(Waiting{ data:self.data }.into()),
-                        Some(ControlMsg::Stop) | None => continue,
+                        Some(_) | None => continue,
                     }
                 },
             }
@@ -225,3 +615,41 @@ impl StateBehavior for Stopped {
         Ok(next_state)
     }
 }
+
+#[test]
+fn seeded_jitter_is_reproducible_and_bounded() {
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[clap(flatten)]
+        trigger: TriggerParams,
+    }
+
+    let cli = Cli::parse_from([
+        "app",
+        "--trigger-jitter",
+        "2s",
+        "--trigger-jitter-seed",
+        "42",
+    ]);
+    let max_jitter = cli.trigger.trigger_jitter;
+    assert_eq!(max_jitter, Duration::from_secs(2));
+    let delays = |seed| {
+        let mut rng = jitter_rng(seed);
+        (0..100)
+            .map(|_| random_jitter(&mut rng, max_jitter))
+            .collect::<Vec<_>>()
+    };
+    let delays_a = delays(cli.trigger.trigger_jitter_seed);
+    assert_eq!(delays_a, delays(Some(42)));
+    assert_ne!(delays_a, delays(Some(43)));
+    assert!(delays_a.iter().all(|&delay| delay <= max_jitter));
+
+    let cli = Cli::parse_from(["app"]);
+    assert_eq!(cli.trigger.trigger_jitter, Duration::ZERO);
+    assert_eq!(
+        random_jitter(&mut jitter_rng(None), Duration::ZERO),
+        Duration::ZERO
+    );
+}
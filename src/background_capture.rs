@@ -0,0 +1,64 @@
+use anyhow::Result;
+use opencv::prelude::Mat;
+use tokio::sync::broadcast;
+use tracing::{debug, info, instrument, warn};
+
+use crate::snapshot_repo::SharedSnapshotRepo;
+
+pub fn spawn(
+    every: usize,
+    repo: SharedSnapshotRepo,
+    frame_receiver: broadcast::Receiver<Mat>,
+    exit_receiver: broadcast::Receiver<bool>,
+) -> tokio::task::JoinHandle<Result<()>> {
+    debug!("spawning background capture");
+    tokio::spawn(background_capture(
+        every,
+        repo,
+        frame_receiver,
+        exit_receiver,
+    ))
+}
+
+/// Save every `every`th captured frame to the background folder of the repository.
+#[instrument(skip(repo, frame_receiver, exit_receiver))]
+async fn background_capture(
+    every: usize,
+    repo: SharedSnapshotRepo,
+    mut frame_receiver: broadcast::Receiver<Mat>,
+    mut exit_receiver: broadcast::Receiver<bool>,
+) -> Result<()> {
+    info!("background capture started");
+    // frames captured since the last saved frame, including those missed while lagging
+    let mut frames = 0;
+    loop {
+        tokio::select! {
+            _ = exit_receiver.recv() => {
+                debug!("exit received");
+                break;
+            }
+            frame = frame_receiver.recv() => match frame {
+                Ok(frame) => {
+                    frames += 1;
+                    if frames < every {
+                        continue;
+                    }
+                    frames = 0;
+                    let repo = repo.clone();
+                    let saved = tokio::task::spawn_blocking(move || {
+                        repo.lock().unwrap().save_background(&frame)
+                    })
+                    .await?;
+                    if let Err(err) = saved {
+                        warn!(?err, "failed saving background frame");
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    frames = frames.saturating_add(usize::try_from(skipped).unwrap_or(usize::MAX));
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+    Ok(())
+}
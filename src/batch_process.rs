@@ -0,0 +1,129 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use anyhow::Result;
+use opencv::{imgcodecs, prelude::*};
+use tracing::{debug, info, instrument, warn};
+
+use crate::args::ProcessParams;
+use crate::chroma_key::ChromaKey;
+use crate::crop::Crop;
+use crate::image_adjust::ImageAdjustment;
+use crate::snapshot_repo::{EncodeParams, OutputFormat};
+use crate::zoom::Zoom;
+
+/// Step of the live video pipeline, which may be applied to saved images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Crop,
+    Zoom,
+    Adjust,
+    ChromaKey,
+    Flip,
+}
+
+/// The filters of the live video, configured as for the live video.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    pub crop: Option<Crop>,
+    pub zoom: Zoom,
+    pub adjustment: ImageAdjustment,
+    pub chroma_key: Option<ChromaKey>,
+}
+
+impl Pipeline {
+    fn apply(&mut self, filters: &[Filter], mut image: Mat) -> Result<Mat> {
+        for filter in filters {
+            image = match filter {
+                Filter::Crop => match &self.crop {
+                    Some(crop) => crop.apply(&image)?,
+                    None => image,
+                },
+                Filter::Zoom => self.zoom.apply(&image)?,
+                Filter::Adjust => self.adjustment.apply(&image)?,
+                Filter::ChromaKey => match &mut self.chroma_key {
+                    Some(chroma_key) => chroma_key.apply(&image)?,
+                    None => image,
+                },
+                Filter::Flip => {
+                    let mut flipped = Mat::default();
+                    opencv::core::flip(&image, &mut flipped, 1)?;
+                    flipped
+                }
+            };
+        }
+        Ok(image)
+    }
+}
+
+/// Apply the filters of `params` to every image in the input folder, saving the results with
+/// the same names in the output folder. The images are processed in parallel.
+#[instrument(skip(pipeline, encode_params))]
+pub fn run(
+    params: &ProcessParams,
+    pipeline: &Pipeline,
+    encode_params: &EncodeParams,
+) -> Result<()> {
+    std::fs::create_dir_all(&params.output_dir)?;
+    let mut inputs = Vec::new();
+    for entry in std::fs::read_dir(&params.input_dir)? {
+        let path = entry?.path();
+        if path.is_file() && OutputFormat::of(&path) != OutputFormat::Other {
+            inputs.push(path);
+        }
+    }
+    inputs.sort();
+    info!(images = inputs.len(), "processing images");
+
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+    let workers = thread::available_parallelism().map_or(1, usize::from);
+    let (inputs, next, done, failed) = (&inputs, &next, &done, &failed);
+    thread::scope(|scope| {
+        for _ in 0..workers.min(inputs.len()) {
+            let mut pipeline = pipeline.clone();
+            scope.spawn(move || {
+                while let Some(input) = inputs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if let Err(err) = process(params, &mut pipeline, encode_params, input) {
+                        warn!(?err, ?input, "failed processing image");
+                        failed.fetch_add(1, Ordering::Relaxed);
+                    }
+                    let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                    info!("processed {}/{}", done, inputs.len());
+                }
+            });
+        }
+    });
+
+    let failed = failed.load(Ordering::Relaxed);
+    if failed > 0 {
+        anyhow::bail!("{} of {} images failed", failed, inputs.len());
+    }
+    Ok(())
+}
+
+fn process(
+    params: &ProcessParams,
+    pipeline: &mut Pipeline,
+    encode_params: &EncodeParams,
+    input: &Path,
+) -> Result<()> {
+    let output = params
+        .output_dir
+        .join(input.file_name().unwrap_or_default());
+    if output.exists() && !params.overwrite {
+        debug!(?output, "already processed");
+        return Ok(());
+    }
+    let image = imgcodecs::imread(&input.display().to_string(), imgcodecs::IMREAD_COLOR)?;
+    if image.empty() {
+        anyhow::bail!("unable to read image");
+    }
+    let image = pipeline.apply(&params.post_process, image)?;
+    let encode_params = encode_params.for_format(OutputFormat::of(&output));
+    imgcodecs::imwrite(&output.display().to_string(), &image, &encode_params)?;
+    debug!(?output, "image saved");
+    Ok(())
+}
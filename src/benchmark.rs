@@ -0,0 +1,172 @@
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use opencv::{highgui, prelude::*};
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tracing::{info, instrument, warn};
+
+use crate::args::VideoParams;
+use crate::capture_thread;
+
+/// Result of measuring the capture and display pipeline.
+#[derive(Debug, Serialize)]
+pub struct BenchmarkReport {
+    pub frames: usize,
+    /// Time from a frame arriving from the capture thread until it has been displayed.
+    pub mean_latency_ms: f64,
+    pub p50_latency_ms: f64,
+    pub p99_latency_ms: f64,
+    /// Rate at which frames arrived from the capture thread.
+    pub fps: f64,
+}
+
+/// Display `frames` frames received from the capture thread, timing each frame.
+#[instrument(skip(frame_receiver))]
+pub fn run(frames: usize, mut frame_receiver: broadcast::Receiver<Mat>) -> Result<BenchmarkReport> {
+    info!("benchmark started");
+    let window = "benchmark";
+    highgui::named_window(window, highgui::WINDOW_NORMAL | highgui::WINDOW_GUI_NORMAL)?;
+
+    let mut arrivals = Vec::with_capacity(frames);
+    let mut latencies = Vec::with_capacity(frames);
+    while arrivals.len() < frames {
+        let frame = match frame_receiver.blocking_recv() {
+            Ok(frame) => frame,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!(skipped, "benchmark lagging behind");
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => anyhow::bail!("capture thread exited"),
+        };
+        let arrival = Instant::now();
+        highgui::imshow(window, &frame)?;
+        highgui::wait_key(1)?;
+        latencies.push(arrival.elapsed());
+        arrivals.push(arrival);
+    }
+    highgui::destroy_window(window)?;
+
+    latencies.sort();
+    let elapsed = match (arrivals.first(), arrivals.last()) {
+        (Some(first), Some(last)) => last.duration_since(*first),
+        _ => Duration::ZERO,
+    };
+    Ok(BenchmarkReport {
+        frames,
+        mean_latency_ms: latencies.iter().sum::<Duration>().as_secs_f64() * 1000.
+            / frames.max(1) as f64,
+        p50_latency_ms: percentile(&latencies, 50),
+        p99_latency_ms: percentile(&latencies, 99),
+        fps: if elapsed > Duration::ZERO {
+            (frames - 1) as f64 / elapsed.as_secs_f64()
+        } else {
+            0.
+        },
+    })
+}
+
+impl BenchmarkReport {
+    pub fn print(&self) {
+        println!("frames:       {}", self.frames);
+        println!("mean latency: {:.2} ms", self.mean_latency_ms);
+        println!("P50 latency:  {:.2} ms", self.p50_latency_ms);
+        println!("P99 latency:  {:.2} ms", self.p99_latency_ms);
+        println!("FPS:          {:.1}", self.fps);
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        info!(?path, "benchmark report written");
+        Ok(())
+    }
+}
+
+/// Distribution of a latency in milliseconds.
+#[derive(Debug, Serialize)]
+pub struct LatencyHistogram {
+    pub min_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencyHistogram {
+    fn new(mut durations: Vec<Duration>) -> Self {
+        durations.sort();
+        Self {
+            min_ms: percentile(&durations, 0),
+            p50_ms: percentile(&durations, 50),
+            p95_ms: percentile(&durations, 95),
+            p99_ms: percentile(&durations, 99),
+            max_ms: percentile(&durations, 100),
+        }
+    }
+}
+
+/// Result of measuring the camera without the UI.
+#[derive(Debug, Serialize)]
+pub struct CaptureBenchmarkReport {
+    pub frames: usize,
+    /// Time spent reading a frame from the camera.
+    pub capture: LatencyHistogram,
+    /// Time from a frame having been read until a broadcast receiver has it.
+    pub delivery: LatencyHistogram,
+}
+
+/// Read `frames` frames from the camera without starting the UI, timing each frame.
+#[instrument(skip(video_params))]
+pub fn run_capture(frames: usize, video_params: &VideoParams) -> Result<CaptureBenchmarkReport> {
+    info!("capture benchmark started");
+    let mut camera = capture_thread::open_camera(video_params)?;
+    let (sender, mut receiver) =
+        broadcast::channel::<(Instant, Mat)>(video_params.max_capture_queue_depth);
+    let delivery = thread::spawn(move || {
+        let mut latencies = Vec::with_capacity(frames);
+        loop {
+            match receiver.blocking_recv() {
+                Ok((captured, _)) => latencies.push(captured.elapsed()),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "benchmark lagging behind");
+                }
+                Err(broadcast::error::RecvError::Closed) => break latencies,
+            }
+        }
+    });
+
+    let mut capture = Vec::with_capacity(frames);
+    while capture.len() < frames {
+        let mut frame = Mat::default();
+        let started = Instant::now();
+        camera.read(&mut frame)?;
+        let captured = Instant::now();
+        if frame.empty() {
+            warn!("empty frame from camera");
+            continue;
+        }
+        capture.push(captured - started);
+        if sender.send((captured, frame)).is_err() {
+            anyhow::bail!("benchmark receiver exited");
+        }
+    }
+    drop(sender);
+    let delivery = delivery.join().expect("thread join failed");
+
+    Ok(CaptureBenchmarkReport {
+        frames,
+        capture: LatencyHistogram::new(capture),
+        delivery: LatencyHistogram::new(delivery),
+    })
+}
+
+/// Percentile of the sorted `durations` in milliseconds.
+fn percentile(durations: &[Duration], percent: usize) -> f64 {
+    if durations.is_empty() {
+        return 0.;
+    }
+    let index = (durations.len() * percent / 100).min(durations.len() - 1);
+    durations[index].as_secs_f64() * 1000.
+}
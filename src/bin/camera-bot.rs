@@ -0,0 +1,1401 @@
+use actix_web::web::Bytes;
+use anyhow::{Context, Result};
+use opencv::{
+    core::Vector,
+    imgcodecs, imgproc,
+    prelude::{Mat, MatTraitConst},
+};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::time::{sleep, sleep_until, Instant};
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
+
+use camera_bot::alpha_image::AlphaImage;
+use camera_bot::app_event::{AppEvent, AppEventSender, EventBus};
+use camera_bot::auto_trigger::TriggersFired;
+use camera_bot::chroma_key::ChromaKey;
+use camera_bot::csv_log::CsvLog;
+use camera_bot::face_detector::FaceDetector;
+use camera_bot::frame_interpolation::FrameInterpolator;
+use camera_bot::grid_view::GridView;
+use camera_bot::metrics::{SharedSnapshotMetrics, SnapshotLatency, SnapshotMetrics};
+use camera_bot::overlay_bundle::OverlaySet;
+use camera_bot::palette::Palette;
+use camera_bot::phash::PhashDedup;
+use camera_bot::pip::PictureInPicture;
+use camera_bot::printer::Printer;
+use camera_bot::snapshot_cache::{SharedSnapshotCache, SnapshotCache};
+use camera_bot::snapshot_db::{SharedSnapshotDb, SnapshotDb};
+use camera_bot::snapshot_repo::{
+    EncodeParams, OutputFormat, SavedImage, SharedSnapshotRepo, SnapshotRepo, TriggerSource,
+};
+use camera_bot::stats::{SharedStats, Stats};
+use camera_bot::webhook::Webhook;
+use camera_bot::zoom::SharedZoom;
+
+#[cfg(unix)]
+use camera_bot::trigger_pipe;
+use camera_bot::{
+    adaptive_quality, app_event, args, audio, auto_trigger, background_capture, batch_process,
+    benchmark, camera_monitor, camera_query, capture_thread, gphoto2_tether, log, overlay_bundle,
+    rtsp_output, schedules, sharpness, trigger_config, ui_thread, web, BotHandle, ControlMsg,
+};
+
+const KEY_0: i32 = b'0' as i32;
+const KEY_9: i32 = b'9' as i32;
+/// Longest the countdown overlay waits for the `--intro-clip` to finish playing.
+const INTRO_CLIP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CoordinatorState {
+    Idle,
+    Countdown,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = match args::Args::parse_with_config()? {
+        args::Parsed::Run(args) => *args,
+        args::Parsed::PrintConfig(config) => {
+            print!("{}", config);
+            return Ok(());
+        }
+    };
+    log::setup_tracing(&args.log)?;
+    info!("starting");
+
+    if args.web.dry_run_web {
+        info!("dry running web service");
+        return web::dry_run(args.web).await;
+    }
+
+    if args.query_cameras {
+        let cameras = camera_query::query(args.video.capture_backend)?;
+        if args.query_cameras_json {
+            println!("{}", serde_json::to_string_pretty(&cameras)?);
+        } else {
+            camera_query::print(&cameras);
+        }
+        return Ok(());
+    }
+
+    if let Some(frames) = args.benchmark_frames {
+        let video = args.video.clone();
+        let report =
+            tokio::task::spawn_blocking(move || benchmark::run_capture(frames.max(1), &video))
+                .await??;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    if let Some(threads) = args.opencv_threads {
+        opencv::core::set_num_threads(threads)?;
+    }
+    info!(threads = opencv::core::get_num_threads()?, "opencv threads");
+    if let Some(optimize) = args.opencv_optimize {
+        opencv::core::set_use_optimized(optimize)?;
+    }
+    info!(
+        optimized = opencv::core::use_optimized()?,
+        "opencv optimizations"
+    );
+
+    let countdown = if args.countdown_from_stdin {
+        Some(read_countdown_from_stdin().await?)
+    } else {
+        args.countdown.clone()
+    };
+    let mut overlay_sets = match &args.per_shot_overlays {
+        Some(path) => overlay_bundle::read_per_shot_overlays(path)?,
+        None => vec![match &args.overlay_bundle {
+            _ if args.builtin_overlays => overlay_bundle::builtin_overlays()?,
+            Some(bundle) => overlay_bundle::read_overlay_bundle(bundle)?,
+            None => read_overlay_images(
+                &countdown.unwrap_or_else(|| {
+                    ["assets/1.png", "assets/2.png", "assets/3.png"]
+                        .into_iter()
+                        .map(PathBuf::from)
+                        .collect()
+                }),
+                &args
+                    .mugshot
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from("assets/mugshot.png")),
+            )?,
+        }],
+    };
+
+    if let Some(steps) = args.countdown_steps {
+        for (countdown_images, _) in &mut overlay_sets {
+            *countdown_images = cycle_countdown(countdown_images, steps);
+        }
+    }
+
+    let face_detector = match FaceDetector::new(&args.face_cascade) {
+        Ok(face_detector) => Some(Arc::new(Mutex::new(face_detector))),
+        Err(err) if args.require_face || args.countdown_on_face_detect => return Err(err),
+        Err(err) => {
+            warn!(?err, "face detection not available");
+            None
+        }
+    };
+
+    let stacked_overlays = read_alpha_images(&args.overlay)?;
+    let startup_animation = args
+        .startup_animation
+        .as_deref()
+        .map(read_animation)
+        .transpose()?
+        .unwrap_or_default();
+    let shutdown_animation = args
+        .shutdown_animation
+        .as_deref()
+        .map(read_animation)
+        .transpose()?
+        .unwrap_or_default();
+
+    let chroma_key = match (&args.chroma_key, &args.background_image) {
+        (Some(color), Some(background)) => {
+            Some(ChromaKey::new(color, args.chroma_tolerance, background)?)
+        }
+        _ => match (&args.reference_frame_path, &args.virtual_bg) {
+            (Some(reference), Some(background)) => {
+                Some(ChromaKey::with_reference(reference, background)?)
+            }
+            _ => None,
+        },
+    };
+
+    if let Some(args::SubCommand::Process(params)) = &args.command {
+        let pipeline = batch_process::Pipeline {
+            crop: args.video.crop(),
+            zoom: args.video.zoom(),
+            adjustment: args.video.adjustment(),
+            chroma_key,
+        };
+        return batch_process::run(params, &pipeline, &args.encode_params());
+    }
+
+    if !args.start_delay.is_zero() {
+        info!(delay = ?args.start_delay, "waiting before opening the camera");
+        sleep(args.start_delay).await;
+    }
+
+    let (exit_sender, exit_receiver) = broadcast::channel(1);
+    let (capture_event_sender, capture_event_receiver) =
+        broadcast::channel(args.video.max_capture_queue_depth);
+    let frame_sender = capture_event_sender.clone();
+    let (capture_thread, software_adjustment, capture_control_sender) = {
+        let (sender, receiver) = mpsc::channel(1);
+        let (capture_thread, software_adjustment) = capture_thread::spawn(
+            args.video.clone(),
+            receiver,
+            capture_event_sender,
+            exit_sender.subscribe(),
+        )
+        .await?;
+        (capture_thread, software_adjustment, sender)
+    };
+
+    if let Some(frames) = args.benchmark_capture {
+        let report = tokio::task::spawn_blocking(move || {
+            benchmark::run(frames.max(1), capture_event_receiver)
+        })
+        .await?;
+        exit_sender.send(true)?;
+        capture_thread.join().expect("thread join failed");
+        let report = report?;
+        report.print();
+        if let Some(path) = &args.benchmark_output {
+            report.write_json(path)?;
+        }
+        return Ok(());
+    }
+
+    let stats = SharedStats::new(Mutex::new(Stats {
+        camera_name: args.camera_name.clone(),
+        started_at: Some(chrono::Local::now()),
+        ..Stats::default()
+    }));
+    let mut repo = SnapshotRepo::from_path_and_namepattern(args.output.clone(), &args.filename)?
+        .with_thumbnails(args.thumbnail_width, args.thumbnail_quality)
+        .with_encode_params(args.encode_params())
+        .with_camera_name(args.camera_name.clone())
+        .with_dry_run(args.dry_run)
+        .with_collage(args.collage, args.collage_save_individual);
+    if args.photo_strip {
+        repo = repo.with_photo_strip(args.strip_layout, args.strip_border);
+    }
+    let repo = Arc::new(Mutex::new(repo));
+    let (ui_event_sender, ui_event_receiver) = broadcast::channel(1);
+    let ui_settings = ui_thread::UiSettings {
+        window_mode: if args.fullscreen {
+            ui_thread::WindowMode::Fullscreen
+        } else {
+            ui_thread::WindowMode::Windowed
+        },
+        window_close_debounce: args.window_close_debounce,
+        placement: args.overlay_placement(),
+        blend_mode: args.blend_mode,
+        transition_duration: args.transition_duration,
+        crop: args.video.crop(),
+        zoom: args.video.zoom(),
+        adjustment: software_adjustment,
+        chroma_key: chroma_key.clone(),
+        flip: !args.no_flip,
+        show_fps: args.show_fps,
+        fps_limit: args.fps_limit,
+        grid_view: args
+            .grid_view_after
+            .map(|idle_after| GridView::new(idle_after, repo.clone())),
+        interpolator: args
+            .interpolate_frames
+            .then(|| FrameInterpolator::new(args.interpolate_threshold_fps)),
+        palette: args
+            .palette_overlay
+            .map(|colors| Palette::new(colors, args.palette_interval)),
+        pip: args
+            .pip_last_snapshot
+            .then(|| PictureInPicture::new(args.pip_scale, args.pip_position)),
+        countdown_bar_color: args.countdown_bar_color,
+        stdout_output: args.stdout_output,
+        key_help: args.key_help(),
+    };
+    let (ui_thread, ui_control_sender) = ui_thread::spawn(
+        ui_settings,
+        stats.clone(),
+        ui_event_sender,
+        capture_event_receiver,
+        exit_receiver,
+    )
+    .await?;
+    for overlay in &stacked_overlays {
+        ui_control_sender
+            .send(ui_thread::ControlMsg::PushOverlay(overlay.clone()))
+            .await
+            .ok();
+    }
+
+    let (trigger_event_sender, trigger_event_receiver) = broadcast::channel(1);
+    let mut trigger = args.trigger.clone();
+    if let Some(path) = &args.trigger_config {
+        trigger.update_from_file(path)?;
+    }
+    trigger.validate(countdown_length(&overlay_sets))?;
+    if args.preview_only {
+        info!("preview only, auto-trigger disabled");
+        trigger.timeout = None;
+        trigger.trigger_schedule.clear();
+    }
+    let trigger_params = Arc::new(RwLock::new(trigger));
+    // reloading the trigger config would enable the auto-trigger again
+    let trigger_config_thread = args
+        .trigger_config
+        .clone()
+        .filter(|_| !args.preview_only)
+        .map(|path| {
+            trigger_config::spawn(
+                path,
+                args.trigger.clone(),
+                trigger_params.clone(),
+                exit_sender.subscribe(),
+            )
+        });
+    let gphoto2_thread = args.gphoto2_tether.then(|| {
+        gphoto2_tether::spawn(
+            args.gphoto2_download_dir.clone(),
+            repo.clone(),
+            exit_sender.subscribe(),
+        )
+    });
+    let triggers_fired = TriggersFired::default();
+    let (trigger_threads, trigger_control_sender, trigger_state) = auto_trigger::spawn_all(
+        &trigger_params,
+        &triggers_fired,
+        &trigger_event_sender,
+        &exit_sender,
+        countdown_length(&overlay_sets),
+        face_detector
+            .clone()
+            .filter(|_| args.countdown_on_face_detect)
+            .map(|face_detector| auto_trigger::FaceWait {
+                frame_sender: frame_sender.clone(),
+                face_detector,
+            }),
+    );
+
+    let camera_monitor_thread = camera_monitor::spawn(
+        (args.monitor_exposure_min, args.monitor_exposure_max),
+        capture_control_sender.clone(),
+        stats.clone(),
+        exit_sender.subscribe(),
+    );
+
+    #[cfg(unix)]
+    let trigger_pipe_thread = args.trigger_pipe.clone().map(|path| {
+        trigger_pipe::spawn(
+            path,
+            trigger_event_sender.clone(),
+            trigger_control_sender.clone(),
+            exit_sender.subscribe(),
+        )
+    });
+    #[cfg(not(unix))]
+    if args.trigger_pipe.is_some() {
+        warn!("--trigger-pipe is only supported on unix");
+    }
+
+    let snapshot_cache = Arc::new(Mutex::new(SnapshotCache::new(args.cache_capacity)));
+    let zoom = Arc::new(Mutex::new(args.video.zoom()));
+    let db = args
+        .db
+        .as_deref()
+        .map(SnapshotDb::open)
+        .transpose()?
+        .map(|db| Arc::new(Mutex::new(db)));
+    let metrics = args
+        .metrics
+        .then(|| Arc::new(Mutex::new(SnapshotMetrics::default())));
+    // schedules added through the REST API, kept across restarts only in the database
+    let schedules = Arc::new(RwLock::new(match &db {
+        Some(db) => db.lock().unwrap().schedules()?.into_iter().collect(),
+        None => HashMap::new(),
+    }));
+    let scheduler_thread = schedules::spawn(
+        schedules.clone(),
+        trigger_control_sender.clone(),
+        exit_sender.subscribe(),
+    );
+    let adaptive_quality_thread = args.adaptive_quality.then(|| {
+        adaptive_quality::spawn(
+            args.jpeg_quality,
+            args.video.video_width,
+            repo.clone(),
+            capture_control_sender.clone(),
+            exit_sender.subscribe(),
+        )
+    });
+    let background_capture_thread = args.background_capture.map(|every| {
+        background_capture::spawn(
+            every,
+            repo.clone(),
+            frame_sender.subscribe(),
+            exit_sender.subscribe(),
+        )
+    });
+    let rtsp_output_thread = args.rtsp_output.clone().map(|uri| {
+        rtsp_output::spawn(
+            uri,
+            args.video.frame_rate,
+            frame_sender.subscribe(),
+            exit_sender.subscribe(),
+        )
+    });
+
+    let (coordinator_control_sender, coordinator_control_receiver) = mpsc::channel(1);
+    let bot = BotHandle::new(trigger_event_sender, EventBus::new(16));
+    let app_event_sender = bot.event_bus().sender();
+    let rest_service_thread = web::spawn(
+        args.web.clone(),
+        exit_sender.subscribe(),
+        web::AppData {
+            trigger_event_sender: bot.trigger_event_sender(),
+            trigger_control_sender: trigger_control_sender.clone(),
+            trigger_state,
+            trigger_params,
+            capture_event_sender: frame_sender,
+            capture_control_sender: capture_control_sender.clone(),
+            face_detector: face_detector.clone(),
+            ui_control_sender: ui_control_sender.clone(),
+            overlays: stacked_overlays,
+            stats,
+            snapshot_cache: snapshot_cache.clone(),
+            zoom: zoom.clone(),
+            repo: repo.clone(),
+            db: db.clone(),
+            metrics: metrics.clone(),
+            schedules,
+            coordinator_control_sender,
+            app_event_sender: app_event_sender.clone(),
+            countdown_steps: countdown_length(&overlay_sets),
+            preview_only: args.preview_only,
+        },
+    );
+
+    let webhook = args
+        .webhook_url
+        .clone()
+        .filter(|_| !args.dry_run)
+        .map(|url| Webhook::new(url, args.webhook_timeout, args.webhook_include_image))
+        .transpose()?;
+
+    if let Some(max_runtime) = args.max_runtime {
+        tokio::spawn(shutdown_after(max_runtime, exit_sender.clone()));
+    }
+
+    let snapshots = SnapshotContext {
+        capture_control_sender: capture_control_sender.clone(),
+        trigger_control_sender: trigger_control_sender.clone(),
+        display_control_sender: ui_control_sender.clone(),
+        repo,
+        face_detector: face_detector.filter(|_| args.require_face),
+        webhook,
+        snapshot_cache,
+        zoom,
+        db,
+        chroma_key,
+        csv_log: args.csv_log.clone().map(CsvLog::new),
+        printer: args
+            .print_command
+            .clone()
+            .filter(|_| !args.dry_run)
+            .map(|command| Printer::new(command, args.print_copies, args.print_on_nth)),
+        phash_dedup: args
+            .phash_dedup_threshold
+            .map(|threshold| PhashDedup::new(threshold, args.phash_history)),
+        metrics,
+        app_event_sender: app_event_sender.clone(),
+    };
+
+    let animation_frame_duration = args.startup_frame_duration;
+    let shutdown_animation_timeout = args.shutdown_animation_timeout;
+    coordinate_events(
+        args,
+        capture_control_sender,
+        ui_event_receiver,
+        &ui_control_sender,
+        trigger_event_receiver,
+        &trigger_control_sender,
+        &overlay_sets,
+        startup_animation,
+        snapshots,
+        triggers_fired,
+        app_event_sender,
+        coordinator_control_receiver,
+        exit_sender.subscribe(),
+    )
+    .await;
+
+    if !shutdown_animation.is_empty() {
+        info!("playing shutdown animation");
+        let animation = play_animation(
+            &ui_control_sender,
+            &shutdown_animation,
+            animation_frame_duration,
+        );
+        if tokio::time::timeout(shutdown_animation_timeout, animation)
+            .await
+            .is_err()
+        {
+            warn!("shutdown animation timed out");
+        }
+    }
+
+    info!("sending exit message");
+    // receivers are gone already when exiting due to --max-runtime
+    exit_sender.send(true).ok();
+    rest_service_thread.await??;
+    camera_monitor_thread.await??;
+    if let Some(adaptive_quality_thread) = adaptive_quality_thread {
+        adaptive_quality_thread.await??;
+    }
+    if let Some(background_capture_thread) = background_capture_thread {
+        background_capture_thread.await??;
+    }
+    if let Some(rtsp_output_thread) = rtsp_output_thread {
+        rtsp_output_thread.await??;
+    }
+    for trigger_thread in trigger_threads {
+        trigger_thread.await??;
+    }
+    scheduler_thread.await??;
+    if let Some(trigger_config_thread) = trigger_config_thread {
+        trigger_config_thread.await??;
+    }
+    if let Some(gphoto2_thread) = gphoto2_thread {
+        gphoto2_thread.await??;
+    }
+    #[cfg(unix)]
+    if let Some(trigger_pipe_thread) = trigger_pipe_thread {
+        trigger_pipe_thread.await??;
+    }
+    capture_thread.join().expect("thread join failed");
+    ui_thread.join().expect("thread join failed");
+
+    info!("exited");
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn coordinate_events(
+    args: args::Args,
+    capture_control_sender: mpsc::Sender<capture_thread::Command>,
+    mut ui_event_receiver: broadcast::Receiver<ui_thread::EventMsg>,
+    ui_control_sender: &mpsc::Sender<ui_thread::ControlMsg>,
+    mut trigger_event_receiver: broadcast::Receiver<auto_trigger::EventMsg>,
+    trigger_control_sender: &mpsc::Sender<auto_trigger::ControlMsg>,
+    overlay_sets: &[OverlaySet],
+    startup_animation: Vec<AlphaImage>,
+    mut snapshots: SnapshotContext,
+    triggers_fired: TriggersFired,
+    app_event_sender: AppEventSender,
+    mut control_receiver: mpsc::Receiver<ControlMsg>,
+    mut exit_receiver: broadcast::Receiver<bool>,
+) {
+    let mut state = CoordinatorState::Idle;
+    let countdown_from = countdown_length(overlay_sets);
+    // overlay sets are used in turn, one for each shot
+    let mut shots = 0;
+    // digits typed since the last press of the snapshot key
+    let mut passcode_buffer = String::new();
+    let mut exposure_locked = false;
+    let countdown_sound = args.countdown_sound();
+    let snapshot_sound = args.snapshot_sound.clone().map(audio::Sound::File);
+    for sound in countdown_sound.iter().chain(&snapshot_sound) {
+        sound.check();
+    }
+    let trigger_effects = TriggerEffects {
+        triggers_fired,
+        app_event_sender: app_event_sender.clone(),
+        snapshot_sound,
+    };
+    if !startup_animation.is_empty() {
+        info!("playing startup animation");
+        trigger_control_sender
+            .send(auto_trigger::ControlMsg::Stop)
+            .await
+            .ok();
+        play_animation(
+            ui_control_sender,
+            &startup_animation,
+            args.startup_frame_duration,
+        )
+        .await;
+        trigger_control_sender
+            .send(auto_trigger::ControlMsg::Run)
+            .await
+            .ok();
+    }
+    set_window_title(ui_control_sender, &args, "Live").await;
+    let mut last_activity = Instant::now();
+    let mut attract_mode: Option<tokio::task::JoinHandle<()>> = None;
+    loop {
+        tokio::select! {
+            _ = exit_receiver.recv() => {
+                debug!("exit received");
+                return;
+            }
+            _ = sleep_until(last_activity + args.attract_mode_after.unwrap_or_default()),
+                if args.attract_mode_after.is_some() && attract_mode.is_none() => {
+                info!("entering attract mode");
+                attract_mode = Some(tokio::spawn(play_slideshow(
+                    snapshots.repo.clone(),
+                    ui_control_sender.clone(),
+                    args.attract_mode_interval,
+                )));
+            }
+            Some(msg) = control_receiver.recv() => {
+                debug!(?msg, "control msg");
+                match msg {
+                    ControlMsg::SnapshotWithOverlay(overlay, done_sender) => {
+                        state = CoordinatorState::Idle;
+                        if !args.preview_only {
+                            trigger_effects.fire(TriggerSource::Api);
+                        }
+                        snapshots.save_snapshot(&args, Some(overlay), TriggerSource::Api).await;
+                        shots += 1;
+                        done_sender.send(()).ok();
+                    }
+                }
+            }
+            msg = ui_event_receiver.recv() => {
+                debug!(?msg, "msg from ui thread");
+                last_activity = Instant::now();
+                stop_slideshow(&mut attract_mode, ui_control_sender).await;
+                if let Ok(msg) = msg {
+                    match msg {
+                        ui_thread::EventMsg::KeyPressed(key) => match key {
+                            key if key == args.key_snapshot => {
+                                let entered = std::mem::take(&mut passcode_buffer);
+                                if args.passcode.as_ref().is_some_and(|passcode| *passcode != entered) {
+                                    warn!(at = %chrono::Local::now().to_rfc3339(), "invalid passcode entered");
+                                } else {
+                                    state = CoordinatorState::Idle;
+                                    if !args.preview_only {
+                                        trigger_effects.fire(TriggerSource::Keyboard);
+                                    }
+                                    for shot in 1..=args.session_shots.max(1) {
+                                        snapshots.save_snapshot(&args, overlay_sets[shots % overlay_sets.len()].1.clone(), TriggerSource::Keyboard).await;
+                                        shots += 1;
+                                        session_pause(ui_control_sender, trigger_control_sender, &args, shot).await;
+                                    }
+                                    unlock_exposure(&capture_control_sender, &mut exposure_locked).await;
+                                }
+                            },
+                            key if key == args.key_exit && state == CoordinatorState::Countdown => {
+                                trigger_control_sender.send(auto_trigger::ControlMsg::AbortCountdown).await.ok();
+                            },
+                            key if key == args.key_exit => return,
+                            key @ KEY_0..=KEY_9 if args.passcode.is_some() => {
+                                passcode_buffer.push(char::from(key as u8));
+                            },
+                            _ if args.cancelable_countdown && state == CoordinatorState::Countdown => {
+                                trigger_control_sender.send(auto_trigger::ControlMsg::CancelCountdown).await.ok();
+                            },
+                            _ => {}
+                        },
+                        ui_thread::EventMsg::WindowClosed => return,
+                    }
+                }
+            }
+            msg = trigger_event_receiver.recv() => {
+                debug!(?msg, "msg from trigger");
+                last_activity = Instant::now();
+                stop_slideshow(&mut attract_mode, ui_control_sender).await;
+                if let Ok(msg) = msg {
+                    match msg {
+                        auto_trigger::EventMsg::Trigger { schedule_index } => {
+                            debug!(?schedule_index, "triggered");
+                            if args.preview_only {
+                                debug!("preview only, trigger ignored");
+                                continue;
+                            }
+                            trigger_effects.fire(TriggerSource::Trigger);
+                            state = CoordinatorState::Idle;
+                            for shot in 1..=args.session_shots.max(1) {
+                                snapshots.save_snapshot(&args, overlay_sets[shots % overlay_sets.len()].1.clone(), TriggerSource::Trigger).await;
+                                shots += 1;
+                                session_pause(ui_control_sender, trigger_control_sender, &args, shot).await;
+                            }
+                            unlock_exposure(&capture_control_sender, &mut exposure_locked).await;
+                        },
+                        auto_trigger::EventMsg::WaitingForFace => {
+                            state = CoordinatorState::Countdown;
+                            ui_control_sender.send(ui_thread::ControlMsg::SetText(Some("Get ready!".to_string()))).await.ok();
+                            set_window_title(ui_control_sender, &args, &msg.to_string()).await;
+                        },
+                        auto_trigger::EventMsg::Countdown(n) => {
+                            if args.countdown_on_face_detect {
+                                ui_control_sender.send(ui_thread::ControlMsg::SetText(None)).await.ok();
+                            }
+                            state = CoordinatorState::Countdown;
+                            app_event::publish(&app_event_sender, AppEvent::CountdownStep { remaining: n });
+                            if let Some(sound) = &countdown_sound {
+                                audio::play(sound);
+                            }
+                            if args.lock_exposure && !exposure_locked {
+                                capture_control_sender.send(capture_thread::Command::LockExposure).await.ok();
+                                exposure_locked = true;
+                            }
+                            let countdown_blend_images = &overlay_sets[shots % overlay_sets.len()].0;
+                            if n == countdown_from {
+                                if let Some(intro_clip) = args.intro_clip.clone() {
+                                    let (s, r) = oneshot::channel();
+                                    ui_control_sender.send(ui_thread::ControlMsg::ShowVideoClip(intro_clip, s)).await.ok();
+                                    if tokio::time::timeout(INTRO_CLIP_TIMEOUT, r).await.is_err() {
+                                        warn!("intro clip still playing, showing the countdown");
+                                    }
+                                }
+                            }
+                            ui_control_sender.send(ui_thread::ControlMsg::Blend(countdown_blend_images.get(n-1).cloned())).await.ok();
+                            if args.show_countdown_bar {
+                                let steps_total = countdown_from.max(n);
+                                ui_control_sender.send(ui_thread::ControlMsg::ShowCountdownBar { steps_total, steps_remaining: n }).await.ok();
+                            }
+                            set_window_title(ui_control_sender, &args, &auto_trigger::EventMsg::Countdown(n).to_string()).await;
+                        },
+                        auto_trigger::EventMsg::MaxTriggersReached => {
+                            info!("max triggers reached, exiting");
+                            return;
+                        },
+                        auto_trigger::EventMsg::CountdownAborted => {
+                            state = CoordinatorState::Idle;
+                            if args.countdown_on_face_detect {
+                                ui_control_sender.send(ui_thread::ControlMsg::SetText(None)).await.ok();
+                            }
+                            unlock_exposure(&capture_control_sender, &mut exposure_locked).await;
+                            ui_control_sender.send(ui_thread::ControlMsg::Blend(None)).await.ok();
+                            set_window_title(ui_control_sender, &args, "Live").await;
+                        },
+                    }
+                }
+            }
+        };
+    }
+}
+
+/// What happens whenever a snapshot is triggered, alike for every source of snapshots.
+struct TriggerEffects {
+    triggers_fired: TriggersFired,
+    app_event_sender: AppEventSender,
+    snapshot_sound: Option<audio::Sound>,
+}
+
+impl TriggerEffects {
+    /// Count the trigger from `source` towards `--max-triggers`, announce it and play the
+    /// `--snapshot-sound`.
+    fn fire(&self, source: TriggerSource) {
+        let fired = self.triggers_fired.fire();
+        debug!(?source, fired, "trigger fired");
+        app_event::publish(&self.app_event_sender, AppEvent::TriggerFired);
+        if let Some(sound) = &self.snapshot_sound {
+            audio::play(sound);
+        }
+    }
+}
+
+/// `steps` countdown overlays from `countdown_images`, the overlay of step n is at index n - 1.
+///
+/// Fewer steps use the first `steps` overlays, so the countdown still ends on overlay 1. More
+/// steps cycle back and forth through the overlays, so 3 overlays over 5 steps show 1, 2, 3, 2, 1.
+fn cycle_countdown<T: Clone>(countdown_images: &[T], steps: usize) -> Vec<T> {
+    let len = countdown_images.len();
+    if steps <= len {
+        return countdown_images[..steps].to_vec();
+    }
+    let period = 2 * (len.max(2) - 1);
+    (0..steps)
+        .filter_map(|step| {
+            let index = step % period;
+            countdown_images.get(index.min(period - index).min(len.saturating_sub(1)))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Number of countdown steps, the length of the longest set of countdown overlays.
+fn countdown_length(overlay_sets: &[OverlaySet]) -> usize {
+    overlay_sets
+        .iter()
+        .map(|(countdown_images, _)| countdown_images.len())
+        .max()
+        .unwrap_or_default()
+}
+
+fn read_overlay_images(countdown_images: &[PathBuf], mugshot_image: &Path) -> Result<OverlaySet> {
+    debug!("reading overlay images");
+    let countdown_blend_images = read_alpha_images(countdown_images)?;
+    let snapshot_blend_image = read_alpha_image(mugshot_image).ok();
+    Ok((countdown_blend_images, snapshot_blend_image))
+}
+
+/// Read the paths of the countdown overlays from stdin, one per line.
+async fn read_countdown_from_stdin() -> Result<Vec<PathBuf>> {
+    debug!("reading countdown overlay paths from stdin");
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut paths = Vec::new();
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim();
+        if !line.is_empty() {
+            paths.push(PathBuf::from(line));
+        }
+    }
+    if paths.is_empty() {
+        anyhow::bail!("no countdown overlay paths read from stdin");
+    }
+    Ok(paths)
+}
+
+/// Read the PNG images of `dir`, ordered by filename.
+fn read_animation(dir: &Path) -> Result<Vec<AlphaImage>> {
+    let mut paths = std::fs::read_dir(dir)
+        .with_context(|| format!("unable to read animation folder {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+    });
+    paths.sort();
+    read_alpha_images(&paths)
+}
+
+fn read_alpha_images(paths: &[PathBuf]) -> Result<Vec<AlphaImage>> {
+    paths.iter().map(|path| read_alpha_image(path)).collect()
+}
+
+/// Read an overlay image from a file, or from an `http://` or `https://` URL.
+fn read_alpha_image(path: &Path) -> Result<AlphaImage> {
+    if let Some(url) = path
+        .to_str()
+        .filter(|path| path.starts_with("http://") || path.starts_with("https://"))
+    {
+        return AlphaImage::from_png_bytes(&fetch_overlay(url)?)
+            .with_context(|| format!("unable to read overlay {}", url));
+    }
+    let rgba = imgcodecs::imread(&path.display().to_string(), imgcodecs::IMREAD_UNCHANGED)?;
+    Ok(AlphaImage::new(rgba)?)
+}
+
+/// Download the image at `url`, once per run: the image is cached in a temporary file.
+fn fetch_overlay(url: &str) -> Result<Vec<u8>> {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    let cached = std::env::temp_dir()
+        .join(format!("camera-bot-overlays-{}", std::process::id()))
+        .join(format!("{:016x}", hasher.finish()));
+    if let Ok(bytes) = std::fs::read(&cached) {
+        debug!(url, ?cached, "overlay read from cache");
+        return Ok(bytes);
+    }
+    info!(url, "downloading overlay");
+    // blocking requests can't be made from within the async runtime
+    let bytes = tokio::task::block_in_place(|| -> Result<Vec<u8>> {
+        Ok(reqwest::blocking::get(url)?
+            .error_for_status()?
+            .bytes()?
+            .to_vec())
+    })?;
+    if let Err(err) = std::fs::create_dir_all(cached.parent().unwrap())
+        .and_then(|()| std::fs::write(&cached, &bytes))
+    {
+        warn!(?err, ?cached, "unable to cache overlay");
+    }
+    Ok(bytes)
+}
+
+/// Freeze the video and show the JPEG snapshots of `repo` in turn, each for `interval`.
+async fn play_slideshow(
+    repo: SharedSnapshotRepo,
+    display_control_sender: mpsc::Sender<ui_thread::ControlMsg>,
+    interval: Duration,
+) {
+    let snapshots = {
+        let repo = repo.lock().unwrap();
+        match repo.list() {
+            Ok(snapshots) => snapshots
+                .into_iter()
+                .map(|snapshot| repo.path().join(snapshot.filename))
+                .filter(|path| OutputFormat::of(path) == OutputFormat::Jpeg)
+                .collect::<Vec<_>>(),
+            Err(err) => {
+                warn!(?err, "unable to list snapshots for attract mode");
+                return;
+            }
+        }
+    };
+    if snapshots.is_empty() {
+        debug!("no snapshots for attract mode");
+        return;
+    }
+    display_control_sender
+        .send(ui_thread::ControlMsg::Freeze)
+        .await
+        .ok();
+    for path in snapshots.iter().cycle() {
+        match read_slide(path).await {
+            Ok(slide) => {
+                if display_control_sender
+                    .send(ui_thread::ControlMsg::Blend(Some(slide)))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+            Err(err) => warn!(?err, ?path, "unable to show snapshot in attract mode"),
+        }
+        sleep(interval).await;
+    }
+}
+
+async fn read_slide(path: &Path) -> Result<AlphaImage> {
+    let bytes = tokio::fs::read(path).await?;
+    let image = imgcodecs::imdecode(&Vector::<u8>::from_slice(&bytes), imgcodecs::IMREAD_COLOR)?;
+    if image.empty() {
+        anyhow::bail!("unable to decode snapshot");
+    }
+    let mut opaque = Mat::default();
+    imgproc::cvt_color(&image, &mut opaque, imgproc::COLOR_BGR2BGRA, 0)?;
+    Ok(AlphaImage::new(opaque)?)
+}
+
+/// Leave attract mode, returning to the live video.
+async fn stop_slideshow(
+    attract_mode: &mut Option<tokio::task::JoinHandle<()>>,
+    display_control_sender: &mpsc::Sender<ui_thread::ControlMsg>,
+) {
+    if let Some(slideshow) = attract_mode.take() {
+        info!("leaving attract mode");
+        slideshow.abort();
+        display_control_sender
+            .send(ui_thread::ControlMsg::Blend(None))
+            .await
+            .ok();
+        display_control_sender
+            .send(ui_thread::ControlMsg::Live)
+            .await
+            .ok();
+    }
+}
+
+/// Show each frame as overlay for `frame_duration`, then clear the overlay.
+async fn play_animation(
+    display_control_sender: &mpsc::Sender<ui_thread::ControlMsg>,
+    frames: &[AlphaImage],
+    frame_duration: Duration,
+) {
+    for frame in frames {
+        if display_control_sender
+            .send(ui_thread::ControlMsg::Blend(Some(frame.clone())))
+            .await
+            .is_err()
+        {
+            // the ui has exited already
+            return;
+        }
+        sleep(frame_duration).await;
+    }
+    display_control_sender
+        .send(ui_thread::ControlMsg::Blend(None))
+        .await
+        .ok();
+}
+
+/// Restore the camera exposure mode if locked during the countdown.
+async fn unlock_exposure(
+    capture_control_sender: &mpsc::Sender<capture_thread::Command>,
+    exposure_locked: &mut bool,
+) {
+    if std::mem::take(exposure_locked) {
+        capture_control_sender
+            .send(capture_thread::Command::UnlockExposure)
+            .await
+            .ok();
+    }
+}
+
+/// Pause after a shot of a `--session-shots` session, showing the progress.
+async fn session_pause(
+    display_control_sender: &mpsc::Sender<ui_thread::ControlMsg>,
+    trigger_control_sender: &mpsc::Sender<auto_trigger::ControlMsg>,
+    args: &args::Args,
+    shot: usize,
+) {
+    if args.session_shots <= 1 {
+        return;
+    }
+    let last_shot = shot >= args.session_shots;
+    let text = if last_shot {
+        format!("Session complete: {} shots", args.session_shots)
+    } else {
+        format!("Shot {} of {}", shot + 1, args.session_shots)
+    };
+    trigger_control_sender
+        .send(auto_trigger::ControlMsg::Stop)
+        .await
+        .ok();
+    display_control_sender
+        .send(ui_thread::ControlMsg::SetText(Some(text)))
+        .await
+        .ok();
+    sleep(args.session_interval).await;
+    display_control_sender
+        .send(ui_thread::ControlMsg::SetText(None))
+        .await
+        .ok();
+    if last_shot {
+        trigger_control_sender
+            .send(auto_trigger::ControlMsg::Run)
+            .await
+            .ok();
+    }
+}
+
+/// Show `status` in the window title, after the `--window-title` prefix.
+async fn set_window_title(
+    display_control_sender: &mpsc::Sender<ui_thread::ControlMsg>,
+    args: &args::Args,
+    status: &str,
+) {
+    display_control_sender
+        .send(ui_thread::ControlMsg::SetTitle(format!(
+            "{} – {}",
+            args.window_title, status
+        )))
+        .await
+        .ok();
+}
+
+/// Send the exit message after `max_runtime`, warning 5 minutes in advance.
+async fn shutdown_after(max_runtime: Duration, exit_sender: broadcast::Sender<bool>) {
+    const WARNING: Duration = Duration::from_secs(5 * 60);
+    if let Some(until_warning) = max_runtime.checked_sub(WARNING) {
+        sleep(until_warning).await;
+        warn!("Scheduled shutdown in 5 minutes");
+        sleep(WARNING).await;
+    } else {
+        sleep(max_runtime).await;
+    }
+    info!("maximum runtime reached");
+    exit_sender.send(true).ok();
+}
+
+/// Encode `snapshot` in the format given by the extension of `filename`, JPEG by default.
+fn encode_snapshot(snapshot: &Mat, filename: &Path, encode_params: &EncodeParams) -> Result<Bytes> {
+    let extension = filename
+        .extension()
+        .map_or_else(|| "jpg".into(), |e| e.to_string_lossy());
+    let format = OutputFormat::of(filename);
+    if format == OutputFormat::Jpeg {
+        return Ok(Bytes::from(SnapshotRepo::encode_frame(
+            snapshot,
+            encode_params.jpeg_quality,
+        )?));
+    }
+    let mut encoded = Vector::new();
+    imgcodecs::imencode(
+        &format!(".{}", extension),
+        snapshot,
+        &mut encoded,
+        &encode_params.for_format(format),
+    )?;
+    Ok(Bytes::from(encoded.to_vec()))
+}
+
+async fn capture_raw(
+    capture_control_sender: &mpsc::Sender<capture_thread::Command>,
+) -> Option<Mat> {
+    let (s, r) = oneshot::channel();
+    capture_control_sender
+        .send(capture_thread::Command::RawSnapshot(s))
+        .await
+        .ok();
+    r.await.ok().flatten()
+}
+
+async fn capture_snapshot(capture_control_sender: &mpsc::Sender<capture_thread::Command>) -> Mat {
+    let (s, r) = oneshot::channel();
+    capture_control_sender
+        .send(capture_thread::Command::Snapshot(s, Span::current()))
+        .await
+        .ok();
+    r.await.unwrap()
+}
+
+/// Retake a snapshot blurrier than `threshold` up to `--blur-retry-max` times, returning the
+/// sharpest snapshot taken.
+async fn sharpest_snapshot(
+    args: &args::Args,
+    capture_control_sender: &mpsc::Sender<capture_thread::Command>,
+    snapshot: Mat,
+    threshold: f64,
+) -> Mat {
+    let score = |snapshot: &Mat| {
+        sharpness::sharpness(snapshot).unwrap_or_else(|err| {
+            warn!(?err, "unable to measure snapshot sharpness");
+            0.
+        })
+    };
+    let mut sharpest = (score(&snapshot), snapshot);
+    for attempt in 1..=args.blur_retry_max {
+        if sharpest.0 >= threshold {
+            break;
+        }
+        debug!(attempt, sharpness = sharpest.0, "blurry snapshot, retrying");
+        sleep(args.blur_retry_delay).await;
+        let retry = capture_snapshot(capture_control_sender).await;
+        let retry_sharpness = score(&retry);
+        if retry_sharpness > sharpest.0 {
+            sharpest = (retry_sharpness, retry);
+        }
+    }
+    info!(sharpness = sharpest.0, threshold, "snapshot sharpness");
+    sharpest.1
+}
+
+/// What taking, saving and announcing snapshots needs, built once in `main`.
+struct SnapshotContext {
+    capture_control_sender: mpsc::Sender<capture_thread::Command>,
+    trigger_control_sender: mpsc::Sender<auto_trigger::ControlMsg>,
+    display_control_sender: mpsc::Sender<ui_thread::ControlMsg>,
+    repo: SharedSnapshotRepo,
+    /// Only set with `--require-face`.
+    face_detector: Option<Arc<Mutex<FaceDetector>>>,
+    webhook: Option<Webhook>,
+    snapshot_cache: SharedSnapshotCache,
+    zoom: SharedZoom,
+    db: Option<SharedSnapshotDb>,
+    chroma_key: Option<ChromaKey>,
+    csv_log: Option<CsvLog>,
+    printer: Option<Printer>,
+    phash_dedup: Option<PhashDedup>,
+    metrics: Option<SharedSnapshotMetrics>,
+    app_event_sender: AppEventSender,
+}
+
+impl SnapshotContext {
+    async fn save_snapshot(
+        &mut self,
+        args: &args::Args,
+        snapshot_blend_image: Option<AlphaImage>,
+        source: TriggerSource,
+    ) {
+        let Self {
+            capture_control_sender,
+            trigger_control_sender,
+            display_control_sender,
+            repo,
+            face_detector,
+            webhook,
+            snapshot_cache,
+            zoom,
+            db,
+            chroma_key,
+            csv_log,
+            printer,
+            phash_dedup,
+            metrics,
+            app_event_sender,
+        } = self;
+        if args.preview_only {
+            debug!(?source, "preview only, snapshot skipped");
+            return;
+        }
+        // correlates the capture, saving and notifications of the snapshot, across threads
+        let span = info_span!("snapshot", shot_number = repo.lock().unwrap().counter());
+        async {
+            info!(?source, "Taking snapshot");
+            let started = Instant::now();
+            let _ = trigger_control_sender
+                .send(auto_trigger::ControlMsg::Stop)
+                .await;
+
+            let mut snapshot = capture_snapshot(capture_control_sender).await;
+            if let Some(threshold) = args.blur_threshold {
+                snapshot =
+                    sharpest_snapshot(args, capture_control_sender, snapshot, threshold).await;
+            }
+            let captured = started.elapsed();
+            let raw = if args.save_raw {
+                capture_raw(capture_control_sender).await
+            } else {
+                None
+            };
+
+            if let Some(crop) = args.video.crop() {
+                match crop.apply(&snapshot) {
+                    Ok(cropped) => snapshot = cropped,
+                    Err(err) => warn!(?err, "unable to crop snapshot"),
+                }
+            }
+            let zoom = *zoom.lock().unwrap();
+            if !zoom.is_identity() {
+                match zoom.apply(&snapshot) {
+                    Ok(zoomed) => snapshot = zoomed,
+                    Err(err) => warn!(?err, "unable to zoom snapshot"),
+                }
+            }
+            if let Some(chroma_key) = chroma_key {
+                match chroma_key.apply(&snapshot) {
+                    Ok(keyed) => snapshot = keyed,
+                    Err(err) => warn!(?err, "unable to chroma key snapshot"),
+                }
+            }
+            if let Some(rotate_code) = args.video.rotate_code() {
+                let mut rotated = Mat::default();
+                match opencv::core::rotate(&snapshot, &mut rotated, rotate_code) {
+                    Ok(()) => snapshot = rotated,
+                    Err(err) => warn!(?err, "unable to rotate snapshot"),
+                }
+            }
+
+            if let Some(face_detector) = face_detector {
+                // the detection blocks for a while, keep it off the async workers
+                let face_detector = face_detector.clone();
+                let detected = tokio::task::spawn_blocking(move || {
+                    let face_found = face_detector.lock().unwrap().detect(&snapshot);
+                    (snapshot, face_found)
+                })
+                .await;
+                let face_found = match detected {
+                    Ok((detected_snapshot, face_found)) => {
+                        snapshot = detected_snapshot;
+                        face_found.unwrap_or_else(|err| {
+                            warn!(?err, "face detection failed");
+                            false
+                        })
+                    }
+                    Err(err) => {
+                        error!(?err, "face detection panicked, skipping");
+                        let _ = trigger_control_sender
+                            .send(auto_trigger::ControlMsg::Run)
+                            .await;
+                        return;
+                    }
+                };
+                if !face_found {
+                    warn!("no face in snapshot, skipping");
+                    set_window_title(display_control_sender, args, "Live").await;
+                    let _ = trigger_control_sender
+                        .send(auto_trigger::ControlMsg::Run)
+                        .await;
+                    return;
+                }
+            }
+
+            if let Some(phash_dedup) = phash_dedup {
+                let duplicate = phash_dedup.is_duplicate(&snapshot).unwrap_or_else(|err| {
+                    warn!(?err, "perceptual hashing failed");
+                    false
+                });
+                if duplicate {
+                    warn!("snapshot looks like a recent one, skipping");
+                    set_window_title(display_control_sender, args, "Live").await;
+                    let _ = trigger_control_sender
+                        .send(auto_trigger::ControlMsg::Run)
+                        .await;
+                    return;
+                }
+            }
+
+            display_control_sender
+                .send(ui_thread::ControlMsg::Blend(snapshot_blend_image))
+                .await
+                .ok();
+            display_control_sender
+                .send(ui_thread::ControlMsg::Freeze)
+                .await
+                .ok();
+            set_window_title(display_control_sender, args, "Frozen").await;
+            let saving = Instant::now();
+            let saved = match repo.lock().unwrap().save_frame(&snapshot, source) {
+                Ok(saved) => {
+                    if let Some(metrics) = metrics {
+                        metrics.lock().unwrap().record(SnapshotLatency {
+                            capture: captured,
+                            save: saving.elapsed(),
+                            total: started.elapsed(),
+                        });
+                    }
+                    saved
+                }
+                Err(err) => {
+                    error!(?err, "failed saving snapshot");
+                    app_event::publish(
+                        app_event_sender,
+                        AppEvent::Error {
+                            message: format!("{:#}", anyhow::Error::from(err)),
+                        },
+                    );
+                    Vec::new()
+                }
+            };
+            if args.pip_last_snapshot {
+                display_control_sender
+                    .send(ui_thread::ControlMsg::LastSnapshot(snapshot.clone()))
+                    .await
+                    .ok();
+            }
+            if let (Some(raw), Some(saved)) = (&raw, saved.first()) {
+                if let Err(err) = repo.lock().unwrap().save_raw(raw, &saved.record.path) {
+                    warn!(?err, "failed saving raw image");
+                }
+            }
+            for SavedImage { record, image } in saved {
+                let encoded = match encode_snapshot(&image, &record.path, &args.encode_params()) {
+                    Ok(encoded) => {
+                        snapshot_cache
+                            .lock()
+                            .unwrap()
+                            .insert(record.path.clone(), encoded.clone());
+                        Some(encoded)
+                    }
+                    Err(err) => {
+                        warn!(?err, "failed caching snapshot");
+                        None
+                    }
+                };
+                if let Some(webhook) = webhook {
+                    webhook.notify(&record.path, record.counter, encoded);
+                }
+                if let Some(db) = db {
+                    if let Err(err) = db
+                        .lock()
+                        .unwrap()
+                        .insert(&record, image.cols(), image.rows())
+                    {
+                        warn!(?err, "failed recording snapshot in database");
+                    }
+                }
+                if let Some(csv_log) = csv_log {
+                    if let Err(err) = csv_log.append(&record, image.cols(), image.rows()) {
+                        warn!(?err, "failed appending snapshot to csv log");
+                    }
+                }
+                if let Some(printer) = printer {
+                    printer.print(&record.path);
+                }
+                app_event::publish(
+                    app_event_sender,
+                    AppEvent::SnapshotSaved { path: record.path },
+                );
+            }
+
+            sleep(args.freeze).await;
+
+            info!("restarting video");
+            display_control_sender
+                .send(ui_thread::ControlMsg::Blend(None))
+                .await
+                .ok();
+            if let Some(outro_clip) = args.outro_clip.clone() {
+                let (s, r) = oneshot::channel();
+                display_control_sender
+                    .send(ui_thread::ControlMsg::ShowVideoClip(outro_clip, s))
+                    .await
+                    .ok();
+                r.await.ok();
+            }
+            display_control_sender
+                .send(ui_thread::ControlMsg::Live)
+                .await
+                .ok();
+            set_window_title(display_control_sender, args, "Live").await;
+            let _ = trigger_control_sender
+                .send(auto_trigger::ControlMsg::Run)
+                .await;
+            debug!("snapshot taken");
+        }
+        .instrument(span)
+        .await
+    }
+}
+
+#[test]
+fn short_countdown_ends_on_the_first_overlay() {
+    let overlays = ["1.png", "2.png", "3.png", "4.png", "5.png"];
+    // the overlay of step n is at index n - 1, so the last step shown is at index 0
+    let countdown = cycle_countdown(&overlays, 3);
+    assert_eq!(countdown, ["1.png", "2.png", "3.png"]);
+    assert_eq!(countdown.first(), Some(&"1.png"));
+    assert_eq!(
+        cycle_countdown(&overlays[..3], 5),
+        ["1.png", "2.png", "3.png", "2.png", "1.png"]
+    );
+}
+
+#[test]
+fn api_snapshots_count_toward_the_cap() {
+    let events = EventBus::new(4);
+    let mut receiver = events.subscribe();
+    let trigger_effects = TriggerEffects {
+        triggers_fired: TriggersFired::default(),
+        app_event_sender: events.sender(),
+        snapshot_sound: None,
+    };
+    trigger_effects.fire(TriggerSource::Keyboard);
+    assert!(!trigger_effects.triggers_fired.reached(Some(2)));
+    trigger_effects.fire(TriggerSource::Api);
+    assert!(trigger_effects.triggers_fired.reached(Some(2)));
+    assert!(!trigger_effects.triggers_fired.reached(None));
+    for _ in 0..2 {
+        assert!(matches!(receiver.try_recv(), Ok(AppEvent::TriggerFired)));
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use opencv::videoio;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::time::interval;
+use tracing::{debug, info, instrument, warn};
+
+use crate::capture_thread::Command;
+use crate::stats::SharedStats;
+
+const MONITOR_INTERVAL: Duration = Duration::from_secs(5);
+
+pub fn spawn(
+    exposure_range: (Option<f64>, Option<f64>),
+    capture_control_sender: mpsc::Sender<Command>,
+    stats: SharedStats,
+    exit_receiver: broadcast::Receiver<bool>,
+) -> tokio::task::JoinHandle<Result<()>> {
+    debug!("spawning camera monitor");
+    tokio::spawn(camera_monitor(
+        exposure_range,
+        capture_control_sender,
+        stats,
+        exit_receiver,
+    ))
+}
+
+#[instrument(skip(capture_control_sender, stats, exit_receiver))]
+async fn camera_monitor(
+    (exposure_min, exposure_max): (Option<f64>, Option<f64>),
+    capture_control_sender: mpsc::Sender<Command>,
+    stats: SharedStats,
+    mut exit_receiver: broadcast::Receiver<bool>,
+) -> Result<()> {
+    info!("camera monitor started");
+    let mut interval = interval(MONITOR_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = exit_receiver.recv() => {
+                debug!("exit received");
+                break;
+            }
+            _ = interval.tick() => {
+                let (s, r) = oneshot::channel();
+                if capture_control_sender
+                    .send(Command::GetProperty(videoio::CAP_PROP_EXPOSURE, s))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+                let exposure = match r.await {
+                    Ok(exposure) => exposure,
+                    Err(_) => continue,
+                };
+                debug!(exposure, "exposure read");
+                stats.lock().unwrap().exposure = Some(exposure);
+
+                if exposure_min.is_some_and(|min| exposure < min)
+                    || exposure_max.is_some_and(|max| exposure > max)
+                {
+                    warn!(exposure, ?exposure_min, ?exposure_max, "exposure out of range");
+                }
+            }
+        }
+    }
+    warn!("exit camera monitor");
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use anyhow::Result;
+use opencv::{prelude::*, videoio};
+use serde::Serialize;
+use tracing::debug;
+
+use crate::capture_thread::{open_device, CaptureBackend};
+use crate::snapshot_repo::OutputFormat;
+
+/// Devices probed before giving up, should the backend open any index.
+const MAX_DEVICES: i32 = 64;
+
+/// A video device, the format it opens with and its image settings.
+#[derive(Debug, Serialize)]
+pub struct CameraInfo {
+    pub device: i32,
+    pub backend: String,
+    pub width: f64,
+    pub height: f64,
+    pub fps: f64,
+    pub fourcc: String,
+    pub brightness: f64,
+    pub contrast: f64,
+    pub saturation: f64,
+    pub gain: f64,
+    pub exposure: f64,
+    pub auto_exposure: f64,
+}
+
+impl CameraInfo {
+    /// Read the `CAP_PROP_*` values of the opened `camera`.
+    pub fn read(device: i32, camera: &videoio::VideoCapture) -> Result<Self> {
+        Ok(Self {
+            device,
+            backend: camera.get_backend_name()?,
+            width: camera.get(videoio::CAP_PROP_FRAME_WIDTH)?,
+            height: camera.get(videoio::CAP_PROP_FRAME_HEIGHT)?,
+            fps: camera.get(videoio::CAP_PROP_FPS)?,
+            fourcc: fourcc_name(camera.get(videoio::CAP_PROP_FOURCC)?),
+            brightness: camera.get(videoio::CAP_PROP_BRIGHTNESS)?,
+            contrast: camera.get(videoio::CAP_PROP_CONTRAST)?,
+            saturation: camera.get(videoio::CAP_PROP_SATURATION)?,
+            gain: camera.get(videoio::CAP_PROP_GAIN)?,
+            exposure: camera.get(videoio::CAP_PROP_EXPOSURE)?,
+            auto_exposure: camera.get(videoio::CAP_PROP_AUTO_EXPOSURE)?,
+        })
+    }
+}
+
+/// The four characters of a `CAP_PROP_FOURCC` value.
+fn fourcc_name(fourcc: f64) -> String {
+    (fourcc as u32)
+        .to_le_bytes()
+        .iter()
+        .map(|&byte| char::from(byte))
+        .filter(|c| !c.is_control())
+        .collect()
+}
+
+/// Open the video devices from index 0 until one fails, reading the format of each.
+pub fn query(backend: CaptureBackend) -> Result<Vec<CameraInfo>> {
+    let mut cameras = Vec::new();
+    for device in 0..MAX_DEVICES {
+        let camera = match open_device(device, backend) {
+            Ok(camera) => camera,
+            Err(_) => break,
+        };
+        let info = CameraInfo::read(device, &camera)?;
+        debug!(?info, "camera found");
+        cameras.push(info);
+    }
+    Ok(cameras)
+}
+
+pub fn print(cameras: &[CameraInfo]) {
+    if cameras.is_empty() {
+        println!("no cameras found");
+    } else {
+        println!("device  width  height   fps");
+        for camera in cameras {
+            println!(
+                "{:>6}  {:>5}  {:>6}  {:>4.1}",
+                camera.device, camera.width, camera.height, camera.fps
+            );
+        }
+    }
+    for format in [OutputFormat::Avif, OutputFormat::Heic] {
+        let support = if format.is_supported() {
+            "supported"
+        } else {
+            "unavailable, saved as JPEG"
+        };
+        println!("{:?} output: {}", format, support);
+    }
+}
+
+#[test]
+fn fourcc_name_reads_characters() {
+    assert_eq!(fourcc_name(f64::from(0x4750_4a4d_u32)), "MJPG");
+    assert_eq!(fourcc_name(0.), "");
+}
@@ -1,100 +1,325 @@
 use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use opencv::{prelude::*, videoio};
+use opencv::{core, prelude::*, videoio};
+use serde::Serialize;
 use tokio::sync::{broadcast, mpsc, oneshot};
-use tracing::{debug, info, instrument, trace, warn};
+use tracing::{debug, info, instrument, trace, warn, Span};
 
 use crate::args::VideoParams;
+use crate::camera_query::CameraInfo;
+use crate::error::CameraBotError;
+use crate::image_adjust::ImageAdjustment;
+use crate::sharpness;
+use crate::stdin_input;
 
 pub enum Command {
-    Snapshot(oneshot::Sender<Mat>),
+    /// Capture a snapshot within the span of the snapshot being taken.
+    Snapshot(oneshot::Sender<Mat>, Span),
+    /// Capture a snapshot at the given resolution, then restore the video resolution.
+    SnapshotAtResolution {
+        width: u32,
+        height: u32,
+        reply: oneshot::Sender<Mat>,
+    },
+    /// Capture the raw sensor data, `None` if the camera doesn't support it.
+    RawSnapshot(oneshot::Sender<Option<Mat>>),
+    /// Read a `CAP_PROP_*` property from the camera.
+    GetProperty(i32, oneshot::Sender<f64>),
+    GetStats(oneshot::Sender<CaptureStats>),
+    /// Read the format and image settings of the camera.
+    GetInfo(oneshot::Sender<CameraInfo>),
+    /// Change the width of the video images, snapshots keep their width.
+    SetVideoWidth(u32),
+    /// Switch to manual exposure, keeping the current exposure.
+    LockExposure,
+    /// Restore the exposure mode from before [`Command::LockExposure`].
+    UnlockExposure,
 }
 
+/// Video capture API used to open the camera device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureBackend {
+    /// V4L2, falling back to GStreamer.
+    Auto,
+    V4l2,
+    GStreamer,
+    /// Let OpenCV choose.
+    Any,
+}
+
+impl CaptureBackend {
+    /// The `videoio::CAP_*` APIs to try, in order.
+    const fn apis(self) -> &'static [i32] {
+        match self {
+            Self::Auto => &[videoio::CAP_V4L2, videoio::CAP_GSTREAMER],
+            Self::V4l2 => &[videoio::CAP_V4L2],
+            Self::GStreamer => &[videoio::CAP_GSTREAMER],
+            Self::Any => &[videoio::CAP_ANY],
+        }
+    }
+}
+
+/// Open the camera `device` with the first API of `backend` able to.
+pub fn open_device(device: i32, backend: CaptureBackend) -> Result<videoio::VideoCapture> {
+    for &api in backend.apis() {
+        let camera = videoio::VideoCapture::new(device, api)?;
+        if camera.is_opened()? {
+            debug!(device, api, "camera opened");
+            return Ok(camera);
+        }
+        debug!(device, api, "unable to open camera with this api");
+    }
+    anyhow::bail!("Unable to open camera {} using {:?}", device, backend)
+}
+
+/// Open the camera or gstreamer pipeline of `video_params`, at the requested size and rate.
+pub fn open_camera(video_params: &VideoParams) -> Result<videoio::VideoCapture> {
+    debug!("opening camera");
+    let camera = match &video_params.gst_pipeline {
+        Some(pipeline) => {
+            let ignored = video_params.ignored_by_pipeline();
+            if !ignored.is_empty() {
+                warn!(
+                    ?ignored,
+                    "flags are ignored when using a gstreamer pipeline"
+                );
+            }
+            videoio::VideoCapture::from_file(pipeline, videoio::CAP_GSTREAMER)?
+        }
+        None => {
+            let mut camera = open_device(video_params.device, video_params.capture_backend)?;
+
+            //camera.set(videoio::CAP_PROP_FOURCC, f64::from(videoio::VideoWriter::fourcc(b'M' as i8, b'J' as i8, b'P' as i8, b'G' as i8).unwrap()))?;
+            camera.set(
+                videoio::CAP_PROP_FRAME_WIDTH,
+                f64::from(video_params.video_width),
+            )?;
+            if let Some(height) = video_params.video_height {
+                camera.set(videoio::CAP_PROP_FRAME_HEIGHT, f64::from(height))?;
+            }
+            camera.set(
+                videoio::CAP_PROP_XI_FRAMERATE,
+                f64::from(video_params.frame_rate),
+            )?;
+            camera
+        }
+    };
+
+    if !videoio::VideoCapture::is_opened(&camera)? {
+        anyhow::bail!("Unable to open default camera!");
+    }
+    Ok(camera)
+}
+
+/// Enable or disable the autofocus, setting the fixed `focus` when disabled.
+fn set_focus(camera: &mut videoio::VideoCapture, autofocus: bool, focus: Option<u8>) -> Result<()> {
+    debug!(autofocus, ?focus, "setting focus");
+    if !camera.set(videoio::CAP_PROP_AUTOFOCUS, if autofocus { 1. } else { 0. })? {
+        warn!(autofocus, "camera ignored the autofocus setting");
+    }
+    match focus {
+        Some(focus) if autofocus => warn!(focus, "--focus is ignored with autofocus enabled"),
+        Some(focus) if !camera.set(videoio::CAP_PROP_FOCUS, f64::from(focus))? => {
+            warn!(focus, "camera ignored the focus setting");
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Statistics of the frames captured since the capture thread started.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CaptureStats {
+    pub frames_captured: u64,
+    /// Frames overwritten before all receivers had seen them.
+    pub frames_dropped: u64,
+    pub mean_frame_interval_ms: f32,
+}
+
+/// Spawn the capture thread.
+///
+/// Returns the part of the requested image adjustment the camera couldn't apply, and which
+/// must be applied in software instead.
 pub async fn spawn(
     video_params: VideoParams,
     command_receiver: mpsc::Receiver<Command>,
     capture_event_sender: broadcast::Sender<Mat>,
     exit_receiver: broadcast::Receiver<bool>,
-) -> Result<thread::JoinHandle<()>> {
+) -> Result<(thread::JoinHandle<()>, ImageAdjustment), CameraBotError> {
     debug!("spawning capture thread");
     let (mut s, mut r) = mpsc::channel(1);
     let joinhandle = thread::spawn(move || {
-        if frame_grabber(
+        if let Err(err) = frame_grabber(
             &mut s,
             video_params,
             command_receiver,
             capture_event_sender,
             exit_receiver,
-        )
-        .is_err()
-        {
-            s.blocking_send(false).ok();
+        ) {
+            warn!(?err, "capture thread failed");
+            s.blocking_send(None).ok();
         }
     });
 
     match r.recv().await.unwrap() {
-        true => Ok(joinhandle),
-        false => anyhow::bail!("failed to start capture-thread"),
+        Some(software_adjustment) => Ok((joinhandle, software_adjustment)),
+        None => Err(CameraBotError::CameraOpen(
+            "failed to start capture-thread".to_string(),
+        )),
     }
 }
 
 #[instrument(skip_all)]
 fn frame_grabber(
-    start_sender: &mut mpsc::Sender<bool>,
+    start_sender: &mut mpsc::Sender<Option<ImageAdjustment>>,
     video_params: VideoParams,
     mut command_receiver: mpsc::Receiver<Command>,
     frame_event_sender: broadcast::Sender<Mat>,
     mut exit_receiver: broadcast::Receiver<bool>,
 ) -> Result<()> {
     info!("capture thread started");
+    if let Some(input) = video_params.stdin_input() {
+        return stdin_input::frame_grabber(
+            input,
+            start_sender,
+            video_params,
+            command_receiver,
+            frame_event_sender,
+            exit_receiver,
+        );
+    }
 
-    debug!("opening camera");
-    let mut camera = videoio::VideoCapture::new(video_params.device, videoio::CAP_GSTREAMER)?;
-
-    //camera.set(videoio::CAP_PROP_FOURCC, f64::from(videoio::VideoWriter::fourcc(b'M' as i8, b'J' as i8, b'P' as i8, b'G' as i8).unwrap()))?;
-    camera.set(
-        videoio::CAP_PROP_FRAME_WIDTH,
-        f64::from(video_params.video_width),
-    )?;
-    camera.set(
-        videoio::CAP_PROP_XI_FRAMERATE,
-        f64::from(video_params.frame_rate),
-    )?;
+    let mut camera = open_camera(&video_params)?;
+    if video_params.gst_pipeline.is_none() {
+        set_focus(&mut camera, video_params.autofocus, video_params.focus)?;
+    }
 
-    if !videoio::VideoCapture::is_opened(&camera)? {
-        anyhow::bail!("Unable to open default camera!");
+    let software_adjustment = set_adjustment(&mut camera, video_params.adjustment());
+
+    let mut frame = Mat::default();
+    for _ in 0..video_params.warmup_frames {
+        camera.read(&mut frame)?;
     }
+    debug!(
+        frames = video_params.warmup_frames,
+        "warm-up frames discarded"
+    );
 
-    start_sender.blocking_send(true).ok();
+    start_sender.blocking_send(Some(software_adjustment)).ok();
     debug!("entering camera capture loop");
 
-    let mut frame = Mat::default();
+    let mut video_width = video_params.video_width;
+    // auto exposure mode to restore when unlocking the exposure
+    let mut locked_auto_exposure: Option<f64> = None;
+    let mut publisher = FramePublisher::new(frame_event_sender, &video_params);
     loop {
         camera.read(&mut frame)?;
-        if !frame.empty() {
-            trace!(?frame, "image captured");
-            if frame_event_sender.send(frame.clone()).is_err() {
-                info!("all receivers has left");
-                break;
-            }
+        if !frame.empty() && !publisher.publish(&frame)? {
+            info!("all receivers has left");
+            break;
         }
         if let Ok(command) = command_receiver.try_recv() {
-            let Command::Snapshot(sender) = command;
+            match command {
+                Command::Snapshot(sender, span) if video_params.gst_pipeline.is_some() => {
+                    let _span = span.enter();
+                    let snapshot = read_sharpest(&mut camera, video_params.best_of)?;
+                    sender.send(snapshot).ok();
+                }
+                Command::Snapshot(sender, span) => {
+                    let _span = span.enter();
+                    // the video height to restore, read as it may be chosen by the camera
+                    let video_height = match video_params.snapshot_height {
+                        Some(height) => {
+                            let video_height = camera.get(videoio::CAP_PROP_FRAME_HEIGHT)?;
+                            camera.set(videoio::CAP_PROP_FRAME_HEIGHT, f64::from(height))?;
+                            Some(video_height)
+                        }
+                        None => None,
+                    };
+                    camera.set(
+                        videoio::CAP_PROP_FRAME_WIDTH,
+                        f64::from(video_params.snapshot_width),
+                    )?;
 
-            camera.set(
-                videoio::CAP_PROP_FRAME_WIDTH,
-                f64::from(video_params.snapshot_width),
-            )?;
+                    let snapshot = read_sharpest(&mut camera, video_params.best_of)?;
+                    sender.send(snapshot).ok();
 
-            let mut snapshot = Mat::default();
-            camera.read(&mut snapshot)?;
-            sender.send(snapshot).ok();
+                    camera.set(videoio::CAP_PROP_FRAME_WIDTH, f64::from(video_width))?;
+                    if let Some(video_height) = video_height {
+                        camera.set(videoio::CAP_PROP_FRAME_HEIGHT, video_height)?;
+                    }
+                }
+                Command::SnapshotAtResolution { reply, .. }
+                    if video_params.gst_pipeline.is_some() =>
+                {
+                    warn!("resolution can't be changed when using a gstreamer pipeline");
+                    reply
+                        .send(read_sharpest(&mut camera, video_params.best_of)?)
+                        .ok();
+                }
+                Command::SnapshotAtResolution {
+                    width,
+                    height,
+                    reply,
+                } => {
+                    // the video height is read, as it may be chosen by the camera
+                    let video_height = camera.get(videoio::CAP_PROP_FRAME_HEIGHT)?;
+                    camera.set(videoio::CAP_PROP_FRAME_WIDTH, f64::from(width))?;
+                    camera.set(videoio::CAP_PROP_FRAME_HEIGHT, f64::from(height))?;
 
-            camera.set(
-                videoio::CAP_PROP_FRAME_WIDTH,
-                f64::from(video_params.video_width),
-            )?;
+                    let snapshot = read_sharpest(&mut camera, video_params.best_of)?;
+                    reply.send(snapshot).ok();
+
+                    camera.set(videoio::CAP_PROP_FRAME_WIDTH, f64::from(video_width))?;
+                    camera.set(videoio::CAP_PROP_FRAME_HEIGHT, video_height)?;
+                }
+                Command::RawSnapshot(sender) if video_params.gst_pipeline.is_some() => {
+                    sender.send(None).ok();
+                }
+                Command::RawSnapshot(sender) => {
+                    sender.send(read_raw(&mut camera)).ok();
+                }
+                Command::GetProperty(property, sender) => match camera.get(property) {
+                    Ok(value) => {
+                        sender.send(value).ok();
+                    }
+                    Err(err) => warn!(?err, property, "failed reading camera property"),
+                },
+                Command::GetStats(sender) => {
+                    sender.send(publisher.stats()).ok();
+                }
+                Command::GetInfo(sender) => match CameraInfo::read(video_params.device, &camera) {
+                    Ok(info) => {
+                        sender.send(info).ok();
+                    }
+                    Err(err) => warn!(?err, "failed reading camera info"),
+                },
+                Command::SetVideoWidth(_) if video_params.gst_pipeline.is_some() => {
+                    warn!("video width can't be changed when using a gstreamer pipeline");
+                }
+                Command::SetVideoWidth(width) => {
+                    video_width = width;
+                    camera.set(videoio::CAP_PROP_FRAME_WIDTH, f64::from(video_width))?;
+                }
+                Command::LockExposure if locked_auto_exposure.is_none() => {
+                    match lock_exposure(&mut camera) {
+                        Ok(auto_exposure) => locked_auto_exposure = Some(auto_exposure),
+                        Err(err) => warn!(?err, "unable to lock the exposure"),
+                    }
+                }
+                Command::LockExposure => {}
+                Command::UnlockExposure => {
+                    if let Some(auto_exposure) = locked_auto_exposure.take() {
+                        debug!(auto_exposure, "unlocking exposure");
+                        if let Err(err) = camera.set(videoio::CAP_PROP_AUTO_EXPOSURE, auto_exposure)
+                        {
+                            warn!(?err, "unable to unlock the exposure");
+                        }
+                    }
+                }
+            }
         }
         if exit_receiver.try_recv().is_ok() {
             info!("exit received");
@@ -105,3 +330,190 @@ fn frame_grabber(
     warn!("exiting");
     Ok(())
 }
+
+/// Broadcasts the captured frames, skipping duplicates and keeping the statistics.
+pub struct FramePublisher {
+    sender: broadcast::Sender<Mat>,
+    dedup_threshold: f64,
+    max_queue_depth: usize,
+    previous_frame: Mat,
+    stats: CaptureStats,
+    last_frame: Option<Instant>,
+    total_frame_interval: Duration,
+    broadcast_interval: Duration,
+    /// Frames captured before this aren't broadcast.
+    next_broadcast: Instant,
+}
+
+impl FramePublisher {
+    pub fn new(sender: broadcast::Sender<Mat>, video_params: &VideoParams) -> Self {
+        Self {
+            sender,
+            dedup_threshold: video_params.frame_dedup_threshold,
+            max_queue_depth: video_params.max_capture_queue_depth,
+            previous_frame: Mat::default(),
+            stats: CaptureStats::default(),
+            last_frame: None,
+            total_frame_interval: Duration::ZERO,
+            broadcast_interval: Duration::from_secs_f32(
+                1. / video_params
+                    .broadcast_fps
+                    .unwrap_or(video_params.frame_rate as f32)
+                    .max(1.),
+            ),
+            next_broadcast: Instant::now(),
+        }
+    }
+
+    /// Broadcast `frame` unless it's a duplicate, returning `false` once all receivers have left.
+    pub fn publish(&mut self, frame: &Mat) -> Result<bool> {
+        trace!(?frame, "image captured");
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            self.total_frame_interval += now - last_frame;
+        }
+        self.last_frame = Some(now);
+        self.stats.frames_captured += 1;
+        let duplicate = self.dedup_threshold > 0.
+            && match frame_difference(frame, &self.previous_frame) {
+                Some(difference) if difference < self.dedup_threshold => {
+                    trace!(difference, "duplicate frame skipped");
+                    true
+                }
+                _ => {
+                    frame.copy_to(&mut self.previous_frame)?;
+                    false
+                }
+            };
+        if duplicate {
+            return Ok(true);
+        }
+        // with some slack, so jitter doesn't skip frames captured at the broadcast rate
+        if now + self.broadcast_interval / 4 < self.next_broadcast {
+            trace!("frame skipped, limited by the broadcast rate");
+            return Ok(true);
+        }
+        self.next_broadcast = (self.next_broadcast + self.broadcast_interval).max(now);
+        if self.sender.len() >= self.max_queue_depth {
+            debug!(
+                depth = self.max_queue_depth,
+                "capture queue full, dropping oldest frame"
+            );
+            self.stats.frames_dropped += 1;
+        }
+        Ok(self.sender.send(frame.clone()).is_ok())
+    }
+
+    pub fn stats(&mut self) -> CaptureStats {
+        if self.stats.frames_captured > 1 {
+            self.stats.mean_frame_interval_ms = self.total_frame_interval.as_secs_f32() * 1000.
+                / (self.stats.frames_captured - 1) as f32;
+        }
+        self.stats
+    }
+}
+
+/// Read `frames` frames in rapid succession, returning the sharpest.
+fn read_sharpest(camera: &mut videoio::VideoCapture, frames: usize) -> Result<Mat> {
+    let mut snapshot = Mat::default();
+    camera.read(&mut snapshot)?;
+    if frames <= 1 {
+        return Ok(snapshot);
+    }
+    // e.g. empty frames score 0
+    let score = |frame: &Mat| sharpness::sharpness(frame).unwrap_or_default();
+    let mut best = score(&snapshot);
+    for _ in 1..frames {
+        let mut frame = Mat::default();
+        camera.read(&mut frame)?;
+        let frame_score = score(&frame);
+        if frame_score > best {
+            best = frame_score;
+            snapshot = frame;
+        }
+    }
+    debug!(frames, sharpness = best, "sharpest of the frames");
+    Ok(snapshot)
+}
+
+/// Read a frame without converting it to RGB, e.g. Bayer data, restoring the format afterwards.
+fn read_raw(camera: &mut videoio::VideoCapture) -> Option<Mat> {
+    let format = camera.get(videoio::CAP_PROP_FORMAT).ok()?;
+    // -1 disables the RGB conversion with the V4L2 backend
+    if !camera.set(videoio::CAP_PROP_FORMAT, -1.).unwrap_or(false) {
+        debug!("raw capture not supported by the camera");
+        return None;
+    }
+    let mut raw = Mat::default();
+    let read = camera.read(&mut raw);
+    camera.set(videoio::CAP_PROP_FORMAT, format).ok();
+    match read {
+        Ok(true) if !raw.empty() => Some(raw),
+        _ => {
+            warn!("failed reading raw frame");
+            None
+        }
+    }
+}
+
+/// Switch the camera to manual exposure at its current exposure, returning the previous auto
+/// exposure mode.
+fn lock_exposure(camera: &mut videoio::VideoCapture) -> Result<f64> {
+    let auto_exposure = camera.get(videoio::CAP_PROP_AUTO_EXPOSURE)?;
+    let exposure = camera.get(videoio::CAP_PROP_EXPOSURE)?;
+    debug!(auto_exposure, exposure, "locking exposure");
+    // 0.25 selects manual exposure with the V4L2 backend
+    camera.set(videoio::CAP_PROP_AUTO_EXPOSURE, 0.25)?;
+    camera.set(videoio::CAP_PROP_EXPOSURE, exposure)?;
+    Ok(auto_exposure)
+}
+
+/// Mean absolute difference per pixel channel, `None` if the frames can't be compared.
+fn frame_difference(frame: &Mat, previous_frame: &Mat) -> Option<f64> {
+    if frame.size().ok()? != previous_frame.size().ok()? || frame.typ() != previous_frame.typ() {
+        return None;
+    }
+    let elements = frame.total() * frame.channels() as usize;
+    let norm = core::norm2(frame, previous_frame, core::NORM_L1, &Mat::default()).ok()?;
+    Some(norm / elements.max(1) as f64)
+}
+
+/// Set the image adjustment on the camera, returning the adjustment not supported by the camera.
+fn set_adjustment(
+    camera: &mut videoio::VideoCapture,
+    adjustment: ImageAdjustment,
+) -> ImageAdjustment {
+    let default = ImageAdjustment::default();
+    let mut set = |property, value: f32, default_value: f32| {
+        if value == default_value {
+            return default_value;
+        }
+        match camera.set(property, f64::from(value)) {
+            Ok(true) => default_value,
+            _ => {
+                warn!(
+                    property,
+                    value, "camera property not supported, adjusting in software"
+                );
+                value
+            }
+        }
+    };
+    ImageAdjustment {
+        brightness: set(
+            videoio::CAP_PROP_BRIGHTNESS,
+            adjustment.brightness,
+            default.brightness,
+        ),
+        contrast: set(
+            videoio::CAP_PROP_CONTRAST,
+            adjustment.contrast,
+            default.contrast,
+        ),
+        saturation: set(
+            videoio::CAP_PROP_SATURATION,
+            adjustment.saturation,
+            default.saturation,
+        ),
+    }
+}
@@ -0,0 +1,206 @@
+use std::path::Path;
+
+use anyhow::Result;
+use opencv::{
+    core::{self, Point, Scalar, Size},
+    imgcodecs, imgproc,
+    prelude::*,
+};
+
+/// Pixels less saturated or darker than this have no reliable hue and are never keyed.
+const MIN_SATURATION: f64 = 50.;
+const MIN_VALUE: f64 = 50.;
+/// Pixels differing less than this in gray level from the reference frame are background.
+const REFERENCE_THRESHOLD: f64 = 30.;
+
+/// Replaces pixels of a key color (e.g. a green screen) by a background image.
+#[derive(Debug, Clone)]
+pub struct ChromaKey {
+    key: Key,
+    background: Mat,
+    /// `background` resized to the size of the last frame.
+    resized_background: Mat,
+}
+
+/// How the pixels to replace are found.
+#[derive(Debug, Clone)]
+enum Key {
+    Color {
+        /// Hue of the key color, in OpenCV units (0-180).
+        hue: f64,
+        /// Allowed hue deviation, in OpenCV units.
+        tolerance: f64,
+    },
+    /// Pixels looking like a frame of the empty background.
+    Reference {
+        reference: Mat,
+        /// `reference` resized to the size of the last frame.
+        resized_reference: Mat,
+    },
+}
+
+impl ChromaKey {
+    /// Key the hex color `color` (e.g. `00ff00`), allowing the hue to deviate `tolerance` degrees.
+    pub fn new(color: &str, tolerance: f32, background: &Path) -> Result<Self> {
+        let (red, green, blue) = parse_hex_color(color)?;
+        Ok(Self {
+            key: Key::Color {
+                hue: hue(red, green, blue) / 2.,
+                tolerance: f64::from(tolerance) / 2.,
+            },
+            background: read_image(background)?,
+            resized_background: Mat::default(),
+        })
+    }
+
+    /// Key the pixels of frames looking like the `reference` frame of the empty background, a
+    /// virtual green screen for setups with controlled lighting.
+    pub fn with_reference(reference: &Path, background: &Path) -> Result<Self> {
+        Ok(Self {
+            key: Key::Reference {
+                reference: read_image(reference)?,
+                resized_reference: Mat::default(),
+            },
+            background: read_image(background)?,
+            resized_background: Mat::default(),
+        })
+    }
+
+    /// Replace the keyed pixels of the 8 bit BGR `frame` by the background.
+    pub fn apply(&mut self, frame: &Mat) -> Result<Mat> {
+        let size = frame.size()?;
+        if self.resized_background.size()? != size {
+            imgproc::resize(
+                &self.background,
+                &mut self.resized_background,
+                size,
+                0.,
+                0.,
+                imgproc::INTER_LINEAR,
+            )?;
+        }
+
+        let mask = match &mut self.key {
+            Key::Color { hue, tolerance } => {
+                let mut hsv = Mat::default();
+                imgproc::cvt_color(frame, &mut hsv, imgproc::COLOR_BGR2HSV, 0)?;
+                hue_mask(&hsv, size, *hue, *tolerance)?
+            }
+            Key::Reference {
+                reference,
+                resized_reference,
+            } => {
+                if resized_reference.size()? != size {
+                    imgproc::resize(
+                        reference,
+                        resized_reference,
+                        size,
+                        0.,
+                        0.,
+                        imgproc::INTER_LINEAR,
+                    )?;
+                }
+                reference_mask(frame, resized_reference)?
+            }
+        };
+
+        let mut keyed = frame.clone();
+        self.resized_background.copy_to_masked(&mut keyed, &mask)?;
+        Ok(keyed)
+    }
+}
+
+/// Mask of the pixels of `frame` close to the `reference` frame, with speckles removed.
+fn reference_mask(frame: &Mat, reference: &Mat) -> Result<Mat> {
+    let mut diff = Mat::default();
+    core::absdiff(frame, reference, &mut diff)?;
+    let mut gray = Mat::default();
+    imgproc::cvt_color(&diff, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?;
+    let mut mask = Mat::default();
+    imgproc::threshold(
+        &gray,
+        &mut mask,
+        REFERENCE_THRESHOLD,
+        255.,
+        imgproc::THRESH_BINARY_INV,
+    )?;
+    let kernel = imgproc::get_structuring_element(
+        imgproc::MORPH_ELLIPSE,
+        Size::new(5, 5),
+        Point::new(-1, -1),
+    )?;
+    let mut opened = Mat::default();
+    imgproc::morphology_ex(
+        &mask,
+        &mut opened,
+        imgproc::MORPH_OPEN,
+        &kernel,
+        Point::new(-1, -1),
+        1,
+        core::BORDER_CONSTANT,
+        imgproc::morphology_default_border_value()?,
+    )?;
+    Ok(opened)
+}
+
+/// Mask of the pixels within the hue range, wrapping around the ends of the hue circle.
+fn hue_mask(hsv: &Mat, size: Size, hue: f64, tolerance: f64) -> Result<Mat> {
+    let (low, high) = (hue - tolerance, hue + tolerance);
+    let mut ranges = vec![(low.max(0.), high.min(180.))];
+    if low < 0. {
+        ranges.push((low + 180., 180.));
+    }
+    if high > 180. {
+        ranges.push((0., high - 180.));
+    }
+
+    let mut mask = Mat::new_size_with_default(size, core::CV_8U, Scalar::all(0.))?;
+    for (low, high) in ranges {
+        let mut range_mask = Mat::default();
+        core::in_range(
+            hsv,
+            &Scalar::new(low, MIN_SATURATION, MIN_VALUE, 0.),
+            &Scalar::new(high, 255., 255., 0.),
+            &mut range_mask,
+        )?;
+        let mut combined = Mat::default();
+        core::bitwise_or(&mask, &range_mask, &mut combined, &Mat::default())?;
+        mask = combined;
+    }
+    Ok(mask)
+}
+
+fn read_image(path: &Path) -> Result<Mat> {
+    let image = imgcodecs::imread(&path.display().to_string(), imgcodecs::IMREAD_COLOR)?;
+    if image.empty() {
+        anyhow::bail!("unable to read image {}", path.display());
+    }
+    Ok(image)
+}
+
+pub(crate) fn parse_hex_color(color: &str) -> Result<(u8, u8, u8)> {
+    let hex = color.trim_start_matches('#');
+    if hex.len() != 6 {
+        anyhow::bail!("color must be 6 hex digits, e.g. 00ff00: {}", color);
+    }
+    let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16);
+    Ok((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Hue of the RGB color in degrees (0-360).
+fn hue(red: u8, green: u8, blue: u8) -> f64 {
+    let (red, green, blue) = (f64::from(red), f64::from(green), f64::from(blue));
+    let max = red.max(green).max(blue);
+    let delta = max - red.min(green).min(blue);
+    if delta == 0. {
+        return 0.;
+    }
+    let hue = if max == red {
+        60. * ((green - blue) / delta)
+    } else if max == green {
+        60. * ((blue - red) / delta + 2.)
+    } else {
+        60. * ((red - green) / delta + 4.)
+    };
+    hue.rem_euclid(360.)
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+
+use crate::snapshot_repo::SnapshotMeta;
+use crate::stats::Stats;
+
+/// Client of the REST API of a running camera-bot.
+#[derive(Debug, Clone)]
+pub struct Client {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl Client {
+    /// Client of the camera-bot at `base_url`, e.g. `http://localhost:8080`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Trigger a snapshot, as `GET /trigger`.
+    pub async fn trigger(&self) -> Result<()> {
+        self.client
+            .get(self.url("/trigger"))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Runtime statistics, as `GET /stats`.
+    pub async fn get_stats(&self) -> Result<Stats> {
+        Ok(self
+            .client
+            .get(self.url("/stats"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// The snapshots in the output folder, as `GET /snapshots`.
+    pub async fn list_snapshots(&self) -> Result<Vec<SnapshotMeta>> {
+        Ok(self
+            .client
+            .get(self.url("/snapshots"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+}
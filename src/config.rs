@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use clap::{ArgMatches, Command};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Settings read from a TOML config file.
+///
+/// Keys mirror the long command line flags, e.g. `video-width = 1280` or `fullscreen = true`.
+/// Tables are flattened, so keys may be grouped in sections like `[trigger]`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Config {
+    values: BTreeMap<String, toml::Value>,
+}
+
+impl Config {
+    /// Load the config file at `path`.
+    ///
+    /// A missing file is not an error and results in `None`, but a malformed one is.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            debug!(?path, "no config file");
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed reading config file {}", path.display()))?;
+        let config = toml::from_str(&content)
+            .with_context(|| format!("malformed config file {}", path.display()))?;
+        debug!(?path, ?config, "config file loaded");
+        Ok(Some(config))
+    }
+
+    /// The values of the flags of `command` in `matches`, as strings as they'd be given on the
+    /// command line.
+    pub fn from_matches(command: &Command, matches: &ArgMatches) -> Self {
+        let mut values = BTreeMap::new();
+        for arg in command.get_arguments() {
+            let long = match arg.get_long() {
+                Some("help" | "version" | "config" | "print-config") | None => continue,
+                Some(long) => long,
+            };
+            let value = if !arg.is_takes_value_set() {
+                toml::Value::Boolean(matches.is_present(arg.get_id()))
+            } else {
+                let raw: Vec<_> = match matches.get_raw(arg.get_id()) {
+                    Some(raw) => raw
+                        .map(|value| toml::Value::String(value.to_string_lossy().into_owned()))
+                        .collect(),
+                    None => continue,
+                };
+                if arg.is_multiple_occurrences_set() {
+                    toml::Value::Array(raw)
+                } else {
+                    match raw.into_iter().next() {
+                        Some(value) => value,
+                        None => continue,
+                    }
+                }
+            };
+            values.insert(long.to_string(), value);
+        }
+        Self { values }
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Convert the settings into command line arguments, skipping those rejected by `filter`.
+    ///
+    /// `filter` is called with the long flag name, e.g. `video-width`.
+    pub fn to_args(&self, filter: impl Fn(&str) -> bool) -> Result<Vec<OsString>> {
+        let mut args = Vec::new();
+        push_args(&self.values, &filter, &mut args)?;
+        Ok(args)
+    }
+}
+
+fn push_args(
+    values: &BTreeMap<String, toml::Value>,
+    filter: &impl Fn(&str) -> bool,
+    args: &mut Vec<OsString>,
+) -> Result<()> {
+    for (key, value) in values {
+        if let toml::Value::Table(table) = value {
+            let table = table.clone().into_iter().collect();
+            push_args(&table, filter, args)?;
+            continue;
+        }
+        let long = key.replace('_', "-");
+        if !filter(&long) {
+            continue;
+        }
+        let flag = format!("--{}", long);
+        match value {
+            toml::Value::Boolean(true) => args.push(flag.into()),
+            toml::Value::Boolean(false) => {}
+            toml::Value::Array(values) => {
+                for value in values {
+                    args.push(flag.clone().into());
+                    args.push(value_to_arg(key, value)?);
+                }
+            }
+            value => {
+                args.push(flag.into());
+                args.push(value_to_arg(key, value)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn value_to_arg(key: &str, value: &toml::Value) -> Result<OsString> {
+    Ok(match value {
+        toml::Value::String(s) => s.into(),
+        toml::Value::Integer(i) => i.to_string().into(),
+        toml::Value::Float(f) => f.to_string().into(),
+        toml::Value::Boolean(b) => b.to_string().into(),
+        toml::Value::Datetime(d) => d.to_string().into(),
+        toml::Value::Array(_) | toml::Value::Table(_) => {
+            anyhow::bail!("unsupported value for config key {}", key)
+        }
+    })
+}
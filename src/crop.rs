@@ -0,0 +1,64 @@
+use anyhow::Result;
+use opencv::{
+    core::{Rect, Size},
+    prelude::{Mat, MatTraitConst, MatTraitConstManual},
+};
+
+/// Region of the camera frame to show and save.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crop {
+    /// Region in pixels of a frame `video_width` wide.
+    pub rect: Rect,
+    pub video_width: u32,
+}
+
+impl Crop {
+    /// Crop the region from `frame`, scaled to the width of `frame`, e.g. for snapshots.
+    pub fn apply(&self, frame: &Mat) -> Result<Mat> {
+        let mut cropped = Mat::default();
+        Mat::roi(frame, self.scaled_rect(frame.size()?))?.copy_to(&mut cropped)?;
+        Ok(cropped)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn scaled_rect(&self, size: Size) -> Rect {
+        let scale = f64::from(size.width) / f64::from(self.video_width.max(1));
+        let scaled = |value: i32| (f64::from(value) * scale).round() as i32;
+        let x = scaled(self.rect.x).clamp(0, size.width - 1);
+        let y = scaled(self.rect.y).clamp(0, size.height - 1);
+        Rect::new(
+            x,
+            y,
+            scaled(self.rect.width).clamp(1, size.width - x),
+            scaled(self.rect.height).clamp(1, size.height - y),
+        )
+    }
+}
+
+#[test]
+fn crop_is_scaled_to_frame_width() {
+    let crop = Crop {
+        rect: Rect::new(100, 50, 800, 600),
+        video_width: 1000,
+    };
+    assert_eq!(
+        crop.scaled_rect(Size::new(2000, 1500)),
+        Rect::new(200, 100, 1600, 1200)
+    );
+    assert_eq!(
+        crop.scaled_rect(Size::new(500, 375)),
+        Rect::new(50, 25, 400, 300)
+    );
+}
+
+#[test]
+fn crop_is_clipped_to_frame() {
+    let crop = Crop {
+        rect: Rect::new(900, 0, 800, 600),
+        video_width: 1000,
+    };
+    assert_eq!(
+        crop.scaled_rect(Size::new(1000, 750)),
+        Rect::new(900, 0, 100, 600)
+    );
+}
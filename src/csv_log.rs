@@ -0,0 +1,58 @@
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::Serialize;
+use tracing::{debug, instrument};
+
+use crate::snapshot_repo::{SnapshotRecord, TriggerSource};
+
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    timestamp: String,
+    path: &'a str,
+    counter: usize,
+    camera_name: &'a str,
+    trigger_source: TriggerSource,
+    file_size_bytes: u64,
+    width: i32,
+    height: i32,
+}
+
+/// CSV file with a row for every saved snapshot.
+#[derive(Debug)]
+pub struct CsvLog {
+    path: PathBuf,
+}
+
+impl CsvLog {
+    pub const fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Append a row for `record`, writing the header first when the file is new.
+    #[instrument(skip(self))]
+    pub fn append(&self, record: &SnapshotRecord, width: i32, height: i32) -> Result<()> {
+        let is_new = !self.path.exists();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(is_new)
+            .from_writer(file);
+        writer.serialize(CsvRow {
+            timestamp: record.saved_at.to_rfc3339(),
+            path: &record.path.display().to_string(),
+            counter: record.counter,
+            camera_name: &record.camera_name,
+            trigger_source: record.trigger_source,
+            file_size_bytes: record.file_size_bytes,
+            width,
+            height,
+        })?;
+        writer.flush()?;
+        debug!("snapshot appended to csv log");
+        Ok(())
+    }
+}
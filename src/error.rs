@@ -0,0 +1,35 @@
+use std::io;
+
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+/// Errors of the library API, for callers needing to tell them apart.
+#[derive(Debug, Error)]
+pub enum CameraBotError {
+    #[error("unable to open camera: {0}")]
+    CameraOpen(String),
+    #[error("unable to open the display window, is a display available?")]
+    DisplayOpen(#[source] opencv::Error),
+    #[error("unable to read from camera")]
+    CameraRead(#[source] opencv::Error),
+    #[error("unable to load overlay")]
+    OverlayLoad(#[source] opencv::Error),
+    #[error("unable to save snapshot: {0}")]
+    SnapshotSave(String),
+    #[error("receiver of the channel is gone")]
+    ChannelSend,
+    #[error("unable to start web server")]
+    WebServer(#[source] io::Error),
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    #[error(transparent)]
+    OpenCv(#[from] opencv::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl<T> From<mpsc::error::SendError<T>> for CameraBotError {
+    fn from(_: mpsc::error::SendError<T>) -> Self {
+        Self::ChannelSend
+    }
+}
@@ -0,0 +1,52 @@
+use std::path::Path;
+
+use anyhow::Result;
+use opencv::{
+    core::{Rect, Size, Vector},
+    imgproc,
+    objdetect::CascadeClassifier,
+    prelude::*,
+};
+use tracing::{debug, instrument};
+
+pub struct FaceDetector {
+    classifier: CascadeClassifier,
+}
+
+impl FaceDetector {
+    /// Create a face detector from a Haar cascade file (e.g. `haarcascade_frontalface_default.xml`).
+    pub fn new(cascade: &Path) -> Result<Self> {
+        let classifier = CascadeClassifier::new(&cascade.display().to_string())?;
+        if classifier.empty()? {
+            anyhow::bail!("unable to load face cascade: {}", cascade.display());
+        }
+        Ok(Self { classifier })
+    }
+
+    /// Check whether at least one face is present in `frame`.
+    pub fn detect(&mut self, frame: &Mat) -> Result<bool> {
+        Ok(!self.faces(frame)?.is_empty())
+    }
+
+    /// Find the bounding boxes of all faces present in `frame`.
+    #[instrument(skip_all)]
+    pub fn faces(&mut self, frame: &Mat) -> Result<Vector<Rect>> {
+        let mut gray = Mat::default();
+        imgproc::cvt_color(frame, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?;
+        let mut equalized = Mat::default();
+        imgproc::equalize_hist(&gray, &mut equalized)?;
+
+        let mut faces = Vector::new();
+        self.classifier.detect_multi_scale(
+            &equalized,
+            &mut faces,
+            1.1,
+            3,
+            0,
+            Size::new(30, 30),
+            Size::default(),
+        )?;
+        debug!(count = faces.len(), "faces detected");
+        Ok(faces)
+    }
+}
@@ -0,0 +1,213 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use opencv::{
+    calib3d,
+    core::{self, Mat, Point2f, Scalar, Size, TermCriteria, Vector, CV_64F},
+    imgproc,
+    prelude::*,
+    video,
+};
+use tracing::{debug, trace};
+
+/// Most feature points tracked between frames.
+const MAX_FEATURES: i32 = 200;
+/// Fewest tracked feature points needed to estimate the motion.
+const MIN_FEATURES: usize = 6;
+
+/// Synthesize frames between the captured frames when the camera is slower than a threshold,
+/// using the camera motion estimated from the optical flow.
+///
+/// Showing the interpolated frames delays the video by one captured frame.
+pub struct FrameInterpolator {
+    threshold_fps: f32,
+    /// Arrival of the last captured frame.
+    last_arrival: Option<Instant>,
+    previous: Option<Mat>,
+    transition: Option<Transition>,
+    last_shown: Instant,
+}
+
+/// Interpolating from one captured frame to the next.
+struct Transition {
+    from: Mat,
+    to: Mat,
+    /// Affine motion from `from` to `to`, and back.
+    forward: Mat,
+    backward: Mat,
+    started: Instant,
+    duration: Duration,
+}
+
+impl FrameInterpolator {
+    pub fn new(threshold_fps: f32) -> Self {
+        Self {
+            threshold_fps,
+            last_arrival: None,
+            previous: None,
+            transition: None,
+            last_shown: Instant::now(),
+        }
+    }
+
+    /// The frame to show given the frame `received` from the camera if any, or `None` when
+    /// there is nothing new to show.
+    pub fn next(&mut self, received: Option<Mat>) -> Result<Option<Mat>> {
+        match received {
+            Some(frame) => self.received(frame),
+            None => self.interpolate(),
+        }
+    }
+
+    fn received(&mut self, frame: Mat) -> Result<Option<Mat>> {
+        let now = Instant::now();
+        let interval = self.last_arrival.replace(now).map(|last| now - last);
+
+        // finish the current transition, if the frame arrived early
+        let unfinished = self.transition.take().map(|transition| transition.to);
+
+        let slow = interval.is_some_and(|interval| {
+            interval.as_secs_f32() > 0. && 1. / interval.as_secs_f32() < self.threshold_fps
+        });
+        let previous = self.previous.replace(frame.clone());
+        match (slow, previous, interval) {
+            (true, Some(from), Some(duration)) => match estimate_motion(&from, &frame)? {
+                Some(forward) => {
+                    let mut backward = Mat::default();
+                    imgproc::invert_affine_transform(&forward, &mut backward)?;
+                    trace!(?duration, "interpolating to the received frame");
+                    self.transition = Some(Transition {
+                        from,
+                        to: frame,
+                        forward,
+                        backward,
+                        started: now,
+                        duration,
+                    });
+                    Ok(unfinished)
+                }
+                None => {
+                    debug!("too few features to interpolate frames");
+                    Ok(Some(frame))
+                }
+            },
+            _ => Ok(Some(frame)),
+        }
+    }
+
+    fn interpolate(&mut self) -> Result<Option<Mat>> {
+        let transition = match &self.transition {
+            Some(transition) => transition,
+            None => return Ok(None),
+        };
+        let t = transition.started.elapsed().as_secs_f64() / transition.duration.as_secs_f64();
+        if t >= 1. {
+            return Ok(self.transition.take().map(|transition| transition.to));
+        }
+        if self.last_shown.elapsed().as_secs_f32() < 1. / self.threshold_fps {
+            return Ok(None);
+        }
+        self.last_shown = Instant::now();
+        transition.at(t).map(Some)
+    }
+}
+
+impl Transition {
+    /// The frame a fraction `t` of the way from `from` to `to`.
+    fn at(&self, t: f64) -> Result<Mat> {
+        let size = self.from.size()?;
+        let from = warp(&self.from, &self.forward, t, size)?;
+        let to = warp(&self.to, &self.backward, 1. - t, size)?;
+        let mut frame = Mat::default();
+        core::add_weighted(&from, 1. - t, &to, t, 0., &mut frame, -1)?;
+        Ok(frame)
+    }
+}
+
+/// Move `frame` a fraction `t` of the affine `motion`.
+fn warp(frame: &Mat, motion: &Mat, t: f64, size: Size) -> Result<Mat> {
+    let identity = Mat::from_slice_2d(&[[1., 0., 0.], [0., 1., 0.]])?;
+    let mut partial = Mat::default();
+    core::add_weighted(&identity, 1. - t, motion, t, 0., &mut partial, CV_64F)?;
+    let mut warped = Mat::default();
+    imgproc::warp_affine(
+        frame,
+        &mut warped,
+        &partial,
+        size,
+        imgproc::INTER_LINEAR,
+        core::BORDER_REPLICATE,
+        Scalar::default(),
+    )?;
+    Ok(warped)
+}
+
+/// The affine motion from `from` to `to`, tracking features using the Lucas-Kanade optical
+/// flow, or `None` if too few features could be tracked.
+fn estimate_motion(from: &Mat, to: &Mat) -> Result<Option<Mat>> {
+    let mut from_gray = Mat::default();
+    imgproc::cvt_color(from, &mut from_gray, imgproc::COLOR_BGR2GRAY, 0)?;
+    let mut to_gray = Mat::default();
+    imgproc::cvt_color(to, &mut to_gray, imgproc::COLOR_BGR2GRAY, 0)?;
+
+    let mut features = Vector::<Point2f>::new();
+    imgproc::good_features_to_track(
+        &from_gray,
+        &mut features,
+        MAX_FEATURES,
+        0.01,
+        8.,
+        &core::no_array(),
+        3,
+        false,
+        0.04,
+    )?;
+    if features.len() < MIN_FEATURES {
+        return Ok(None);
+    }
+
+    let mut tracked = Vector::<Point2f>::new();
+    let mut status = Vector::<u8>::new();
+    let mut errors = Vector::<f32>::new();
+    video::calc_optical_flow_pyr_lk(
+        &from_gray,
+        &to_gray,
+        &features,
+        &mut tracked,
+        &mut status,
+        &mut errors,
+        Size::new(21, 21),
+        3,
+        TermCriteria::new(
+            core::TermCriteria_Type::COUNT as i32 + core::TermCriteria_Type::EPS as i32,
+            30,
+            0.01,
+        )?,
+        0,
+        1e-4,
+    )?;
+
+    let mut src = Vector::<Point2f>::new();
+    let mut dst = Vector::<Point2f>::new();
+    for ((feature, tracked), found) in features.iter().zip(tracked.iter()).zip(status.iter()) {
+        if found != 0 {
+            src.push(feature);
+            dst.push(tracked);
+        }
+    }
+    if src.len() < MIN_FEATURES {
+        return Ok(None);
+    }
+
+    let motion = calib3d::estimate_affine_partial_2d(
+        &src,
+        &dst,
+        &mut core::no_array(),
+        calib3d::RANSAC,
+        3.,
+        2000,
+        0.99,
+        10,
+    )?;
+    Ok((!motion.empty()).then_some(motion))
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use opencv::{imgcodecs, prelude::*};
+use tokio::process::Command;
+use tokio::sync::broadcast;
+use tokio::time::interval;
+use tracing::{debug, info, instrument, warn};
+
+use crate::snapshot_repo::{SharedSnapshotRepo, TriggerSource};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Name pattern of the downloaded images, see `gphoto2 --help-filename`.
+const FILENAME_PATTERN: &str = "%Y%m%d-%H%M%S-%n.%C";
+
+/// Shoot tethered to a camera through `gphoto2`, saving the images downloaded into
+/// `download_dir` to the snapshot repository.
+pub fn spawn(
+    download_dir: PathBuf,
+    repo: SharedSnapshotRepo,
+    exit_receiver: broadcast::Receiver<bool>,
+) -> tokio::task::JoinHandle<Result<()>> {
+    debug!("spawning gphoto2 tether");
+    tokio::spawn(tether(download_dir, repo, exit_receiver))
+}
+
+#[instrument(skip(repo, exit_receiver))]
+async fn tether(
+    download_dir: PathBuf,
+    repo: SharedSnapshotRepo,
+    mut exit_receiver: broadcast::Receiver<bool>,
+) -> Result<()> {
+    std::fs::create_dir_all(&download_dir)?;
+    let mut gphoto2 = Command::new("gphoto2")
+        .arg("--capture-tethered")
+        .arg("--filename")
+        .arg(download_dir.join(FILENAME_PATTERN))
+        .kill_on_drop(true)
+        .spawn()
+        .context("unable to run gphoto2")?;
+    info!("gphoto2 tether started");
+
+    // files already downloaded aren't snapshots, the others are saved once their size settles
+    let mut sizes = file_sizes(&download_dir)?;
+    let mut pending: HashMap<PathBuf, u64> = HashMap::new();
+    let mut interval = interval(POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = exit_receiver.recv() => {
+                debug!("exit received");
+                break;
+            }
+            status = gphoto2.wait() => {
+                warn!(status = ?status?, "gphoto2 exited");
+                break;
+            }
+            _ = interval.tick() => {
+                for (path, size) in file_sizes(&download_dir)? {
+                    if sizes.get(&path) == Some(&size) {
+                        continue;
+                    }
+                    if pending.get(&path) == Some(&size) {
+                        pending.remove(&path);
+                        sizes.insert(path.clone(), size);
+                        let repo = repo.clone();
+                        tokio::task::spawn_blocking(move || save_download(&path, &repo)).await??;
+                    } else {
+                        pending.insert(path, size);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Size of each file in `dir`.
+fn file_sizes(dir: &Path) -> Result<HashMap<PathBuf, u64>> {
+    let mut sizes = HashMap::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            sizes.insert(entry.path(), metadata.len());
+        }
+    }
+    Ok(sizes)
+}
+
+fn save_download(path: &Path, repo: &SharedSnapshotRepo) -> Result<()> {
+    let image = imgcodecs::imread(&path.display().to_string(), imgcodecs::IMREAD_COLOR)?;
+    if image.empty() {
+        warn!(?path, "unable to read downloaded image");
+        return Ok(());
+    }
+    repo.lock()
+        .unwrap()
+        .save_frame(&image, TriggerSource::Tether)?;
+    info!(?path, "tethered image saved");
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use opencv::{
+    core::{self, Size, Vector, CV_8UC3},
+    imgcodecs, imgproc,
+    prelude::{Mat, MatTraitConst},
+};
+use tracing::{debug, warn};
+
+use crate::snapshot_repo::SharedSnapshotRepo;
+
+/// Time between reloading the snapshots shown in the grid.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+/// Most snapshots shown, tiled 3x3. Up to 4 snapshots are tiled 2x2.
+const MAX_SNAPSHOTS: usize = 9;
+
+/// Tile the most recently saved snapshots when the UI has been idle for a while.
+pub struct GridView {
+    idle_after: Duration,
+    repo: SharedSnapshotRepo,
+    last_activity: Instant,
+    /// When the grid was loaded, and the grid if there were any snapshots to show.
+    grid: Option<(Instant, Option<Mat>)>,
+}
+
+impl GridView {
+    pub fn new(idle_after: Duration, repo: SharedSnapshotRepo) -> Self {
+        Self {
+            idle_after,
+            repo,
+            last_activity: Instant::now(),
+            grid: None,
+        }
+    }
+
+    /// Leave the grid view and restart the idle timer.
+    pub fn activity(&mut self) {
+        if self.grid.take().is_some() {
+            debug!("leaving grid view");
+        }
+        self.last_activity = Instant::now();
+    }
+
+    /// The grid to show instead of the video, or `None` unless idle.
+    pub fn frame(&mut self, size: Size) -> Option<&Mat> {
+        if self.last_activity.elapsed() < self.idle_after {
+            return None;
+        }
+        if self
+            .grid
+            .as_ref()
+            .is_none_or(|(loaded, _)| loaded.elapsed() >= REFRESH_INTERVAL)
+        {
+            let grid = self.load(size).unwrap_or_else(|err| {
+                warn!(?err, "unable to load snapshots for the grid view");
+                None
+            });
+            self.grid = Some((Instant::now(), grid));
+        }
+        self.grid.as_ref().and_then(|(_, grid)| grid.as_ref())
+    }
+
+    fn load(&self, size: Size) -> Result<Option<Mat>> {
+        let paths = self.repo.lock().unwrap().recent(MAX_SNAPSHOTS)?;
+        if paths.is_empty() {
+            return Ok(None);
+        }
+        debug!(snapshots = paths.len(), "loading grid view");
+        tile(&paths, size).map(Some)
+    }
+}
+
+/// Tile the images at `paths` in a square grid of `size`, leaving unused cells black.
+fn tile(paths: &[PathBuf], size: Size) -> Result<Mat> {
+    let columns = if paths.len() <= 4 { 2 } else { 3 };
+    let cell_size = Size::new(size.width / columns, size.height / columns);
+    let black = Mat::new_size_with_default(cell_size, CV_8UC3, core::Scalar::all(0.))?;
+
+    let mut rows = Vector::<Mat>::new();
+    for row in 0..columns {
+        let mut cells = Vector::<Mat>::new();
+        for column in 0..columns {
+            let cell = match paths.get((row * columns + column) as usize) {
+                Some(path) => read_cell(path, cell_size).unwrap_or_else(|err| {
+                    warn!(?err, ?path, "unable to read snapshot for the grid view");
+                    black.clone()
+                }),
+                None => black.clone(),
+            };
+            cells.push(cell);
+        }
+        let mut tiled = Mat::default();
+        core::hconcat(&cells, &mut tiled)?;
+        rows.push(tiled);
+    }
+    let mut grid = Mat::default();
+    core::vconcat(&rows, &mut grid)?;
+    Ok(grid)
+}
+
+fn read_cell(path: &Path, cell_size: Size) -> Result<Mat> {
+    let image = imgcodecs::imread(&path.display().to_string(), imgcodecs::IMREAD_COLOR)?;
+    if image.empty() {
+        anyhow::bail!("empty image");
+    }
+    let mut cell = Mat::default();
+    imgproc::resize(&image, &mut cell, cell_size, 0., 0., imgproc::INTER_AREA)?;
+    Ok(cell)
+}
@@ -0,0 +1,64 @@
+use anyhow::Result;
+use opencv::{
+    core::{self, Vector},
+    imgproc,
+    prelude::{Mat, MatTraitConst},
+};
+
+/// Brightness, contrast and saturation adjustment of camera frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageAdjustment {
+    /// Offset added to each pixel value.
+    pub brightness: f32,
+    /// Factor each pixel value is multiplied with.
+    pub contrast: f32,
+    /// Factor the saturation is multiplied with.
+    pub saturation: f32,
+}
+
+impl Default for ImageAdjustment {
+    fn default() -> Self {
+        Self {
+            brightness: 0.,
+            contrast: 1.,
+            saturation: 1.,
+        }
+    }
+}
+
+impl ImageAdjustment {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Adjust an 8 bit BGR frame.
+    pub fn apply(&self, frame: &Mat) -> Result<Mat> {
+        let mut adjusted = Mat::default();
+        core::convert_scale_abs(
+            frame,
+            &mut adjusted,
+            f64::from(self.contrast),
+            f64::from(self.brightness),
+        )?;
+        if (self.saturation - 1.).abs() > f32::EPSILON {
+            adjusted = self.saturate(&adjusted)?;
+        }
+        Ok(adjusted)
+    }
+
+    fn saturate(&self, frame: &Mat) -> Result<Mat> {
+        let mut hsv = Mat::default();
+        imgproc::cvt_color(frame, &mut hsv, imgproc::COLOR_BGR2HSV, 0)?;
+        let mut channels = Vector::<Mat>::new();
+        core::split(&hsv, &mut channels)?;
+        let mut saturation = Mat::default();
+        channels
+            .get(1)?
+            .convert_to(&mut saturation, -1, f64::from(self.saturation), 0.)?;
+        channels.set(1, saturation)?;
+        core::merge(&channels, &mut hsv)?;
+        let mut bgr = Mat::default();
+        imgproc::cvt_color(&hsv, &mut bgr, imgproc::COLOR_HSV2BGR, 0)?;
+        Ok(bgr)
+    }
+}
@@ -0,0 +1,123 @@
+//! Photo booth bot: shows the camera video with overlays, and saves mugshots when triggered.
+//!
+//! The building blocks used by the `camera-bot` binary, for embedding in other applications.
+//!
+//! ```
+//! use camera_bot::{SnapshotRepo, VideoParams};
+//! use clap::Parser;
+//!
+//! #[derive(Parser)]
+//! struct Cli {
+//!     #[clap(flatten)]
+//!     video: VideoParams,
+//! }
+//!
+//! let cli = Cli::parse_from(["app", "--video-width", "640"]);
+//! assert_eq!(cli.video.video_width, 640);
+//!
+//! let repo = SnapshotRepo::from_path_and_namepattern("snapshots".into(), "mugshot_$COUNTER$.jpg")?
+//!     .with_camera_name("booth".to_string());
+//! assert!(repo.last_saved_path().is_none());
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use tokio::sync::{broadcast, oneshot};
+
+pub mod adaptive_quality;
+pub mod alpha_image;
+pub mod app_event;
+pub mod args;
+pub mod audio;
+pub mod auto_trigger;
+pub mod background_capture;
+pub mod batch_process;
+pub mod benchmark;
+pub mod camera_monitor;
+pub mod camera_query;
+pub mod capture_thread;
+pub mod chroma_key;
+pub mod client;
+pub mod config;
+pub mod crop;
+pub mod csv_log;
+pub mod error;
+pub mod face_detector;
+pub mod frame_interpolation;
+pub mod gphoto2_tether;
+pub mod grid_view;
+pub mod image_adjust;
+pub mod log;
+pub mod metrics;
+pub mod opencv_info;
+pub mod overlay_bundle;
+pub mod palette;
+pub mod phash;
+pub mod pip;
+pub mod pixel_buffer;
+pub mod printer;
+pub mod rtsp_output;
+pub mod schedules;
+pub mod sharpness;
+pub mod snapshot_cache;
+pub mod snapshot_db;
+pub mod snapshot_repo;
+pub mod stats;
+pub mod stdin_input;
+pub mod trigger_config;
+#[cfg(unix)]
+pub mod trigger_pipe;
+pub mod ui_thread;
+pub mod web;
+pub mod webhook;
+pub mod zoom;
+
+pub use alpha_image::AlphaImage;
+pub use app_event::{AppEvent, EventBus};
+pub use args::{TriggerParams, VideoParams};
+pub use client::Client;
+pub use error::CameraBotError;
+pub use snapshot_repo::SnapshotRepo;
+
+#[derive(Debug)]
+pub enum ControlMsg {
+    /// Take a snapshot using this overlay instead of the mugshot overlay, signalling when done.
+    SnapshotWithOverlay(AlphaImage, oneshot::Sender<()>),
+}
+
+/// Handle on the channels of a running bot, for adding consumers of its events, e.g. a metrics
+/// task, besides the coordinator and the web API.
+#[derive(Debug, Clone)]
+pub struct BotHandle {
+    trigger_event_sender: broadcast::Sender<auto_trigger::EventMsg>,
+    event_bus: EventBus,
+}
+
+impl BotHandle {
+    pub fn new(
+        trigger_event_sender: broadcast::Sender<auto_trigger::EventMsg>,
+        event_bus: EventBus,
+    ) -> Self {
+        Self {
+            trigger_event_sender,
+            event_bus,
+        }
+    }
+
+    /// Receive the trigger and countdown events sent from now on.
+    pub fn subscribe_trigger_events(&self) -> broadcast::Receiver<auto_trigger::EventMsg> {
+        self.trigger_event_sender.subscribe()
+    }
+
+    /// Receive the application events published from now on.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<AppEvent> {
+        self.event_bus.subscribe()
+    }
+
+    pub fn trigger_event_sender(&self) -> broadcast::Sender<auto_trigger::EventMsg> {
+        self.trigger_event_sender.clone()
+    }
+
+    pub fn event_bus(&self) -> &EventBus {
+        &self.event_bus
+    }
+}
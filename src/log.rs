@@ -1,13 +1,44 @@
-#[cfg(not(feature = "tokio-console"))]
-pub fn setup_tracing() {
-    tracing_subscriber::fmt::init();
+use anyhow::Result;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, Layer, Registry};
+
+use crate::args::LogParams;
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Log to stderr and, when `--log-file` is given, to a daily rotated log file.
+pub fn setup_tracing(params: &LogParams) -> Result<()> {
+    let level = LevelFilter::from_level(params.log_level);
+    let mut layers = vec![fmt_layer(std::io::stderr, params.log_json, true, level)];
+    if let Some(log_file) = &params.log_file {
+        let file_name = log_file
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("--log-file must name a file"))?;
+        let directory = log_file
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or_else(|| ".".as_ref());
+        let appender = tracing_appender::rolling::daily(directory, file_name);
+        layers.push(fmt_layer(appender, params.log_json, false, level));
+    }
+
+    #[cfg(feature = "tokio-console")]
+    layers.push(console_subscriber::spawn().boxed());
+
+    tracing_subscriber::registry().with(layers).try_init()?;
+    Ok(())
 }
 
-#[cfg(feature = "tokio-console")]
-pub fn setup_tracing() {
-    use tracing_subscriber::prelude::*;
-    tracing_subscriber::registry()
-        .with(console_subscriber::spawn())
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+fn fmt_layer<W>(writer: W, json: bool, ansi: bool, level: LevelFilter) -> BoxedLayer
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = fmt::layer().with_writer(writer).with_ansi(ansi);
+    if json {
+        layer.json().with_filter(level).boxed()
+    } else {
+        layer.with_filter(level).boxed()
+    }
 }
@@ -0,0 +1,135 @@
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tracing::info;
+
+/// Upper bounds in seconds of the latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1., 2.5, 5., 10., 30., 60.];
+
+/// Cumulative histogram of observations, as exposed by Prometheus.
+#[derive(Debug)]
+struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    /// Observations at most the bucket bound, for each of `LATENCY_BUCKETS`.
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            buckets: [0; LATENCY_BUCKETS.len()],
+            sum: 0.,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        info!(
+            target: "camera_bot::metrics",
+            histogram = self.name,
+            value = seconds,
+        );
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    /// Append the histogram in the Prometheus text exposition format.
+    fn write_prometheus(&self, out: &mut String) -> std::fmt::Result {
+        writeln!(out, "# HELP {} {}", self.name, self.help)?;
+        writeln!(out, "# TYPE {} histogram", self.name)?;
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            writeln!(out, "{}_bucket{{le=\"{}\"}} {}", self.name, bound, bucket)?;
+        }
+        writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", self.name, self.count)?;
+        writeln!(out, "{}_sum {}", self.name, self.sum)?;
+        writeln!(out, "{}_count {}", self.name, self.count)
+    }
+}
+
+/// Time spent on each part of taking a snapshot.
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotLatency {
+    /// From the trigger until the capture thread replied with the snapshot kept, including the
+    /// retakes of blurry ones.
+    pub capture: Duration,
+    /// Encoding and writing the snapshot.
+    pub save: Duration,
+    /// From the trigger until the snapshot was written.
+    pub total: Duration,
+}
+
+/// Latency histograms of the snapshots taken since startup.
+#[derive(Debug)]
+pub struct SnapshotMetrics {
+    capture: Histogram,
+    save: Histogram,
+    total: Histogram,
+}
+
+pub type SharedSnapshotMetrics = Arc<Mutex<SnapshotMetrics>>;
+
+impl Default for SnapshotMetrics {
+    fn default() -> Self {
+        Self {
+            capture: Histogram::new(
+                "camera_bot_snapshot_capture_seconds",
+                "Time from the trigger until the snapshot was captured",
+            ),
+            save: Histogram::new(
+                "camera_bot_snapshot_save_seconds",
+                "Time spent encoding and writing the snapshot",
+            ),
+            total: Histogram::new(
+                "camera_bot_snapshot_total_seconds",
+                "Time from the trigger until the snapshot was written",
+            ),
+        }
+    }
+}
+
+impl SnapshotMetrics {
+    /// Record the latency of a snapshot, also emitted as `tracing` events.
+    pub fn record(&mut self, latency: SnapshotLatency) {
+        self.capture.observe(latency.capture);
+        self.save.observe(latency.save);
+        self.total.observe(latency.total);
+    }
+
+    /// The histograms in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        for histogram in [&self.capture, &self.save, &self.total] {
+            histogram
+                .write_prometheus(&mut out)
+                .expect("writing to a String can't fail");
+        }
+        out
+    }
+}
+
+#[test]
+fn histogram_buckets_are_cumulative() {
+    let mut metrics = SnapshotMetrics::default();
+    metrics.record(SnapshotLatency {
+        capture: Duration::from_millis(80),
+        save: Duration::from_millis(300),
+        total: Duration::from_millis(380),
+    });
+    let text = metrics.to_prometheus();
+    assert!(text.contains("camera_bot_snapshot_capture_seconds_bucket{le=\"0.05\"} 0"));
+    assert!(text.contains("camera_bot_snapshot_capture_seconds_bucket{le=\"0.1\"} 1"));
+    assert!(text.contains("camera_bot_snapshot_capture_seconds_bucket{le=\"60\"} 1"));
+    assert!(text.contains("camera_bot_snapshot_total_seconds_count 1"));
+}
@@ -0,0 +1,51 @@
+use anyhow::Result;
+use opencv::core;
+use serde::Serialize;
+
+/// OpenCV build and runtime information, useful when debugging performance anomalies.
+#[derive(Debug, Serialize)]
+pub struct OpenCvMetrics {
+    pub opencv_version: String,
+    pub cpu_count: i32,
+    pub number_of_ipps_threads: i32,
+    pub optimized_functions_enabled: bool,
+    pub ipp_enabled: bool,
+    pub cpu_features: String,
+}
+
+impl OpenCvMetrics {
+    pub fn read() -> Result<Self> {
+        Ok(Self {
+            opencv_version: core::get_version_string()?,
+            cpu_count: core::get_number_of_cpus()?,
+            number_of_ipps_threads: core::get_num_threads()?,
+            optimized_functions_enabled: core::use_optimized()?,
+            ipp_enabled: core::use_ipp()?,
+            cpu_features: core::get_cpu_features_line()?,
+        })
+    }
+}
+
+/// OpenCV optimisation settings and the SIMD instruction sets available on this CPU.
+#[derive(Debug, Serialize)]
+pub struct OpenCvConfig {
+    pub use_optimized: bool,
+    pub use_ipp: bool,
+    pub have_sse2: bool,
+    pub have_avx: bool,
+    pub have_avx2: bool,
+    pub have_neon: bool,
+}
+
+impl OpenCvConfig {
+    pub fn read() -> Result<Self> {
+        Ok(Self {
+            use_optimized: core::use_optimized()?,
+            use_ipp: core::use_ipp()?,
+            have_sse2: core::check_hardware_support(core::CPU_SSE2)?,
+            have_avx: core::check_hardware_support(core::CPU_AVX)?,
+            have_avx2: core::check_hardware_support(core::CPU_AVX2)?,
+            have_neon: core::check_hardware_support(core::CPU_NEON)?,
+        })
+    }
+}
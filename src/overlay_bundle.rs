@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use tracing::{debug, instrument};
+use zip::{result::ZipError, ZipArchive};
+
+use crate::alpha_image::AlphaImage;
+
+/// Countdown overlays and the mugshot overlay.
+pub type OverlaySet = (Vec<AlphaImage>, Option<AlphaImage>);
+
+/// Read countdown and mugshot overlays from a ZIP archive.
+///
+/// The countdown overlays are read as `1.png`, `2.png`, ... until a number is missing,
+/// and the mugshot overlay as `mugshot.png`, all from the root of the archive.
+#[instrument]
+pub fn read_overlay_bundle(path: &Path) -> Result<OverlaySet> {
+    let file = File::open(path).with_context(|| format!("unable to open {}", path.display()))?;
+    let mut archive = ZipArchive::new(file)
+        .with_context(|| format!("unable to read overlay bundle {}", path.display()))?;
+
+    let mut countdown_images = Vec::new();
+    while let Some(image) =
+        read_entry(&mut archive, &format!("{}.png", countdown_images.len() + 1))?
+    {
+        countdown_images.push(image);
+    }
+    if countdown_images.is_empty() {
+        anyhow::bail!("overlay bundle {} contains no 1.png", path.display());
+    }
+    debug!(count = countdown_images.len(), "countdown overlays read");
+
+    let mugshot_image = read_entry(&mut archive, "mugshot.png")?;
+    Ok((countdown_images, mugshot_image))
+}
+
+const BUILTIN_COUNTDOWN: [&[u8]; 3] = [
+    include_bytes!("../assets/1.png"),
+    include_bytes!("../assets/2.png"),
+    include_bytes!("../assets/3.png"),
+];
+const BUILTIN_MUGSHOT: &[u8] = include_bytes!("../assets/mugshot.png");
+
+/// Decode the countdown and mugshot overlays compiled into the binary.
+pub fn builtin_overlays() -> Result<OverlaySet> {
+    let countdown_images = BUILTIN_COUNTDOWN
+        .iter()
+        .map(|bytes| AlphaImage::from_png_bytes(bytes))
+        .collect::<Result<_>>()?;
+    Ok((
+        countdown_images,
+        Some(AlphaImage::from_png_bytes(BUILTIN_MUGSHOT)?),
+    ))
+}
+
+#[derive(Deserialize)]
+struct OverlaySetPaths {
+    countdown: Vec<PathBuf>,
+    mugshot: Option<PathBuf>,
+}
+
+/// Read the overlay sets listed in a JSON file, formatted as
+/// `[{"countdown": ["a/1.png"], "mugshot": "a/mugshot.png"}, ...]`.
+#[instrument]
+pub fn read_per_shot_overlays(path: &Path) -> Result<Vec<OverlaySet>> {
+    let file = File::open(path).with_context(|| format!("unable to open {}", path.display()))?;
+    let sets: Vec<OverlaySetPaths> = serde_json::from_reader(file)
+        .with_context(|| format!("unable to parse per shot overlays {}", path.display()))?;
+    if sets.is_empty() {
+        anyhow::bail!(
+            "per shot overlays {} contains no overlay sets",
+            path.display()
+        );
+    }
+    debug!(count = sets.len(), "overlay sets read");
+    sets.iter()
+        .map(|set| {
+            let countdown_images = set
+                .countdown
+                .iter()
+                .map(|path| read_png(path))
+                .collect::<Result<_>>()?;
+            let mugshot_image = set.mugshot.as_deref().map(read_png).transpose()?;
+            Ok((countdown_images, mugshot_image))
+        })
+        .collect()
+}
+
+fn read_png(path: &Path) -> Result<AlphaImage> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("unable to read {}", path.display()))?;
+    AlphaImage::from_png_bytes(&bytes)
+        .with_context(|| format!("unable to decode {}", path.display()))
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Option<AlphaImage>> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("unable to read {}", name)),
+    };
+    let mut bytes = Vec::new();
+    entry.read_to_end(&mut bytes)?;
+    AlphaImage::from_png_bytes(&bytes)
+        .with_context(|| format!("unable to decode {}", name))
+        .map(Some)
+}
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use opencv::{
+    core::{self, Rect, Scalar, Size, TermCriteria, CV_32F},
+    imgproc,
+    prelude::*,
+};
+use tracing::trace;
+
+/// Width the frames are downscaled to before clustering their colors.
+const SAMPLE_WIDTH: i32 = 64;
+/// Height of the color swatches at the bottom of the frame.
+const SWATCH_HEIGHT: i32 = 40;
+
+/// Swatches of the dominant colors of the video, found by k-means clustering.
+pub struct Palette {
+    colors: usize,
+    interval: usize,
+    frames: usize,
+    swatches: Vec<Scalar>,
+}
+
+impl Palette {
+    pub fn new(colors: usize, interval: usize) -> Self {
+        Self {
+            colors,
+            interval: interval.max(1),
+            frames: 0,
+            swatches: Vec::new(),
+        }
+    }
+
+    /// Find the dominant colors of every `interval`th frame.
+    pub fn update(&mut self, frame: &Mat) -> Result<()> {
+        self.frames += 1;
+        if self.frames < self.interval && !self.swatches.is_empty() {
+            return Ok(());
+        }
+        self.frames = 0;
+        self.swatches = dominant_colors(frame, self.colors)?;
+        trace!(swatches = ?self.swatches, "palette updated");
+        Ok(())
+    }
+
+    /// Draw the swatches side by side along the bottom of `frame`.
+    pub fn draw(&self, frame: &mut Mat) -> Result<()> {
+        let count = i32::try_from(self.swatches.len())?;
+        if count == 0 {
+            return Ok(());
+        }
+        let width = frame.cols() / count;
+        let y = frame.rows() - SWATCH_HEIGHT;
+        for (i, color) in (0..).zip(&self.swatches) {
+            imgproc::rectangle(
+                frame,
+                Rect::new(i * width, y, width, SWATCH_HEIGHT),
+                *color,
+                imgproc::FILLED,
+                imgproc::LINE_8,
+                0,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The `k` dominant colors of the 8-bit BGR `frame`.
+fn dominant_colors(frame: &Mat, k: usize) -> Result<Vec<Scalar>> {
+    let height = frame.rows() * SAMPLE_WIDTH / frame.cols().max(1);
+    let mut small = Mat::default();
+    imgproc::resize(
+        frame,
+        &mut small,
+        Size::new(SAMPLE_WIDTH, height.max(1)),
+        0.,
+        0.,
+        imgproc::INTER_AREA,
+    )?;
+    // one row of 3 columns per pixel
+    let mut samples = Mat::default();
+    small
+        .reshape(1, small.rows() * small.cols())?
+        .convert_to(&mut samples, CV_32F, 1., 0.)?;
+
+    let mut labels = Mat::default();
+    let mut centers = Mat::default();
+    core::kmeans(
+        &samples,
+        i32::try_from(k)?,
+        &mut labels,
+        TermCriteria::new(
+            core::TermCriteria_Type::COUNT as i32 + core::TermCriteria_Type::EPS as i32,
+            10,
+            1.,
+        )?,
+        1,
+        core::KMEANS_PP_CENTERS,
+        &mut centers,
+    )?;
+
+    let mut colors = Vec::with_capacity(k);
+    for row in 0..centers.rows() {
+        colors.push(Scalar::new(
+            f64::from(*centers.at_2d::<f32>(row, 0)?),
+            f64::from(*centers.at_2d::<f32>(row, 1)?),
+            f64::from(*centers.at_2d::<f32>(row, 2)?),
+            0.,
+        ));
+    }
+    Ok(colors)
+}
@@ -0,0 +1,108 @@
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use opencv::{
+    core::{self, Size, CV_32F},
+    imgproc,
+    prelude::*,
+};
+use tracing::debug;
+
+/// Side of the gray image the DCT is computed on.
+const DCT_SIZE: i32 = 32;
+/// Side of the low frequency corner of the DCT making up the hash.
+const HASH_SIZE: i32 = 8;
+
+/// 64-bit perceptual hash of the 8-bit BGR `image`: the low frequencies of its DCT compared to
+/// their median.
+pub fn phash(image: &Mat) -> Result<u64> {
+    let mut gray = Mat::default();
+    imgproc::cvt_color(image, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?;
+    let mut small = Mat::default();
+    imgproc::resize(
+        &gray,
+        &mut small,
+        Size::new(DCT_SIZE, DCT_SIZE),
+        0.,
+        0.,
+        imgproc::INTER_AREA,
+    )?;
+    let mut samples = Mat::default();
+    small.convert_to(&mut samples, CV_32F, 1., 0.)?;
+    let mut dct = Mat::default();
+    core::dct(&samples, &mut dct, 0)?;
+
+    let mut low = Vec::with_capacity((HASH_SIZE * HASH_SIZE) as usize);
+    for row in 0..HASH_SIZE {
+        for col in 0..HASH_SIZE {
+            low.push(*dct.at_2d::<f32>(row, col)?);
+        }
+    }
+    // the DC term only reflects the overall brightness
+    let mut sorted = low[1..].to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    Ok(low
+        .iter()
+        .fold(0, |hash, &value| hash << 1 | u64::from(value > median)))
+}
+
+/// Skips snapshots looking like one of the recently saved snapshots.
+#[derive(Debug)]
+pub struct PhashDedup {
+    /// Hashes differing in fewer bits are duplicates.
+    threshold: u32,
+    history: usize,
+    hashes: VecDeque<u64>,
+}
+
+impl PhashDedup {
+    pub fn new(threshold: u32, history: usize) -> Self {
+        Self {
+            threshold,
+            history: history.max(1),
+            hashes: VecDeque::new(),
+        }
+    }
+
+    /// Whether `image` duplicates a recent snapshot, otherwise remembering it.
+    pub fn is_duplicate(&mut self, image: &Mat) -> Result<bool> {
+        let hash = phash(image)?;
+        if let Some(distance) = self
+            .hashes
+            .iter()
+            .map(|recent| (hash ^ recent).count_ones())
+            .find(|&distance| distance < self.threshold)
+        {
+            debug!(hash, distance, "duplicate snapshot");
+            return Ok(true);
+        }
+        if self.hashes.len() == self.history {
+            self.hashes.pop_front();
+        }
+        self.hashes.push_back(hash);
+        Ok(false)
+    }
+}
+
+#[test]
+fn phash_dedup_skips_similar_images() {
+    use opencv::core::{Rect, Scalar, CV_8UC3};
+
+    let image = |x| -> opencv::Result<Mat> {
+        let mut image = Mat::new_rows_cols_with_default(240, 320, CV_8UC3, Scalar::all(0.))?;
+        imgproc::rectangle(
+            &mut image,
+            Rect::new(x, 60, 100, 120),
+            Scalar::all(255.),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )?;
+        Ok(image)
+    };
+    let mut dedup = PhashDedup::new(5, 2);
+    assert!(!dedup.is_duplicate(&image(20).unwrap()).unwrap());
+    assert!(dedup.is_duplicate(&image(20).unwrap()).unwrap());
+    assert!(!dedup.is_duplicate(&image(200).unwrap()).unwrap());
+}
@@ -0,0 +1,82 @@
+use anyhow::Result;
+use opencv::{
+    core::{Rect, Size},
+    imgproc,
+    prelude::*,
+};
+use tracing::trace;
+
+/// Corner of the video showing the picture-in-picture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Inset of the last saved snapshot in a corner of the live video.
+pub struct PictureInPicture {
+    /// Size of the inset as a fraction of the video.
+    scale: f32,
+    position: PipPosition,
+    snapshot: Option<Mat>,
+    /// The snapshot resized for frames of the given size.
+    thumbnail: Option<(Size, Mat)>,
+}
+
+impl PictureInPicture {
+    pub fn new(scale: f32, position: PipPosition) -> Self {
+        Self {
+            scale,
+            position,
+            snapshot: None,
+            thumbnail: None,
+        }
+    }
+
+    pub fn set_snapshot(&mut self, snapshot: Mat) {
+        self.snapshot = Some(snapshot);
+        self.thumbnail = None;
+    }
+
+    /// Copy the thumbnail of the last snapshot into its corner of `frame`.
+    pub fn draw(&mut self, frame: &mut Mat) -> Result<()> {
+        let size = frame.size()?;
+        let snapshot = match &self.snapshot {
+            Some(snapshot) => snapshot,
+            None => return Ok(()),
+        };
+        let thumbnail = match &mut self.thumbnail {
+            Some((for_size, thumbnail)) if *for_size == size => thumbnail,
+            thumbnail => {
+                &thumbnail
+                    .insert((size, resize(snapshot, size, self.scale)?))
+                    .1
+            }
+        };
+        let (width, height) = (thumbnail.cols(), thumbnail.rows());
+        let x = match self.position {
+            PipPosition::TopLeft | PipPosition::BottomLeft => 0,
+            PipPosition::TopRight | PipPosition::BottomRight => size.width - width,
+        };
+        let y = match self.position {
+            PipPosition::TopLeft | PipPosition::TopRight => 0,
+            PipPosition::BottomLeft | PipPosition::BottomRight => size.height - height,
+        };
+        thumbnail.copy_to(&mut Mat::roi(frame, Rect::new(x, y, width, height))?)?;
+        Ok(())
+    }
+}
+
+/// `snapshot` resized to `scale` times the `frame_size`.
+fn resize(snapshot: &Mat, frame_size: Size, scale: f32) -> Result<Mat> {
+    let size = Size::new(
+        ((frame_size.width as f32 * scale) as i32).max(1),
+        ((frame_size.height as f32 * scale) as i32).max(1),
+    );
+    trace!(?size, "resizing last snapshot for picture-in-picture");
+    let mut thumbnail = Mat::default();
+    imgproc::resize(snapshot, &mut thumbnail, size, 0., 0., imgproc::INTER_AREA)?;
+    Ok(thumbnail)
+}
@@ -0,0 +1,56 @@
+use opencv::prelude::{Mat, MatTraitConst, MatTraitConstManual};
+
+/// Order of the four 8-bit channels making up a pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelOrder {
+    Bgra,
+    Rgba,
+}
+
+/// A raw, continuous buffer of 8-bit pixels.
+///
+/// Allows constructing an `AlphaImage` from sources other than OpenCV, e.g. decoded GIF frames or
+/// rendered SVG images.
+pub trait PixelBuffer {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    fn as_slice(&self) -> &[u8];
+
+    fn channel_order(&self) -> ChannelOrder {
+        ChannelOrder::Bgra
+    }
+}
+
+impl PixelBuffer for Mat {
+    fn width(&self) -> u32 {
+        u32::try_from(self.cols()).unwrap_or_default()
+    }
+
+    fn height(&self) -> u32 {
+        u32::try_from(self.rows()).unwrap_or_default()
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        self.data_bytes().unwrap_or_default()
+    }
+}
+
+/// Only `DynamicImage::ImageRgba8` images can be used for an `AlphaImage`, convert other
+/// variants with `DynamicImage::to_rgba8` first.
+impl PixelBuffer for image::DynamicImage {
+    fn width(&self) -> u32 {
+        image::GenericImageView::width(self)
+    }
+
+    fn height(&self) -> u32 {
+        image::GenericImageView::height(self)
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        self.as_bytes()
+    }
+
+    fn channel_order(&self) -> ChannelOrder {
+        ChannelOrder::Rgba
+    }
+}
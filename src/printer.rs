@@ -0,0 +1,66 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tokio::process::Command;
+use tracing::{debug, error, info, instrument};
+
+/// Prints saved snapshots by running an external command, e.g. `lpr`.
+#[derive(Debug)]
+pub struct Printer {
+    /// Command line, with `{filename}` replaced by the path of the snapshot.
+    command: String,
+    copies: usize,
+    every: usize,
+    saved: AtomicUsize,
+}
+
+impl Printer {
+    pub fn new(command: String, copies: usize, every: usize) -> Self {
+        Self {
+            command,
+            copies,
+            every: every.max(1),
+            saved: AtomicUsize::new(0),
+        }
+    }
+
+    /// Print the snapshot at `filename` in a background task, if it's an n-th snapshot.
+    pub fn print(&self, filename: &Path) {
+        let saved = self.saved.fetch_add(1, Ordering::Relaxed) + 1;
+        if !saved.is_multiple_of(self.every) {
+            debug!(saved, every = self.every, "snapshot not printed");
+            return;
+        }
+        let args: Vec<String> = self
+            .command
+            .split_whitespace()
+            .map(|arg| arg.replace("{filename}", &filename.display().to_string()))
+            .collect();
+        tokio::spawn(print(args, self.copies, filename.to_path_buf()));
+    }
+}
+
+#[instrument(skip(args, copies))]
+async fn print(args: Vec<String>, copies: usize, filename: PathBuf) {
+    let (program, args) = match args.split_first() {
+        Some(command) => command,
+        None => return,
+    };
+    for copy in 1..=copies {
+        match Command::new(program).args(args).output().await {
+            Ok(output) => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if output.status.success() {
+                    info!(copy, %stdout, %stderr, "snapshot printed");
+                } else {
+                    error!(copy, status = %output.status, %stdout, %stderr, "print command failed");
+                }
+            }
+            Err(err) => {
+                error!(copy, ?err, program, "unable to run print command");
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use opencv::{
+    core::Size,
+    imgproc,
+    prelude::{Mat, MatTraitConstManual, VideoWriterTrait, VideoWriterTraitConst},
+    videoio::{self, VideoWriter},
+};
+use tokio::sync::broadcast;
+use tracing::{debug, info, instrument, warn};
+
+pub fn spawn(
+    uri: String,
+    fps: u32,
+    frame_receiver: broadcast::Receiver<Mat>,
+    exit_receiver: broadcast::Receiver<bool>,
+) -> tokio::task::JoinHandle<Result<()>> {
+    debug!("spawning rtsp output");
+    tokio::spawn(rtsp_output(uri, fps, frame_receiver, exit_receiver))
+}
+
+/// Stream the captured frames to the RTSP server at `uri`, dropping frames while the stream
+/// lags behind.
+#[instrument(skip(frame_receiver, exit_receiver))]
+async fn rtsp_output(
+    uri: String,
+    fps: u32,
+    mut frame_receiver: broadcast::Receiver<Mat>,
+    mut exit_receiver: broadcast::Receiver<bool>,
+) -> Result<()> {
+    info!("rtsp output started");
+    let pipeline = format!(
+        "appsrc ! videoconvert ! x264enc tune=zerolatency ! rtspclientsink location={}",
+        uri
+    );
+    // opened on the first frame, as the pipeline needs the frame size
+    let mut writer: Option<(VideoWriter, Size)> = None;
+    loop {
+        tokio::select! {
+            _ = exit_receiver.recv() => {
+                debug!("exit received");
+                break;
+            }
+            frame = frame_receiver.recv() => match frame {
+                Ok(frame) => {
+                    let pipeline = pipeline.clone();
+                    let opened = writer.take();
+                    writer = tokio::task::spawn_blocking(move || {
+                        write_frame(opened, &pipeline, fps, &frame)
+                    })
+                    .await??;
+                    if writer.is_none() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!(skipped, "rtsp output lagging behind, frames dropped");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Write `frame` to the `writer`, opening it if needed, and scaling the frame to the size the
+/// stream was opened with. Returns `None` if the stream couldn't be opened.
+fn write_frame(
+    writer: Option<(VideoWriter, Size)>,
+    pipeline: &str,
+    fps: u32,
+    frame: &Mat,
+) -> Result<Option<(VideoWriter, Size)>> {
+    let (mut writer, size) = match writer {
+        Some(writer) => writer,
+        None => {
+            let size = frame.size()?;
+            let writer = VideoWriter::new_with_backend(
+                pipeline,
+                videoio::CAP_GSTREAMER,
+                0,
+                f64::from(fps),
+                size,
+                true,
+            )?;
+            if !writer.is_opened()? {
+                warn!(pipeline, "unable to open rtsp output");
+                return Ok(None);
+            }
+            info!(pipeline, ?size, "rtsp output opened");
+            (writer, size)
+        }
+    };
+    if frame.size()? == size {
+        writer.write(frame)?;
+    } else {
+        let mut scaled = Mat::default();
+        imgproc::resize(frame, &mut scaled, size, 0., 0., imgproc::INTER_AREA)?;
+        writer.write(&scaled)?;
+    }
+    Ok(Some((writer, size)))
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Datelike, Local, Timelike};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::interval;
+use tracing::{debug, info, instrument, warn};
+
+use crate::auto_trigger::ControlMsg;
+
+/// How often the schedules are checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Seconds the scheduler catches up on after a stall, older matches are skipped.
+const MAX_CATCH_UP_SECONDS: i64 = 60;
+
+/// Ranges of the fields of a cron expression: second, minute, hour, day of month, month and day
+/// of week (0 or 7 is sunday).
+const FIELDS: [(&str, u32, u32); 6] = [
+    ("second", 0, 59),
+    ("minute", 0, 59),
+    ("hour", 0, 23),
+    ("day of month", 1, 31),
+    ("month", 1, 12),
+    ("day of week", 0, 7),
+];
+
+/// Cron expression with a seconds field, e.g. `0 */5 * * * *` for every 5 minutes.
+///
+/// A time matches when all six fields match, including both the day of month and day of week.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cron {
+    expression: String,
+    /// Bit `n` of each field is set when the value `n` matches.
+    fields: [u64; 6],
+}
+
+impl Cron {
+    /// Whether the cron expression matches the second `time` falls in.
+    pub fn matches(&self, time: &DateTime<Local>) -> bool {
+        let values = [
+            time.second(),
+            time.minute(),
+            time.hour(),
+            time.day(),
+            time.month(),
+            time.weekday().num_days_from_sunday(),
+        ];
+        self.fields
+            .iter()
+            .zip(values)
+            .all(|(field, value)| field & 1 << value != 0)
+    }
+}
+
+impl TryFrom<String> for Cron {
+    type Error = String;
+
+    fn try_from(expression: String) -> Result<Self, Self::Error> {
+        let parts: Vec<_> = expression.split_whitespace().collect();
+        if parts.len() != FIELDS.len() {
+            return Err(format!(
+                "cron expression must have {} fields: {}",
+                FIELDS.len(),
+                expression
+            ));
+        }
+        let mut fields = [0; 6];
+        for ((field, part), (name, min, max)) in fields.iter_mut().zip(parts).zip(FIELDS) {
+            *field = parse_field(part, min, max)
+                .ok_or_else(|| format!("invalid {} in cron expression: {}", name, part))?;
+        }
+        // sunday is both 0 and 7
+        if fields[5] & 1 << 7 != 0 {
+            fields[5] |= 1;
+        }
+        Ok(Self { expression, fields })
+    }
+}
+
+impl From<Cron> for String {
+    fn from(cron: Cron) -> Self {
+        cron.expression
+    }
+}
+
+impl fmt::Debug for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cron({})", self.expression)
+    }
+}
+
+/// Bits of the values matched by a comma separated list of `*`, `n` or `n-m`, each optionally
+/// followed by `/step`.
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut bits = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse().ok().filter(|&step| step > 0)?),
+            None => (item, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (from.parse().ok()?, to.parse().ok()?),
+                None => {
+                    let value = range.parse().ok()?;
+                    // `n/step` runs from `n` to the end of the range
+                    (value, if item.contains('/') { max } else { value })
+                }
+            },
+        };
+        if from < min || to > max || from > to {
+            return None;
+        }
+        for value in (from..=to).step_by(step) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+/// Countdown and snapshot taken whenever the cron expression matches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub cron: Cron,
+    pub countdown_steps: usize,
+}
+
+impl Schedule {
+    /// Check the countdown has at least one step.
+    pub fn validate(&self) -> Result<()> {
+        if self.countdown_steps == 0 {
+            bail!("countdown_steps must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+/// Schedules added at runtime, by id.
+pub type SharedSchedules = Arc<RwLock<HashMap<String, Schedule>>>;
+
+/// A random (version 4) UUID identifying a new schedule.
+pub fn new_id() -> String {
+    let mut bits: u128 = rand::random();
+    bits = bits & !(0xf << 76) | 0x4 << 76;
+    bits = bits & !(0x3 << 62) | 0x2 << 62;
+    let hex = format!("{:032x}", bits);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Start the countdown of each schedule when its cron expression matches, through the
+/// auto-trigger, so it can be aborted and counts towards `--max-triggers` like any other.
+pub fn spawn(
+    schedules: SharedSchedules,
+    trigger_control_sender: mpsc::Sender<ControlMsg>,
+    exit_receiver: broadcast::Receiver<bool>,
+) -> tokio::task::JoinHandle<Result<()>> {
+    debug!("spawning scheduler");
+    tokio::spawn(scheduler(schedules, trigger_control_sender, exit_receiver))
+}
+
+#[instrument(skip_all)]
+async fn scheduler(
+    schedules: SharedSchedules,
+    trigger_control_sender: mpsc::Sender<ControlMsg>,
+    mut exit_receiver: broadcast::Receiver<bool>,
+) -> Result<()> {
+    info!("scheduler started");
+    let mut checked = whole_second(Local::now());
+    let mut interval = interval(POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = exit_receiver.recv() => {
+                debug!("exit received");
+                break;
+            }
+            _ = interval.tick() => {
+                let now = whole_second(Local::now());
+                if (now - checked).num_seconds() > MAX_CATCH_UP_SECONDS {
+                    warn!(?checked, "skipping schedules missed while stalled");
+                    checked = now - chrono::Duration::seconds(1);
+                }
+                while checked < now {
+                    checked += chrono::Duration::seconds(1);
+                    let due: Vec<_> = schedules
+                        .read()
+                        .unwrap()
+                        .iter()
+                        .filter(|(_, schedule)| schedule.cron.matches(&checked))
+                        .map(|(id, schedule)| (id.clone(), schedule.countdown_steps))
+                        .collect();
+                    for (id, steps) in due {
+                        info!(id, "schedule due");
+                        trigger_control_sender
+                            .send(ControlMsg::StartCountdown { steps })
+                            .await
+                            .ok();
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn whole_second(time: DateTime<Local>) -> DateTime<Local> {
+    time.with_nanosecond(0).unwrap_or(time)
+}
+
+#[test]
+fn cron_matches_fields() {
+    use chrono::TimeZone;
+
+    let cron = Cron::try_from("0 */5 9-17 * * 1-5".to_string()).unwrap();
+    // a monday
+    let time = |hour, minute, second| {
+        Local
+            .with_ymd_and_hms(2022, 6, 13, hour, minute, second)
+            .unwrap()
+    };
+    assert!(cron.matches(&time(9, 0, 0)));
+    assert!(cron.matches(&time(17, 55, 0)));
+    assert!(!cron.matches(&time(9, 3, 0)));
+    assert!(!cron.matches(&time(9, 5, 1)));
+    assert!(!cron.matches(&time(8, 5, 0)));
+    assert!(!cron.matches(&Local.with_ymd_and_hms(2022, 6, 12, 9, 0, 0).unwrap()));
+
+    assert!(Cron::try_from("0 */5 * * *".to_string()).is_err());
+    assert!(Cron::try_from("60 * * * * *".to_string()).is_err());
+}
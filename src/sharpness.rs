@@ -0,0 +1,46 @@
+use anyhow::Result;
+use opencv::{
+    core::{self, Scalar, CV_64F},
+    imgproc,
+    prelude::{Mat, MatTraitConst},
+};
+
+/// Sharpness of `frame` as the variance of its Laplacian, higher is sharper.
+pub fn sharpness(frame: &Mat) -> Result<f64> {
+    let mut gray = Mat::default();
+    if frame.channels() == 1 {
+        frame.copy_to(&mut gray)?;
+    } else {
+        imgproc::cvt_color(frame, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?;
+    }
+    let mut laplacian = Mat::default();
+    imgproc::laplacian(
+        &gray,
+        &mut laplacian,
+        CV_64F,
+        1,
+        1.,
+        0.,
+        core::BORDER_DEFAULT,
+    )?;
+    let mut mean = Scalar::default();
+    let mut stddev = Scalar::default();
+    core::mean_std_dev(&laplacian, &mut mean, &mut stddev, &core::no_array())?;
+    Ok(stddev[0] * stddev[0])
+}
+
+#[test]
+fn edges_are_sharper_than_flat() {
+    use opencv::core::{Rect, CV_8UC1};
+    use opencv::prelude::MatTrait;
+
+    let flat = Mat::new_rows_cols_with_default(8, 8, CV_8UC1, Scalar::all(128.)).unwrap();
+    let edge = flat.clone();
+    Mat::roi(&edge, Rect::new(0, 0, 4, 8))
+        .unwrap()
+        .set_to(&Scalar::all(255.), &core::no_array())
+        .unwrap();
+
+    assert!(sharpness(&flat).unwrap() < f64::EPSILON);
+    assert!(sharpness(&edge).unwrap() > 0.);
+}
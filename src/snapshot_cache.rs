@@ -0,0 +1,70 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use actix_web::web::Bytes;
+use lru::LruCache;
+
+/// Encoded snapshots kept in memory, so recent snapshots can be served without reading the disk.
+#[derive(Debug)]
+pub struct SnapshotCache {
+    entries: LruCache<PathBuf, Bytes>,
+    /// Snapshots converted to another format, keyed by path and file extension.
+    conversions: LruCache<(PathBuf, &'static str), Bytes>,
+    latest: Option<PathBuf>,
+}
+
+pub type SharedSnapshotCache = Arc<Mutex<SnapshotCache>>;
+
+impl SnapshotCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: LruCache::new(capacity.max(1)),
+            conversions: LruCache::new(capacity.max(1)),
+            latest: None,
+        }
+    }
+
+    /// Add the encoded snapshot saved at `path`, making it the latest snapshot.
+    pub fn insert(&mut self, path: PathBuf, encoded: Bytes) {
+        self.entries.put(path.clone(), encoded);
+        self.latest = Some(path);
+    }
+
+    pub fn get(&mut self, path: &Path) -> Option<Bytes> {
+        self.entries.get(&path.to_path_buf()).cloned()
+    }
+
+    /// Add the snapshot saved at `path` converted to the format of `extension`.
+    pub fn insert_converted(&mut self, path: PathBuf, extension: &'static str, encoded: Bytes) {
+        self.conversions.put((path, extension), encoded);
+    }
+
+    pub fn get_converted(&mut self, path: &Path, extension: &'static str) -> Option<Bytes> {
+        self.conversions
+            .get(&(path.to_path_buf(), extension))
+            .cloned()
+    }
+
+    /// Forget the snapshot saved at `path`, e.g. because it was deleted.
+    pub fn remove(&mut self, path: &Path) {
+        self.entries.pop(&path.to_path_buf());
+        let converted: Vec<_> = self
+            .conversions
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|(converted, _)| converted == path)
+            .cloned()
+            .collect();
+        for key in converted {
+            self.conversions.pop(&key);
+        }
+        if self.latest.as_deref() == Some(path) {
+            self.latest = None;
+        }
+    }
+
+    /// Path of the most recently saved snapshot, cached or not.
+    pub fn latest(&self) -> Option<&Path> {
+        self.latest.as_deref()
+    }
+}
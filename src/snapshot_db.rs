@@ -0,0 +1,197 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tracing::{debug, info, instrument};
+
+use crate::schedules::{Cron, Schedule};
+use crate::snapshot_repo::SnapshotRecord;
+
+/// Schema migrations, applied in order. The schema version is the number of applied migrations.
+const MIGRATIONS: [&str; 4] = [
+    include_str!("../migrations/001_initial.sql"),
+    include_str!("../migrations/002_add_trigger_source.sql"),
+    include_str!("../migrations/003_add_camera_name.sql"),
+    include_str!("../migrations/004_add_schedules.sql"),
+];
+
+/// Snapshot metadata stored in the database.
+#[derive(Debug, Serialize)]
+pub struct DbSnapshot {
+    pub id: i64,
+    pub path: String,
+    pub saved_at: String,
+    pub width: i32,
+    pub height: i32,
+    pub trigger_source: String,
+    pub file_size_bytes: i64,
+    pub camera_name: String,
+}
+
+/// SQLite database of the metadata of every saved snapshot.
+#[derive(Debug)]
+pub struct SnapshotDb {
+    connection: Connection,
+}
+
+pub type SharedSnapshotDb = Arc<Mutex<SnapshotDb>>;
+
+impl SnapshotDb {
+    /// Open or create the database at `path`, applying any missing schema migrations.
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut connection = Connection::open(path)
+            .with_context(|| format!("unable to open database {}", path.display()))?;
+        migrate(&mut connection)
+            .with_context(|| format!("unable to migrate database {}", path.display()))?;
+        Ok(Self { connection })
+    }
+
+    #[instrument(skip(self))]
+    pub fn insert(&self, record: &SnapshotRecord, width: i32, height: i32) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO snapshots (path, saved_at, width, height, trigger_source, file_size_bytes, camera_name)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                record.path.display().to_string(),
+                to_db_timestamp(&record.saved_at.with_timezone(&Utc)),
+                width,
+                height,
+                serde_json::to_value(record.trigger_source)?
+                    .as_str()
+                    .unwrap_or_default(),
+                i64::try_from(record.file_size_bytes)?,
+                record.camera_name,
+            ],
+        )?;
+        debug!("snapshot inserted in database");
+        Ok(())
+    }
+
+    /// Snapshots saved within `from` and `to`, both inclusive, oldest first.
+    pub fn query(
+        &self,
+        from: Option<DateTime<FixedOffset>>,
+        to: Option<DateTime<FixedOffset>>,
+    ) -> Result<Vec<DbSnapshot>> {
+        let mut statement = self.connection.prepare(
+            "SELECT id, path, saved_at, width, height, trigger_source, file_size_bytes, camera_name
+            FROM snapshots
+            WHERE (?1 IS NULL OR saved_at >= ?1) AND (?2 IS NULL OR saved_at <= ?2)
+            ORDER BY saved_at",
+        )?;
+        let from = from.map(|from| to_db_timestamp(&from.with_timezone(&Utc)));
+        let to = to.map(|to| to_db_timestamp(&to.with_timezone(&Utc)));
+        let snapshots = statement
+            .query_map(params![from, to], |row| {
+                Ok(DbSnapshot {
+                    id: row.get(0)?,
+                    path: row.get(1)?,
+                    saved_at: row.get(2)?,
+                    width: row.get(3)?,
+                    height: row.get(4)?,
+                    trigger_source: row.get(5)?,
+                    file_size_bytes: row.get(6)?,
+                    camera_name: row.get(7)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(snapshots)
+    }
+
+    #[instrument(skip(self))]
+    pub fn insert_schedule(&self, id: &str, schedule: &Schedule) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO schedules (id, cron, countdown_steps) VALUES (?1, ?2, ?3)",
+            params![
+                id,
+                String::from(schedule.cron.clone()),
+                i64::try_from(schedule.countdown_steps)?,
+            ],
+        )?;
+        debug!("schedule inserted in database");
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub fn delete_schedule(&self, id: &str) -> Result<()> {
+        self.connection
+            .execute("DELETE FROM schedules WHERE id = ?1", [id])?;
+        debug!("schedule deleted from database");
+        Ok(())
+    }
+
+    /// The stored schedules along with their ids.
+    pub fn schedules(&self) -> Result<Vec<(String, Schedule)>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT id, cron, countdown_steps FROM schedules")?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter()
+            .map(|(id, cron, countdown_steps)| {
+                let schedule = Schedule {
+                    cron: Cron::try_from(cron).map_err(anyhow::Error::msg)?,
+                    countdown_steps: usize::try_from(countdown_steps)?,
+                };
+                Ok((id, schedule))
+            })
+            .collect()
+    }
+}
+
+fn migrate(connection: &mut Connection) -> Result<()> {
+    connection.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)",
+        [],
+    )?;
+    let mut version: i64 = connection.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+        [],
+        |row| row.get(0),
+    )?;
+    if version == 0 && has_trigger_source(connection)? {
+        // created with the complete schema, before migrations were introduced
+        connection.execute("INSERT INTO schema_version (version) VALUES (2)", [])?;
+        version = 2;
+    }
+
+    for (migration, sql) in MIGRATIONS
+        .iter()
+        .enumerate()
+        .skip(usize::try_from(version)?)
+    {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(sql)?;
+        transaction.execute(
+            "INSERT INTO schema_version (version) VALUES (?1)",
+            [i64::try_from(migration + 1)?],
+        )?;
+        transaction.commit()?;
+        info!(version = migration + 1, "database migrated");
+    }
+    Ok(())
+}
+
+fn has_trigger_source(connection: &Connection) -> Result<bool> {
+    let count: i64 = connection.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('snapshots') WHERE name = 'trigger_source'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Timestamps are stored as UTC RFC 3339 strings, so they can be compared as text.
+fn to_db_timestamp(timestamp: &DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
@@ -1,55 +1,838 @@
-use anyhow::Result;
-use opencv::{core::Vector, imgcodecs, prelude::Mat};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Local};
+use opencv::{
+    core::{self, Size, Vector},
+    imgcodecs, imgproc,
+    prelude::{Mat, MatTraitConst, MatTraitConstManual},
+};
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::VecDeque,
     fs::create_dir_all,
     path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
 };
-use tracing::{info, instrument, trace, warn};
+use tracing::{debug, info, instrument, trace, warn};
+
+use crate::error::CameraBotError;
+
+/// Information about a snapshot stored in the repository.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotMeta {
+    pub filename: String,
+    /// Modification time of the file, RFC 3339 formatted.
+    pub timestamp: String,
+    pub size_bytes: u64,
+}
+
+/// What caused a snapshot to be taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerSource {
+    /// The enter key was pressed in the UI.
+    Keyboard,
+    /// The auto-trigger or the REST API triggered.
+    Trigger,
+    /// Requested through `POST /snapshot`.
+    Api,
+    /// Downloaded by `gphoto2` from a tethered camera.
+    Tether,
+}
+
+/// A snapshot saved since startup.
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotRecord {
+    pub path: PathBuf,
+    pub saved_at: DateTime<Local>,
+    pub file_size_bytes: u64,
+    pub trigger_source: TriggerSource,
+    pub counter: usize,
+    pub camera_name: String,
+}
+
+/// Grid of snapshots combined into a single collage image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollageLayout {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+/// An image written by [`SnapshotRepo::save_frame`].
+#[derive(Debug)]
+pub struct SavedImage {
+    pub record: SnapshotRecord,
+    pub image: Mat,
+}
+
+/// Image format of a snapshot, from the extension of its filename.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+    /// Needs OpenCV built with an AVIF encoder, e.g. libaom.
+    Avif,
+    /// Needs OpenCV built with a HEIF encoder.
+    Heic,
+    /// Any other format supported by OpenCV, saved with its default settings.
+    Other,
+}
+
+impl OutputFormat {
+    pub fn of(filename: &Path) -> Self {
+        match filename
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .as_deref()
+        {
+            Some("jpg" | "jpeg") => Self::Jpeg,
+            Some("png") => Self::Png,
+            Some("avif") => Self::Avif,
+            Some("heic" | "heif") => Self::Heic,
+            _ => Self::Other,
+        }
+    }
+
+    /// Whether OpenCV can write the format, which depends on the codecs it was built with.
+    pub fn is_supported(self) -> bool {
+        let extension = match self {
+            Self::Avif => ".avif",
+            Self::Heic => ".heic",
+            Self::Jpeg | Self::Png | Self::Other => return true,
+        };
+        imgcodecs::have_image_writer(extension).unwrap_or(false)
+    }
+}
+
+/// Compression strategy of PNG snapshots, see `IMWRITE_PNG_STRATEGY_*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngStrategy {
+    Default,
+    Filtered,
+    HuffmanOnly,
+    Rle,
+}
+
+/// How snapshots are encoded, for the formats having settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeParams {
+    /// JPEG quality, 0-100.
+    pub jpeg_quality: u8,
+    /// PNG compression level, 0-9.
+    pub png_compression: u8,
+    pub png_strategy: PngStrategy,
+}
+
+impl Default for EncodeParams {
+    fn default() -> Self {
+        Self {
+            jpeg_quality: 95,
+            png_compression: 3,
+            png_strategy: PngStrategy::Default,
+        }
+    }
+}
+
+impl EncodeParams {
+    /// The `imwrite`/`imencode` parameters for `format`.
+    pub fn for_format(&self, format: OutputFormat) -> Vector<i32> {
+        match format {
+            OutputFormat::Jpeg => Vector::from_slice(&[
+                imgcodecs::IMWRITE_JPEG_QUALITY,
+                i32::from(self.jpeg_quality),
+            ]),
+            OutputFormat::Png => Vector::from_slice(&[
+                imgcodecs::IMWRITE_PNG_COMPRESSION,
+                i32::from(self.png_compression),
+                imgcodecs::IMWRITE_PNG_STRATEGY,
+                match self.png_strategy {
+                    PngStrategy::Default => imgcodecs::IMWRITE_PNG_STRATEGY_DEFAULT,
+                    PngStrategy::Filtered => imgcodecs::IMWRITE_PNG_STRATEGY_FILTERED,
+                    PngStrategy::HuffmanOnly => imgcodecs::IMWRITE_PNG_STRATEGY_HUFFMAN_ONLY,
+                    PngStrategy::Rle => imgcodecs::IMWRITE_PNG_STRATEGY_RLE,
+                },
+            ]),
+            OutputFormat::Avif | OutputFormat::Heic | OutputFormat::Other => Vector::new(),
+        }
+    }
+}
+
+/// Folder within the repository receiving the `--background-capture` frames.
+const BACKGROUND_FOLDER: &str = "background";
+/// JPEG quality of background frames, lower than snapshots as they're only a record.
+const BACKGROUND_JPEG_QUALITY: i32 = 60;
+
+/// Name pattern of photo strips, see `chrono` formatting.
+const PHOTO_STRIP_NAME: &str = "strip_%Y-%m-%d_%H-%M-%S.jpg";
+
+/// Number of saved snapshots remembered in the history.
+const HISTORY_CAPACITY: usize = 100;
+
+pub type SharedSnapshotRepo = Arc<Mutex<SnapshotRepo>>;
+
+/// Where the images of a repository end up.
+#[derive(Debug)]
+enum StorageBackend {
+    /// Files in the repository folder.
+    Disk,
+    /// Encoded images sent along with their file names, nothing touches the disk.
+    Memory(mpsc::Sender<(String, Vec<u8>)>),
+}
 
 #[derive(Debug)]
 pub struct SnapshotRepo {
     counter: usize,
+    /// Zero-padded width of `$COUNTER$`, from a `$COUNTER:N$` pattern.
+    counter_width: usize,
     path: PathBuf,
     name: String,
+    thumbnail_width: Option<u32>,
+    thumbnail_quality: u8,
+    encode_params: EncodeParams,
+    camera_name: String,
+    collage: Option<CollageLayout>,
+    collage_save_individual: bool,
+    /// Border in pixels around each frame of the collage.
+    collage_border: i32,
+    /// Name pattern of collages, `None` to name them like the snapshots.
+    collage_name: Option<String>,
+    /// Frames collected for the next collage.
+    pending: Vec<Mat>,
+    history: VecDeque<SnapshotRecord>,
+    /// Only log the images that would have been written.
+    dry_run: bool,
+    storage: StorageBackend,
 }
 
 impl SnapshotRepo {
     /// Create a new snapshot repository.
     ///
     /// `name` should contain the pattern `$COUNTER$` in order to supstitute the framecounter
-    /// when save snapshorts. Also, `name` may contain standard time formatting strings (see `chrono`).
+    /// when save snapshorts, or `$COUNTER:N$` to zero-pad the counter to `N` digits.
+    /// Also, `name` may contain standard time formatting strings (see `chrono`), an error is
+    /// returned when these aren't recognized.
     #[instrument(skip_all)]
-    pub fn from_path_and_namepattern(path: PathBuf, name: &str) -> Self {
-        Self {
+    pub fn from_path_and_namepattern(path: PathBuf, name: &str) -> Result<Self> {
+        validate_name_pattern(name)?;
+        let (name, counter_width) = parse_counter_width(name);
+        Ok(Self {
             counter: 0,
+            counter_width,
             path,
-            name: name.into(),
+            name,
+            thumbnail_width: None,
+            thumbnail_quality: 80,
+            encode_params: EncodeParams::default(),
+            camera_name: "camera".to_string(),
+            collage: None,
+            collage_save_individual: false,
+            collage_border: 0,
+            collage_name: None,
+            pending: Vec::new(),
+            history: VecDeque::with_capacity(HISTORY_CAPACITY),
+            dry_run: false,
+            storage: StorageBackend::Disk,
+        })
+    }
+
+    /// Create a repository keeping the snapshots in memory, mostly for testing.
+    ///
+    /// Rather than being written, each image is encoded and sent over the returned channel along
+    /// with its file name.
+    pub fn in_memory() -> (Self, mpsc::Receiver<(String, Vec<u8>)>) {
+        let (sender, receiver) = mpsc::channel();
+        let mut repo = Self::from_path_and_namepattern(PathBuf::new(), "$COUNTER:4$.jpg")
+            .expect("valid name pattern");
+        repo.storage = StorageBackend::Memory(sender);
+        (repo, receiver)
+    }
+
+    /// Additionally save a downscaled copy of each snapshot, `width` pixels wide.
+    ///
+    /// The thumbnail is saved next to the snapshot with `_thumb` appended to the file stem.
+    pub fn with_thumbnails(mut self, width: Option<u32>, quality: u8) -> Self {
+        self.thumbnail_width = width;
+        self.thumbnail_quality = quality;
+        self
+    }
+
+    /// Settings used when saving snapshots as JPEG or PNG.
+    pub fn with_encode_params(mut self, encode_params: EncodeParams) -> Self {
+        self.encode_params = encode_params;
+        self
+    }
+
+    /// Name of the camera, recorded with every snapshot.
+    pub fn with_camera_name(mut self, camera_name: String) -> Self {
+        self.camera_name = camera_name;
+        self
+    }
+
+    /// Collect the frames into collages of `layout` instead of saving them one by one.
+    ///
+    /// With `save_individual` each frame is saved as well.
+    pub fn with_collage(mut self, layout: Option<CollageLayout>, save_individual: bool) -> Self {
+        self.collage = layout;
+        self.collage_save_individual = save_individual;
+        self
+    }
+
+    /// Combine the frames into photo strips of `layout`, named `strip_<timestamp>.jpg`, besides
+    /// saving each frame.
+    pub fn with_photo_strip(mut self, layout: CollageLayout, border: u32) -> Self {
+        self.collage = Some(layout);
+        self.collage_save_individual = true;
+        self.collage_border = i32::try_from(border).unwrap_or(i32::MAX);
+        self.collage_name = Some(PHOTO_STRIP_NAME.to_string());
+        self
+    }
+
+    /// Go through the motions of saving snapshots, counting them, without writing any files.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn set_jpeg_quality(&mut self, quality: u8) {
+        self.encode_params.jpeg_quality = quality;
+    }
+
+    /// Encode `frame` as JPEG in memory, `quality` being 0-100.
+    pub fn encode_frame(frame: &Mat, quality: u8) -> Result<Vec<u8>> {
+        let params = Vector::from_slice(&[imgcodecs::IMWRITE_JPEG_QUALITY, i32::from(quality)]);
+        let mut encoded = Vector::new();
+        imgcodecs::imencode(".jpg", frame, &mut encoded, &params)?;
+        Ok(encoded.to_vec())
+    }
+
+    /// Save `frame`, or add it to the pending collage.
+    ///
+    /// Returns the images written: `frame` unless it only went into the collage, followed by the
+    /// collage once all of its frames are collected.
+    #[instrument(skip(self, frame))]
+    pub fn save_frame(
+        &mut self,
+        frame: &Mat,
+        source: TriggerSource,
+    ) -> Result<Vec<SavedImage>, CameraBotError> {
+        let layout = match self.collage {
+            Some(layout) => layout,
+            None => return Ok(vec![self.write_image(frame, source)?]),
+        };
+        let mut saved = Vec::new();
+        if self.collage_save_individual {
+            saved.push(self.write_image(frame, source)?);
         }
+        self.pending.push(frame.clone());
+        debug!(frames = self.pending.len(), "frame added to collage");
+        if self.pending.len() == layout.columns * layout.rows {
+            let collage = tile(
+                &std::mem::take(&mut self.pending),
+                layout,
+                self.collage_border,
+            )?;
+            let filename = match &self.collage_name {
+                Some(name) => self.path.join(Local::now().format(name).to_string()),
+                None => self.get_filename(),
+            };
+            saved.push(self.write_image_to(&collage, filename, source)?);
+        }
+        Ok(saved)
     }
 
-    #[instrument]
-    pub fn save_frame(&mut self, frame: &Mat) -> Result<()> {
+    fn write_image(
+        &mut self,
+        frame: &Mat,
+        source: TriggerSource,
+    ) -> Result<SavedImage, CameraBotError> {
         let filename = self.get_filename();
-        create_dir_all(Path::new(self.path.as_path()).parent().unwrap())?;
-        imgcodecs::imwrite(&filename.display().to_string(), &frame, &Vector::default())?;
+        self.write_image_to(frame, filename, source)
+    }
+
+    fn write_image_to(
+        &mut self,
+        frame: &Mat,
+        mut filename: PathBuf,
+        source: TriggerSource,
+    ) -> Result<SavedImage, CameraBotError> {
+        if self.dry_run {
+            info!(?filename, "dry run, image not saved");
+            return Ok(self.counted(frame, filename, 0, source));
+        }
+        let mut format = OutputFormat::of(&filename);
+        if !format.is_supported() {
+            warn!(
+                ?format,
+                "output format unavailable in OpenCV, saving as JPEG"
+            );
+            filename = filename.with_extension("jpg");
+            format = OutputFormat::Jpeg;
+        }
+        let params = self.encode_params.for_format(format);
+        let file_size_bytes = self.store(&filename, frame, &params)?;
         info!(?filename, "Image saved");
+        if let Some(width) = self.thumbnail_width {
+            self.save_thumbnail(frame, &filename, width)?;
+        }
+        Ok(self.counted(frame, filename, file_size_bytes, source))
+    }
+
+    /// Write `image` as `filename` to the storage, returning its size in bytes.
+    fn store(
+        &self,
+        filename: &Path,
+        image: &Mat,
+        params: &Vector<i32>,
+    ) -> Result<u64, CameraBotError> {
+        match &self.storage {
+            StorageBackend::Disk => {
+                if let Some(folder) = filename.parent() {
+                    create_dir_all(folder)?;
+                }
+                imgcodecs::imwrite(&filename.display().to_string(), image, params)?;
+                Ok(std::fs::metadata(filename)?.len())
+            }
+            StorageBackend::Memory(sender) => {
+                let extension = filename.extension().unwrap_or_default().to_string_lossy();
+                let mut encoded = Vector::new();
+                imgcodecs::imencode(&format!(".{}", extension), image, &mut encoded, params)?;
+                let encoded = encoded.to_vec();
+                let size = encoded.len() as u64;
+                sender
+                    .send((filename.display().to_string(), encoded))
+                    .map_err(|_| {
+                        CameraBotError::SnapshotSave("in-memory storage closed".to_string())
+                    })?;
+                Ok(size)
+            }
+        }
+    }
+
+    /// Record the image saved as `filename` in the history, and count it.
+    fn counted(
+        &mut self,
+        frame: &Mat,
+        filename: PathBuf,
+        file_size_bytes: u64,
+        source: TriggerSource,
+    ) -> SavedImage {
+        let record = SnapshotRecord {
+            file_size_bytes,
+            path: filename,
+            saved_at: Local::now(),
+            trigger_source: source,
+            counter: self.counter,
+            camera_name: self.camera_name.clone(),
+        };
+        self.record(record.clone());
         self.counter += 1;
+        SavedImage {
+            record,
+            image: frame.clone(),
+        }
+    }
+
+    /// Save a raw sensor frame next to the snapshot at `snapshot_path`, as a 16 bit TIFF.
+    pub fn save_raw(&self, raw: &Mat, snapshot_path: &Path) -> Result<PathBuf> {
+        let scale = if raw.depth() == core::CV_8U { 256. } else { 1. };
+        let mut raw_16 = Mat::default();
+        raw.convert_to(&mut raw_16, core::CV_16U, scale, 0.)?;
+        let filename = snapshot_path.with_extension("tiff");
+        if self.dry_run {
+            info!(?filename, "dry run, raw image not saved");
+            return Ok(filename);
+        }
+        self.store(&filename, &raw_16, &Vector::new())?;
+        info!(?filename, "raw image saved");
+        Ok(filename)
+    }
+
+    /// Save `frame` to the background folder, named by the current time.
+    ///
+    /// Background frames aren't snapshots, so they're neither counted nor added to the history.
+    pub fn save_background(&self, frame: &Mat) -> Result<PathBuf> {
+        let folder = self.path.join(BACKGROUND_FOLDER);
+        let filename = folder.join(format!("{}.jpg", Local::now().format("%Y%m%d-%H%M%S%.3f")));
+        let params =
+            Vector::from_slice(&[imgcodecs::IMWRITE_JPEG_QUALITY, BACKGROUND_JPEG_QUALITY]);
+        self.store(&filename, frame, &params)?;
+        debug!(?filename, "background frame saved");
+        Ok(filename)
+    }
+
+    fn record(&mut self, record: SnapshotRecord) {
+        if self.history.len() == HISTORY_CAPACITY {
+            self.history.pop_front();
+        }
+        self.history.push_back(record);
+    }
+
+    /// Path of the most recently saved snapshot still present in the history.
+    pub fn last_saved_path(&self) -> Option<&Path> {
+        self.history.back().map(|record| record.path.as_path())
+    }
+
+    /// The most recently saved snapshots, newest first.
+    pub fn history(&self, limit: usize) -> Vec<SnapshotRecord> {
+        self.history.iter().rev().take(limit).cloned().collect()
+    }
+
+    /// List the snapshots in the repository folder, sorted by filename.
+    ///
+    /// Files are considered snapshots when they have the extension of the name pattern and are
+    /// not thumbnails.
+    pub fn list(&self) -> Result<Vec<SnapshotMeta>> {
+        let mut snapshots = Vec::new();
+        for (path, metadata) in self.snapshot_files()? {
+            snapshots.push(SnapshotMeta {
+                filename: path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                timestamp: DateTime::<Local>::from(metadata.modified()?).to_rfc3339(),
+                size_bytes: metadata.len(),
+            });
+        }
+        snapshots.sort_by(|a, b| a.filename.cmp(&b.filename));
+        Ok(snapshots)
+    }
+
+    /// Paths of the `limit` most recently modified snapshots in the repository folder, newest
+    /// first.
+    pub fn recent(&self, limit: usize) -> Result<Vec<PathBuf>> {
+        let mut snapshots = Vec::new();
+        for (path, metadata) in self.snapshot_files()? {
+            snapshots.push((metadata.modified()?, path));
+        }
+        snapshots.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        Ok(snapshots
+            .into_iter()
+            .take(limit)
+            .map(|(_, path)| path)
+            .collect())
+    }
+
+    fn snapshot_files(&self) -> Result<Vec<(PathBuf, std::fs::Metadata)>> {
+        let extension = Path::new(&self.name).extension();
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&self.path)? {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+            let is_thumbnail = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .is_some_and(|stem| stem.ends_with("_thumb"));
+            if metadata.is_file() && !is_thumbnail && path.extension() == extension {
+                files.push((path, metadata));
+            }
+        }
+        Ok(files)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn counter(&self) -> usize {
+        self.counter
+    }
+
+    /// Restart the counter at 0, first renaming the output folder to `rename_to` if given.
+    ///
+    /// No files are deleted, existing snapshots are skipped when counting up again.
+    #[instrument(skip(self))]
+    pub fn reset(&mut self, rename_to: Option<&Path>) -> std::io::Result<()> {
+        if let Some(rename_to) = rename_to {
+            std::fs::rename(&self.path, rename_to)?;
+            create_dir_all(&self.path)?;
+            info!(from = ?self.path, "output folder renamed");
+        }
+        self.counter = 0;
+        Ok(())
+    }
+
+    /// Save the following snapshots in the folder `path`, creating it if needed.
+    #[instrument(skip(self))]
+    pub fn set_path(&mut self, path: PathBuf) -> std::io::Result<()> {
+        create_dir_all(&path)?;
+        info!("output folder changed");
+        self.path = path;
+        Ok(())
+    }
+
+    /// Remove the snapshot `filename` from the repository folder, returning its full path.
+    #[instrument]
+    pub fn delete(&mut self, filename: &str) -> std::io::Result<PathBuf> {
+        let path = self.path.join(filename);
+        std::fs::remove_file(&path)?;
+        self.history.retain(|record| record.path != path);
+        info!(?path, "Image deleted");
+        Ok(path)
+    }
+
+    fn save_thumbnail(
+        &self,
+        frame: &Mat,
+        filename: &Path,
+        width: u32,
+    ) -> Result<(), CameraBotError> {
+        let width = i32::try_from(width).unwrap_or(i32::MAX);
+        let height = frame.rows() * width / frame.cols().max(1);
+        let mut thumbnail = Mat::default();
+        imgproc::resize(
+            frame,
+            &mut thumbnail,
+            Size::new(width, height),
+            0.,
+            0.,
+            imgproc::INTER_AREA,
+        )?;
+
+        let mut thumbnail_name = filename.file_stem().unwrap_or_default().to_os_string();
+        thumbnail_name.push("_thumb");
+        if let Some(extension) = filename.extension() {
+            thumbnail_name.push(".");
+            thumbnail_name.push(extension);
+        }
+        let thumbnail_filename = filename.with_file_name(thumbnail_name);
+
+        let params = Vector::from_slice(&[
+            imgcodecs::IMWRITE_JPEG_QUALITY,
+            i32::from(self.thumbnail_quality),
+        ]);
+        self.store(&thumbnail_filename, &thumbnail, &params)?;
+        info!(?thumbnail_filename, "Thumbnail saved");
         Ok(())
     }
 
     fn get_filename(&mut self) -> PathBuf {
         let now = chrono::Local::now().format(&self.name).to_string();
-        let mut filename = self
-            .path
-            .join(now.replace("$COUNTER$", &self.counter.to_string()));
-        while filename.exists() {
+        let mut filename = self.path.join(self.substitute_counter(&now));
+        while matches!(self.storage, StorageBackend::Disk) && filename.exists() {
             warn!(?filename, "file already exists");
             self.counter += 1;
-            filename = self
-                .path
-                .join(self.name.replace("$COUNTER$", &self.counter.to_string()));
+            filename = self.path.join(self.substitute_counter(&now));
         }
         trace!(?filename);
         filename
     }
+
+    fn substitute_counter(&self, name: &str) -> String {
+        name.replace(
+            "$COUNTER$",
+            &format!("{:0>width$}", self.counter, width = self.counter_width),
+        )
+    }
+}
+
+/// Tile `frames` row by row into a grid of `layout`, resizing them to the size of the first.
+///
+/// Each frame is surrounded by a white border of `border` pixels.
+fn tile(frames: &[Mat], layout: CollageLayout, border: i32) -> opencv::Result<Mat> {
+    let size = match frames.first() {
+        Some(frame) => frame.size()?,
+        None => return Ok(Mat::default()),
+    };
+    let mut rows = Vector::<Mat>::new();
+    for row in frames.chunks(layout.columns) {
+        let mut cells = Vector::<Mat>::new();
+        for frame in row {
+            let mut cell = if frame.size()? == size {
+                frame.clone()
+            } else {
+                let mut resized = Mat::default();
+                imgproc::resize(frame, &mut resized, size, 0., 0., imgproc::INTER_AREA)?;
+                resized
+            };
+            if border > 0 {
+                let mut bordered = Mat::default();
+                core::copy_make_border(
+                    &cell,
+                    &mut bordered,
+                    border,
+                    border,
+                    border,
+                    border,
+                    core::BORDER_CONSTANT,
+                    core::Scalar::all(255.),
+                )?;
+                cell = bordered;
+            }
+            cells.push(cell);
+        }
+        let mut tiled = Mat::default();
+        core::hconcat(&cells, &mut tiled)?;
+        rows.push(tiled);
+    }
+    let mut collage = Mat::default();
+    core::vconcat(&rows, &mut collage)?;
+    Ok(collage)
+}
+
+/// Check the time formatting strings of the name pattern `name` are all recognized by `chrono`.
+///
+/// Unrecognized specifiers would otherwise end up in the file names literally, or fail to format.
+fn validate_name_pattern(name: &str) -> Result<()> {
+    use chrono::{TimeZone, Utc};
+    use std::fmt::Write;
+
+    // `%%` is a literal `%` in the output
+    let template = name.replace("%%", "");
+    let reference = Utc.with_ymd_and_hms(2001, 2, 3, 4, 5, 6).unwrap();
+    let mut formatted = String::new();
+    if write!(formatted, "{}", reference.format(&template)).is_err()
+        || (template.contains('%') && formatted == template)
+        || formatted.contains('%')
+    {
+        bail!("invalid time format in file name pattern: {}", name);
+    }
+    Ok(())
+}
+
+/// Replace a `$COUNTER:N$` pattern in `name` by `$COUNTER$`, returning the width `N`.
+fn parse_counter_width(name: &str) -> (String, usize) {
+    if let Some(start) = name.find("$COUNTER:") {
+        let rest = &name[start + "$COUNTER:".len()..];
+        if let Some(end) = rest.find('$') {
+            if let Ok(width) = rest[..end].parse() {
+                let name = format!("{}$COUNTER${}", &name[..start], &rest[end + 1..]);
+                return (name, width);
+            }
+        }
+    }
+    (name.into(), 0)
+}
+
+#[test]
+fn counter_is_zero_padded() {
+    let mut repo =
+        SnapshotRepo::from_path_and_namepattern("captures".into(), "$COUNTER:4$.jpg").unwrap();
+    assert_eq!(repo.get_filename(), Path::new("captures/0000.jpg"));
+    repo.counter = 12345;
+    assert_eq!(repo.get_filename(), Path::new("captures/12345.jpg"));
+}
+
+#[test]
+fn counter_without_width_is_not_padded() {
+    let mut repo =
+        SnapshotRepo::from_path_and_namepattern("captures".into(), "img_$COUNTER$.jpg").unwrap();
+    repo.counter = 7;
+    assert_eq!(repo.get_filename(), Path::new("captures/img_7.jpg"));
+}
+
+#[test]
+fn counter_skips_existing_files() {
+    let path = std::env::temp_dir().join(format!("camera-bot-repo-{}", std::process::id()));
+    create_dir_all(&path).unwrap();
+    std::fs::write(path.join("0000.jpg"), b"").unwrap();
+    std::fs::write(path.join("0001.jpg"), b"").unwrap();
+
+    let mut repo =
+        SnapshotRepo::from_path_and_namepattern(path.clone(), "$COUNTER:4$.jpg").unwrap();
+    let filename = repo.get_filename();
+    std::fs::remove_dir_all(&path).unwrap();
+
+    assert_eq!(filename, path.join("0002.jpg"));
+    assert_eq!(repo.counter, 2);
+}
+
+#[test]
+fn reset_renames_output_folder() {
+    let temp = std::env::temp_dir().join(format!("camera-bot-reset-{}", std::process::id()));
+    let path = temp.join("captures");
+    create_dir_all(&path).unwrap();
+    std::fs::write(path.join("0000.jpg"), b"").unwrap();
+
+    let mut repo =
+        SnapshotRepo::from_path_and_namepattern(path.clone(), "$COUNTER:4$.jpg").unwrap();
+    repo.counter = 5;
+    repo.reset(Some(&temp.join("archive"))).unwrap();
+    let archived = temp.join("archive/0000.jpg").exists();
+    let filename = repo.get_filename();
+    std::fs::remove_dir_all(&temp).unwrap();
+
+    assert!(archived);
+    assert_eq!(filename, path.join("0000.jpg"));
+    assert_eq!(repo.counter, 0);
+}
+
+#[test]
+fn dry_run_counts_without_writing() {
+    use opencv::core::{Scalar, CV_8UC3};
+
+    let path = std::env::temp_dir().join(format!("camera-bot-dry-run-{}", std::process::id()));
+    let mut repo = SnapshotRepo::from_path_and_namepattern(path.clone(), "$COUNTER:4$.jpg")
+        .unwrap()
+        .with_dry_run(true);
+    let frame = Mat::new_rows_cols_with_default(2, 3, CV_8UC3, Scalar::all(0.)).unwrap();
+    let saved = repo.save_frame(&frame, TriggerSource::Api).unwrap();
+
+    assert_eq!(saved[0].record.path, path.join("0000.jpg"));
+    assert_eq!(repo.counter, 1);
+    assert!(!path.exists());
+}
+
+#[test]
+fn in_memory_sends_encoded_images() {
+    use opencv::core::{Scalar, CV_8UC3};
+
+    let (mut repo, receiver) = SnapshotRepo::in_memory();
+    let frame = Mat::new_rows_cols_with_default(2, 3, CV_8UC3, Scalar::all(0.)).unwrap();
+    let saved = repo.save_frame(&frame, TriggerSource::Api).unwrap();
+
+    let (filename, encoded) = receiver.try_recv().unwrap();
+    assert_eq!(filename, "0000.jpg");
+    assert_eq!(saved[0].record.file_size_bytes, encoded.len() as u64);
+    assert!(!Path::new(&filename).exists());
+}
+
+#[test]
+fn collage_tiles_frames_in_grid() {
+    use opencv::core::{Scalar, CV_8UC3};
+
+    let frames: Vec<_> = (0..4)
+        .map(|n| Mat::new_rows_cols_with_default(2, 3, CV_8UC3, Scalar::all(f64::from(n))).unwrap())
+        .collect();
+    let layout = CollageLayout {
+        columns: 2,
+        rows: 2,
+    };
+    let collage = tile(&frames, layout, 0).unwrap();
+
+    assert_eq!((collage.cols(), collage.rows()), (6, 4));
+    let pixel = |x, y| collage.at_2d::<opencv::core::Vec3b>(y, x).unwrap()[0];
+    assert_eq!(
+        [pixel(0, 0), pixel(3, 0), pixel(0, 2), pixel(3, 2)],
+        [0, 1, 2, 3]
+    );
+}
+
+#[test]
+fn output_format_from_extension() {
+    assert_eq!(OutputFormat::of(Path::new("a.JPG")), OutputFormat::Jpeg);
+    assert_eq!(OutputFormat::of(Path::new("a.jpeg")), OutputFormat::Jpeg);
+    assert_eq!(OutputFormat::of(Path::new("a.png")), OutputFormat::Png);
+    assert_eq!(OutputFormat::of(Path::new("a.avif")), OutputFormat::Avif);
+    assert_eq!(OutputFormat::of(Path::new("a.HEIC")), OutputFormat::Heic);
+    assert_eq!(OutputFormat::of(Path::new("a.bmp")), OutputFormat::Other);
+    assert_eq!(OutputFormat::of(Path::new("a")), OutputFormat::Other);
+}
+
+#[test]
+fn name_pattern_validation() {
+    let valid = [
+        "%Y-%m-%d_%H%M%S.jpg",
+        "$COUNTER$.jpg",
+        "$COUNTER:4$.jpg",
+        "booth_%Y%m%d_$COUNTER:3$.png",
+        "100%%_$COUNTER$.jpg",
+    ];
+    for name in valid {
+        assert!(validate_name_pattern(name).is_ok(), "{}", name);
+    }
+    for name in ["%Q.jpg", "%Y-%Q_$COUNTER$.jpg", "snapshot_%.jpg"] {
+        assert!(validate_name_pattern(name).is_err(), "{}", name);
+    }
+    assert!(SnapshotRepo::from_path_and_namepattern("captures".into(), "%Q.jpg").is_err());
 }
@@ -0,0 +1,19 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/// Runtime statistics exposed through `GET /stats`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Stats {
+    /// Name of the camera, from `--camera-name`.
+    pub camera_name: String,
+    /// Last exposure value read from the camera.
+    pub exposure: Option<f64>,
+    /// Display frame rate, averaged over the last 30 frames.
+    pub fps: Option<f64>,
+    /// When camera-bot was started.
+    pub started_at: Option<DateTime<Local>>,
+}
+
+pub type SharedStats = Arc<Mutex<Stats>>;
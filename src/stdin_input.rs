@@ -0,0 +1,127 @@
+use std::io::{self, Read};
+
+use anyhow::Result;
+use opencv::{
+    core::{Scalar, CV_8UC2, CV_8UC3},
+    imgproc,
+    prelude::*,
+};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, info, instrument, warn};
+
+use crate::args::VideoParams;
+use crate::capture_thread::{Command, FramePublisher};
+use crate::image_adjust::ImageAdjustment;
+
+/// Pixel format of the raw frames read from stdin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StdinFormat {
+    Bgr24,
+    Yuyv,
+}
+
+impl StdinFormat {
+    const fn mat_type(self) -> i32 {
+        match self {
+            Self::Bgr24 => CV_8UC3,
+            Self::Yuyv => CV_8UC2,
+        }
+    }
+}
+
+/// Size and format of the raw frames read from stdin, each frame being `width * height`
+/// pixels without any header.
+#[derive(Debug, Clone, Copy)]
+pub struct StdinInput {
+    pub width: u32,
+    pub height: u32,
+    pub format: StdinFormat,
+}
+
+/// Capture loop reading the frames from stdin instead of a camera.
+#[instrument(skip_all)]
+pub fn frame_grabber(
+    input: StdinInput,
+    start_sender: &mut mpsc::Sender<Option<ImageAdjustment>>,
+    video_params: VideoParams,
+    mut command_receiver: mpsc::Receiver<Command>,
+    frame_event_sender: broadcast::Sender<Mat>,
+    mut exit_receiver: broadcast::Receiver<bool>,
+) -> Result<()> {
+    info!(?input, "reading frames from stdin");
+    let ignored = video_params.ignored_by_pipeline();
+    if !ignored.is_empty() {
+        warn!(?ignored, "flags are ignored when reading frames from stdin");
+    }
+
+    let mut raw = Mat::new_rows_cols_with_default(
+        i32::try_from(input.height)?,
+        i32::try_from(input.width)?,
+        input.format.mat_type(),
+        Scalar::all(0.),
+    )?;
+    // there's no camera to adjust, so all of the adjustment is done in software
+    start_sender
+        .blocking_send(Some(video_params.adjustment()))
+        .ok();
+
+    let mut stdin = io::stdin().lock();
+    let mut converted = Mat::default();
+    let mut publisher = FramePublisher::new(frame_event_sender, &video_params);
+    loop {
+        match stdin.read_exact(raw.data_bytes_mut()?) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                info!("end of stdin input");
+                break;
+            }
+            Err(err) => return Err(err.into()),
+        }
+        let frame = match input.format {
+            StdinFormat::Bgr24 => &raw,
+            StdinFormat::Yuyv => {
+                imgproc::cvt_color(&raw, &mut converted, imgproc::COLOR_YUV2BGR_YUYV, 0)?;
+                &converted
+            }
+        };
+        if !publisher.publish(frame)? {
+            info!("all receivers has left");
+            break;
+        }
+        if let Ok(command) = command_receiver.try_recv() {
+            match command {
+                Command::Snapshot(sender, span) => {
+                    let _span = span.enter();
+                    debug!("snapshot taken from stdin");
+                    sender.send(frame.clone()).ok();
+                }
+                Command::SnapshotAtResolution { reply, .. } => {
+                    debug!("snapshot taken from stdin at the input resolution");
+                    reply.send(frame.clone()).ok();
+                }
+                Command::RawSnapshot(sender) => {
+                    sender.send(None).ok();
+                }
+                Command::GetStats(sender) => {
+                    sender.send(publisher.stats()).ok();
+                }
+                Command::GetProperty(property, _) => {
+                    debug!(property, "no camera properties when reading from stdin");
+                }
+                Command::GetInfo(_) => {
+                    debug!("no camera info when reading from stdin");
+                }
+                Command::SetVideoWidth(_) | Command::LockExposure | Command::UnlockExposure => {
+                    debug!("camera command ignored when reading from stdin");
+                }
+            }
+        }
+        if exit_receiver.try_recv().is_ok() {
+            info!("exit received");
+            break;
+        }
+    }
+
+    warn!("exiting");
+    Ok(())
+}
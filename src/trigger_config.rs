@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use tokio::sync::broadcast;
+use tokio::time::interval;
+use tracing::{debug, info, instrument, warn};
+
+use crate::args::TriggerParams;
+use crate::auto_trigger::SharedTriggerParams;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Apply the `--trigger-config` file on top of `base`, reapplying it whenever it's modified.
+pub fn spawn(
+    path: PathBuf,
+    base: TriggerParams,
+    params: SharedTriggerParams,
+    exit_receiver: broadcast::Receiver<bool>,
+) -> tokio::task::JoinHandle<Result<()>> {
+    debug!("spawning trigger config watcher");
+    tokio::spawn(watch_trigger_config(path, base, params, exit_receiver))
+}
+
+#[instrument(skip(base, params, exit_receiver))]
+async fn watch_trigger_config(
+    path: PathBuf,
+    base: TriggerParams,
+    params: SharedTriggerParams,
+    mut exit_receiver: broadcast::Receiver<bool>,
+) -> Result<()> {
+    info!("trigger config watcher started");
+    let mut modified = modified_time(&path);
+    let mut interval = interval(POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = exit_receiver.recv() => {
+                debug!("exit received");
+                break;
+            }
+            _ = interval.tick() => {
+                let now_modified = modified_time(&path);
+                if now_modified == modified {
+                    continue;
+                }
+                modified = now_modified;
+                let mut updated = base.clone();
+                match updated.update_from_file(&path) {
+                    Ok(()) => {
+                        info!(params = ?updated, "trigger config reloaded");
+                        *params.write().unwrap() = updated;
+                    }
+                    Err(err) => warn!(?err, "invalid trigger config, keeping the current one"),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
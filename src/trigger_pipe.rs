@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::unix::pipe;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::sleep;
+use tracing::{debug, info, instrument, warn};
+
+use crate::auto_trigger::{ControlMsg, EventMsg};
+
+/// Delay before reopening the pipe once all writers have closed it.
+const REOPEN_DELAY: Duration = Duration::from_millis(250);
+
+pub fn spawn(
+    path: PathBuf,
+    trigger_event_sender: broadcast::Sender<EventMsg>,
+    trigger_control_sender: mpsc::Sender<ControlMsg>,
+    exit_receiver: broadcast::Receiver<bool>,
+) -> tokio::task::JoinHandle<Result<()>> {
+    debug!("spawning trigger pipe");
+    tokio::spawn(trigger_pipe(
+        path,
+        trigger_event_sender,
+        trigger_control_sender,
+        exit_receiver,
+    ))
+}
+
+/// Read commands from a named pipe, one per line: `trigger`, `stop` or `run`.
+///
+/// The pipe is opened non-blocking, so an exit isn't held up waiting for a writer.
+#[instrument(skip(trigger_event_sender, trigger_control_sender, exit_receiver))]
+async fn trigger_pipe(
+    path: PathBuf,
+    trigger_event_sender: broadcast::Sender<EventMsg>,
+    trigger_control_sender: mpsc::Sender<ControlMsg>,
+    mut exit_receiver: broadcast::Receiver<bool>,
+) -> Result<()> {
+    info!("trigger pipe started");
+    loop {
+        let mut lines = BufReader::new(pipe::OpenOptions::new().open_receiver(&path)?).lines();
+        loop {
+            let line = tokio::select! {
+                _ = exit_receiver.recv() => {
+                    debug!("exit received");
+                    warn!("exit trigger pipe");
+                    return Ok(());
+                }
+                line = lines.next_line() => line?,
+            };
+            let line = match line {
+                Some(line) => line,
+                None => break,
+            };
+            debug!(?line, "line read from pipe");
+            if line.contains("trigger") {
+                trigger_event_sender
+                    .send(EventMsg::Trigger {
+                        schedule_index: None,
+                    })
+                    .ok();
+            } else if line.contains("stop") {
+                trigger_control_sender.send(ControlMsg::Stop).await?;
+            } else if line.contains("run") {
+                trigger_control_sender.send(ControlMsg::Run).await?;
+            } else {
+                warn!(?line, "unknown command in trigger pipe");
+            }
+        }
+        debug!("trigger pipe closed by the writers, reopening");
+        tokio::select! {
+            _ = exit_receiver.recv() => {
+                debug!("exit received");
+                break;
+            }
+            _ = sleep(REOPEN_DELAY) => {}
+        }
+    }
+    warn!("exit trigger pipe");
+    Ok(())
+}
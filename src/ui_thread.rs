@@ -1,32 +1,139 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
 use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use opencv::{
-    core::{CV_32F, CV_8U},
-    highgui,
+    core::{Point, Rect, Scalar, CV_32F, CV_8U},
+    highgui, imgproc,
     prelude::*,
+    videoio,
 };
-use tokio::sync::{broadcast, mpsc};
+use serde::{Deserialize, Serialize, Serializer};
+use tokio::sync::{broadcast, mpsc, oneshot};
 use tracing::{debug, info, instrument, trace, warn};
 
-use crate::alpha_image::AlphaImage;
+use crate::alpha_image::{AlphaImage, BlendMode, OverlayPlacement};
+use crate::chroma_key::ChromaKey;
+use crate::crop::Crop;
+use crate::error::CameraBotError;
+use crate::frame_interpolation::FrameInterpolator;
+use crate::grid_view::GridView;
+use crate::image_adjust::ImageAdjustment;
+use crate::palette::Palette;
+use crate::pip::PictureInPicture;
+use crate::stats::SharedStats;
+use crate::zoom::Zoom;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum EventMsg {
     KeyPressed(i32),
     WindowClosed,
 }
 
+impl fmt::Display for EventMsg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KeyPressed(key) => write!(f, "Key pressed: {}", key),
+            Self::WindowClosed => write!(f, "Window closed"),
+        }
+    }
+}
+
+/// Key toggling the key binding help, handled by the UI rather than sent as an [`EventMsg`].
+const KEY_HELP: i32 = b'?' as i32;
+
+/// Z-order of the layer set by [`ControlMsg::Blend`], placed by `--overlay-scale` and faded
+/// between changes.
+pub const BLEND_LAYER: i32 = 0;
+
 #[derive(Debug)]
 pub enum ControlMsg {
     Blend(Option<AlphaImage>),
+    /// Add or replace the overlay layer at Z-order `z`, layers are blended in ascending order.
+    ///
+    /// The blend image is the layer at [`BLEND_LAYER`].
+    AddLayer(i32, AlphaImage),
+    RemoveLayer(i32),
+    /// Stack an overlay on top of the video, below the layers.
+    PushOverlay(AlphaImage),
+    /// Remove the topmost stacked overlay.
+    PopOverlay,
+    /// Remove all stacked overlays.
+    ClearOverlays,
+    /// Change how the overlays are blended with the video.
+    SetBlendMode(BlendMode),
     Freeze,
+    /// Keep showing the current image, discarding the captured frames until [`ControlMsg::Live`].
+    Pause,
     Live,
+    /// Play a video clip instead of the live feed, signalling on the sender when done.
+    ShowVideoClip(PathBuf, oneshot::Sender<()>),
+    /// Set the window title.
+    SetTitle(String),
+    /// Show a line of text at the bottom of the video, or remove it.
+    SetText(Option<String>),
+    SetZoom {
+        factor: f32,
+        cx: f32,
+        cy: f32,
+    },
+    /// The last saved snapshot, shown by `--pip-last-snapshot`.
+    LastSnapshot(Mat),
+    /// Show the countdown bar, until the next [`ControlMsg::Blend(None)`](ControlMsg::Blend).
+    ShowCountdownBar {
+        steps_total: usize,
+        steps_remaining: usize,
+    },
+}
+
+impl fmt::Display for ControlMsg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Blend(Some(_)) => write!(f, "Blend overlay"),
+            Self::Blend(None) => write!(f, "Clear overlay"),
+            Self::AddLayer(z, _) => write!(f, "Add layer {}", z),
+            Self::RemoveLayer(z) => write!(f, "Remove layer {}", z),
+            Self::PushOverlay(_) => write!(f, "Push overlay"),
+            Self::PopOverlay => write!(f, "Pop overlay"),
+            Self::ClearOverlays => write!(f, "Clear overlays"),
+            Self::SetBlendMode(mode) => write!(f, "Blend mode {:?}", mode),
+            Self::Freeze => write!(f, "Freeze"),
+            Self::Pause => write!(f, "Pause"),
+            Self::Live => write!(f, "Live"),
+            Self::ShowVideoClip(path, _) => write!(f, "Show video clip {}", path.display()),
+            Self::SetTitle(title) => write!(f, "Set title: {}", title),
+            Self::SetText(Some(text)) => write!(f, "Show text: {}", text),
+            Self::SetText(None) => write!(f, "Clear text"),
+            Self::SetZoom { factor, cx, cy } => {
+                write!(f, "Zoom {} at ({}, {})", factor, cx, cy)
+            }
+            Self::LastSnapshot(_) => write!(f, "Last snapshot"),
+            Self::ShowCountdownBar {
+                steps_total,
+                steps_remaining,
+            } => write!(f, "Countdown bar {}/{}", steps_remaining, steps_total),
+        }
+    }
+}
+
+/// Serialized as its [`Display`](fmt::Display) text, as the images and channels it may hold
+/// can't be serialized.
+impl Serialize for ControlMsg {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
 }
 
 enum VideoState {
     Live,
     Frozen,
+    /// Like `Frozen`, but draining the frames so the channel doesn't lag behind.
+    Paused,
+    Clip(videoio::VideoCapture, oneshot::Sender<()>),
 }
 
 #[derive(Debug)]
@@ -35,18 +142,49 @@ pub enum WindowMode {
     Fullscreen,
 }
 
-pub fn spawn(
-    windowmode: WindowMode,
+/// How the UI thread shows the video, set once when it's spawned.
+pub struct UiSettings {
+    pub window_mode: WindowMode,
+    /// Checks in a row the window must be invisible for, before it's considered closed.
+    pub window_close_debounce: usize,
+    pub placement: OverlayPlacement,
+    pub blend_mode: BlendMode,
+    pub transition_duration: Duration,
+    pub crop: Option<Crop>,
+    pub zoom: Zoom,
+    pub adjustment: ImageAdjustment,
+    pub chroma_key: Option<ChromaKey>,
+    /// Mirror the video horizontally.
+    pub flip: bool,
+    pub show_fps: bool,
+    pub fps_limit: Option<f32>,
+    pub grid_view: Option<GridView>,
+    pub interpolator: Option<FrameInterpolator>,
+    pub palette: Option<Palette>,
+    pub pip: Option<PictureInPicture>,
+    pub countdown_bar_color: Scalar,
+    /// Also write the processed video to stdout, as raw BGR frames.
+    pub stdout_output: bool,
+    /// Lines of the key binding help, toggled by `?`.
+    pub key_help: Vec<String>,
+}
+
+pub async fn spawn(
+    settings: UiSettings,
+    stats: SharedStats,
     ui_event_sender: broadcast::Sender<EventMsg>,
     capture_event_receiver: broadcast::Receiver<Mat>,
     exit_receiver: broadcast::Receiver<bool>,
-) -> (thread::JoinHandle<()>, mpsc::Sender<ControlMsg>) {
+) -> Result<(thread::JoinHandle<()>, mpsc::Sender<ControlMsg>), CameraBotError> {
     debug!("spawning ui thread");
+    let (started_sender, started_receiver) = oneshot::channel();
     let (ui_thread, display_control_sender) = {
         let (display_control_sender, control_receiver) = mpsc::channel(1);
         let ui_thread = thread::spawn(move || {
             ui_event_loop(
-                windowmode,
+                started_sender,
+                settings,
+                stats,
                 ui_event_sender,
                 control_receiver,
                 capture_event_receiver,
@@ -56,100 +194,507 @@ pub fn spawn(
         });
         (ui_thread, display_control_sender)
     };
-    (ui_thread, display_control_sender)
+    match started_receiver.await {
+        Ok(Ok(())) => Ok((ui_thread, display_control_sender)),
+        Ok(Err(err)) => Err(CameraBotError::DisplayOpen(err)),
+        Err(_) => Err(CameraBotError::ChannelSend),
+    }
 }
 
 #[instrument(skip_all)]
 fn ui_event_loop(
-    windowmode: WindowMode,
+    started_sender: oneshot::Sender<opencv::Result<()>>,
+    settings: UiSettings,
+    stats: SharedStats,
     event_sender: broadcast::Sender<EventMsg>,
     mut control_receiver: mpsc::Receiver<ControlMsg>,
     mut frame_receiver: broadcast::Receiver<Mat>,
     mut exit_receiver: broadcast::Receiver<bool>,
 ) -> Result<()> {
     info!("ui thread started");
+    let UiSettings {
+        window_mode: windowmode,
+        window_close_debounce,
+        placement,
+        mut blend_mode,
+        transition_duration,
+        crop,
+        mut zoom,
+        adjustment,
+        mut chroma_key,
+        flip,
+        show_fps,
+        fps_limit,
+        mut grid_view,
+        mut interpolator,
+        mut palette,
+        mut pip,
+        countdown_bar_color,
+        stdout_output,
+        key_help,
+    } = settings;
 
     let mut video_state = VideoState::Live;
-    let mut blending_image = None;
+    let mut layers: BTreeMap<i32, AlphaImage> = BTreeMap::new();
+    let mut placed_blending_image: Option<AlphaImage> = None;
+    let mut transition: Option<BlendTransition> = None;
+    let mut text: Option<String> = None;
+    let mut overlay_stack: Vec<AlphaImage> = Vec::new();
+    // steps total and remaining of the countdown bar
+    let mut countdown_bar: Option<(usize, usize)> = None;
 
     debug!("opening window");
     let window = "video capture";
-    highgui::named_window(window, highgui::WINDOW_NORMAL | highgui::WINDOW_GUI_NORMAL)?;
-    if let WindowMode::Fullscreen = windowmode {
-        highgui::set_window_property(window, highgui::WND_PROP_FULLSCREEN, 1.)?;
+    let opened = highgui::named_window(window, highgui::WINDOW_NORMAL | highgui::WINDOW_GUI_NORMAL)
+        .and_then(|()| match windowmode {
+            WindowMode::Fullscreen => {
+                highgui::set_window_property(window, highgui::WND_PROP_FULLSCREEN, 1.)
+            }
+            WindowMode::Windowed => Ok(()),
+        });
+    if let Err(err) = opened {
+        started_sender.send(Err(err)).ok();
+        return Ok(());
     }
+    started_sender.send(Ok(())).ok();
 
     let mut frame_i = Mat::default();
     let mut frame_f = Mat::default();
     let mut tmp_1_f = Mat::default();
     let mut tmp_2_f = Mat::default();
+    let mut fps_counter = FpsCounter::default();
+    let mut stdout = stdout_output.then(|| io::stdout().lock());
+    let mut show_help = false;
+    let mut invisible_checks = 0;
+    let frame_ms = fps_limit.map(|fps| (1000. / fps).round() as i32);
+    // start of the work on the previous frame, which is subtracted from the time waited
+    let mut frame_started = Instant::now();
     loop {
-        let key = highgui::wait_key(20)?;
+        let wait_ms = match frame_ms {
+            Some(frame_ms) => {
+                let elapsed_ms =
+                    i32::try_from(frame_started.elapsed().as_millis()).unwrap_or(i32::MAX);
+                (frame_ms - elapsed_ms).max(1)
+            }
+            None => 20,
+        };
+        let key = highgui::wait_key(wait_ms)?;
+        frame_started = Instant::now();
 
         if exit_receiver.try_recv().is_ok() {
             debug!("exit received");
             break;
         }
 
-        if key > 0 {
+        if key == KEY_HELP {
+            debug!(show_help = !show_help, "toggle key help");
+            show_help = !show_help;
+        } else if key > 0 {
             debug!(?key, "key event");
             event_sender.send(EventMsg::KeyPressed(key))?;
+            if let Some(grid_view) = &mut grid_view {
+                grid_view.activity();
+            }
         }
 
+        // some platforms report the window invisible spuriously
         if highgui::get_window_property(window, highgui::WND_PROP_VISIBLE)? < 1.0 {
-            debug!("window closed");
-            event_sender.send(EventMsg::WindowClosed)?;
-            break;
+            invisible_checks += 1;
+            trace!(invisible_checks, "window invisible");
+            if invisible_checks >= window_close_debounce {
+                debug!("window closed");
+                event_sender.send(EventMsg::WindowClosed)?;
+                break;
+            }
+        } else {
+            invisible_checks = 0;
         }
 
         if let Ok(msg) = control_receiver.try_recv() {
             debug!(?msg, "received control msg");
+            if let Some(grid_view) = &mut grid_view {
+                grid_view.activity();
+            }
             match msg {
+                ControlMsg::LastSnapshot(snapshot) => {
+                    if let Some(pip) = &mut pip {
+                        pip.set_snapshot(snapshot);
+                    }
+                }
+                ControlMsg::ShowCountdownBar {
+                    steps_total,
+                    steps_remaining,
+                } => countdown_bar = Some((steps_total, steps_remaining)),
                 ControlMsg::Blend(img) => {
-                    if let Ok(size) = frame_i.size() {
-                        if !size.empty() {
-                            blending_image = img.map(|img| img.resize(size));
-                        }
+                    if img.is_none() {
+                        countdown_bar = None;
+                    }
+                    let from = placed_blending_image.take();
+                    if !transition_duration.is_zero() && (from.is_some() || img.is_some()) {
+                        transition = Some(BlendTransition {
+                            from,
+                            started: Instant::now(),
+                            duration: transition_duration,
+                        });
+                    }
+                    match img {
+                        Some(img) => layers.insert(BLEND_LAYER, img),
+                        None => layers.remove(&BLEND_LAYER),
+                    };
+                }
+                ControlMsg::AddLayer(z, img) => {
+                    if z == BLEND_LAYER {
+                        placed_blending_image = None;
+                    }
+                    layers.insert(z, img);
+                }
+                ControlMsg::RemoveLayer(z) => {
+                    if z == BLEND_LAYER {
+                        placed_blending_image = None;
                     }
+                    layers.remove(&z);
                 }
+                ControlMsg::PushOverlay(img) => overlay_stack.push(img),
+                ControlMsg::PopOverlay => {
+                    overlay_stack.pop();
+                }
+                ControlMsg::ClearOverlays => overlay_stack.clear(),
+                ControlMsg::SetBlendMode(mode) => blend_mode = mode,
                 ControlMsg::Freeze => video_state = VideoState::Frozen,
+                ControlMsg::Pause => video_state = VideoState::Paused,
                 ControlMsg::Live => video_state = VideoState::Live,
+                ControlMsg::SetTitle(title) => highgui::set_window_title(window, &title)?,
+                ControlMsg::SetText(new_text) => text = new_text,
+                ControlMsg::SetZoom { factor, cx, cy } => zoom = Zoom { factor, cx, cy },
+                ControlMsg::ShowVideoClip(path, done_sender) => {
+                    match videoio::VideoCapture::from_file(
+                        &path.display().to_string(),
+                        videoio::CAP_ANY,
+                    ) {
+                        Ok(clip) if clip.is_opened()? => {
+                            video_state = VideoState::Clip(clip, done_sender);
+                        }
+                        _ => {
+                            warn!(?path, "unable to open video clip");
+                            done_sender.send(()).ok();
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(grid_view) = grid_view.as_mut().filter(|_| !frame_f.empty()) {
+            if let Some(grid) = grid_view.frame(frame_f.size()?) {
+                trace!("display grid view");
+                highgui::imshow(window, grid)?;
+                continue;
             }
         }
 
         match video_state {
             VideoState::Frozen => {}
+            VideoState::Paused => {
+                while frame_receiver.try_recv().is_ok() {
+                    trace!("discard image frame");
+                }
+            }
+            VideoState::Clip(ref mut clip, _) => {
+                let mut clip_frame = Mat::default();
+                if clip.read(&mut clip_frame)? && !clip_frame.empty() {
+                    trace!(?clip_frame, "received clip frame");
+                    clip_frame.assign_to(&mut frame_f, CV_32F)?;
+                } else {
+                    debug!("video clip ended");
+                    if let VideoState::Clip(_, done_sender) =
+                        std::mem::replace(&mut video_state, VideoState::Live)
+                    {
+                        done_sender.send(()).ok();
+                    }
+                }
+            }
             VideoState::Live => {
-                if let Ok(frame) = frame_receiver.try_recv() {
+                let received = frame_receiver.try_recv().ok();
+                let frame = match &mut interpolator {
+                    Some(interpolator) => interpolator.next(received)?,
+                    None => received,
+                };
+                if let Some(mut frame) = frame {
                     trace!(?frame, "received image frame");
-                    frame.assign_to(&mut tmp_1_f, CV_32F)?;
-
-                    trace!("flip image");
-                    opencv::core::flip(&tmp_1_f, &mut frame_f, 1)?;
+                    if let Some(crop) = &crop {
+                        trace!("crop image");
+                        frame = crop.apply(&frame)?;
+                    }
+                    if !zoom.is_identity() {
+                        trace!("zoom image");
+                        frame = zoom.apply(&frame)?;
+                    }
+                    if !adjustment.is_identity() {
+                        trace!("adjust image");
+                        frame = adjustment.apply(&frame)?;
+                    }
+                    if let Some(chroma_key) = &mut chroma_key {
+                        trace!("chroma key image");
+                        frame = chroma_key.apply(&frame)?;
+                    }
+                    if flip {
+                        frame.assign_to(&mut tmp_1_f, CV_32F)?;
+                        trace!("flip image");
+                        opencv::core::flip(&tmp_1_f, &mut frame_f, 1)?;
+                    } else {
+                        frame.assign_to(&mut frame_f, CV_32F)?;
+                    }
                 }
             }
         }
 
-        if let Some(ref blending_image) = blending_image {
-            trace!("blend image");
-            opencv::core::multiply(&frame_f, blending_image.beta(), &mut tmp_1_f, 1., -1)?;
-            opencv::core::add(
-                &tmp_1_f,
-                blending_image.rgb(),
-                &mut tmp_2_f,
-                &Mat::default(),
-                -1,
-            )?;
-            tmp_2_f.assign_to(&mut frame_i, CV_8U)?;
-        } else {
-            frame_f.assign_to(&mut frame_i, CV_8U)?;
-        }
         if !frame_f.empty() {
+            let size = frame_f.size()?;
+            // resized once, when added or when the frame size changes
+            let unsized_layers = overlay_stack.iter_mut().chain(
+                layers
+                    .iter_mut()
+                    .filter(|(z, _)| **z != BLEND_LAYER)
+                    .map(|(_, layer)| layer),
+            );
+            for layer in unsized_layers.filter(|layer| layer.size() != size) {
+                *layer = layer.clone().resize(size)?;
+            }
+            if let Some(img) = layers.get(&BLEND_LAYER) {
+                if placed_blending_image
+                    .as_ref()
+                    .is_none_or(|placed| placed.rgb().size().ok() != Some(size))
+                {
+                    placed_blending_image = Some(img.clone().place(size, placement)?);
+                }
+            }
+
+            if transition.as_ref().is_some_and(BlendTransition::is_done) {
+                transition = None;
+            }
+            let transition_image = transition
+                .as_ref()
+                .map(|transition| transition.blend(placed_blending_image.as_ref(), size))
+                .transpose()?;
+
+            let blend_image = transition_image.as_ref().or(placed_blending_image.as_ref());
+
+            let mut blend_layers = overlay_stack
+                .iter()
+                .chain(layers.range(..BLEND_LAYER).map(|(_, layer)| layer))
+                .chain(blend_image)
+                .chain(layers.range(BLEND_LAYER + 1..).map(|(_, layer)| layer))
+                .peekable();
+            if blend_layers.peek().is_some() {
+                trace!("blend images");
+                frame_f.copy_to(&mut tmp_2_f)?;
+                for layer in blend_layers {
+                    if blend_mode == BlendMode::Alpha {
+                        opencv::core::multiply(&tmp_2_f, layer.beta(), &mut tmp_1_f, 1., -1)?;
+                        opencv::core::add(
+                            &tmp_1_f,
+                            layer.rgb(),
+                            &mut tmp_2_f,
+                            &Mat::default(),
+                            -1,
+                        )?;
+                    } else {
+                        tmp_2_f = layer.blend_over(&tmp_2_f, blend_mode)?;
+                    }
+                }
+                tmp_2_f.assign_to(&mut frame_i, CV_8U)?;
+            } else {
+                frame_f.assign_to(&mut frame_i, CV_8U)?;
+            }
+
+            if let Some(palette) = &mut palette {
+                palette.update(&frame_i)?;
+                palette.draw(&mut frame_i)?;
+            }
+            if let Some(pip) = &mut pip {
+                pip.draw(&mut frame_i)?;
+            }
+            if let Some((steps_total, steps_remaining)) = countdown_bar {
+                draw_countdown_bar(
+                    &mut frame_i,
+                    steps_total,
+                    steps_remaining,
+                    countdown_bar_color,
+                )?;
+            }
+
+            let fps = fps_counter.tick();
+            stats.lock().unwrap().fps = fps;
+
+            if let Some(out) = &mut stdout {
+                trace!("write image to stdout");
+                if let Err(err) = out.write_all(frame_i.data_bytes()?) {
+                    warn!(?err, "unable to write to stdout, stopping stdout output");
+                    stdout = None;
+                }
+            }
+
             trace!("display image");
-            highgui::imshow(window, &frame_i)?;
+            let fps_text = fps
+                .filter(|_| show_fps)
+                .map(|fps| format!("{:.1} FPS", fps));
+            if fps_text.is_none() && text.is_none() && !show_help {
+                highgui::imshow(window, &frame_i)?;
+            } else {
+                let mut display = frame_i.clone();
+                if let Some(fps_text) = fps_text {
+                    imgproc::put_text(
+                        &mut display,
+                        &fps_text,
+                        Point::new(10, 30),
+                        imgproc::FONT_HERSHEY_SIMPLEX,
+                        1.,
+                        Scalar::all(255.),
+                        2,
+                        imgproc::LINE_8,
+                        false,
+                    )?;
+                }
+                if let Some(text) = &text {
+                    let rows = display.rows();
+                    imgproc::put_text(
+                        &mut display,
+                        text,
+                        Point::new(20, rows - 30),
+                        imgproc::FONT_HERSHEY_SIMPLEX,
+                        2.,
+                        Scalar::all(255.),
+                        3,
+                        imgproc::LINE_8,
+                        false,
+                    )?;
+                }
+                if show_help {
+                    display = draw_help(&display, &key_help)?;
+                }
+                highgui::imshow(window, &display)?;
+            }
         }
     }
 
     warn!("exiting");
     Ok(())
 }
+
+/// List `lines` on a dark, semi-transparent box in the top-left corner of `image`.
+fn draw_help(image: &Mat, lines: &[String]) -> Result<Mat> {
+    const LINE_HEIGHT: i32 = 30;
+    const MARGIN: i32 = 10;
+    let font_scale = 0.7;
+
+    let mut width = 0;
+    for line in lines {
+        let mut baseline = 0;
+        let size = imgproc::get_text_size(
+            line,
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            font_scale,
+            1,
+            &mut baseline,
+        )?;
+        width = width.max(size.width);
+    }
+    let origin = Point::new(MARGIN, 2 * LINE_HEIGHT);
+    let rect = Rect::new(
+        origin.x,
+        origin.y,
+        width + 2 * MARGIN,
+        LINE_HEIGHT * lines.len() as i32 + MARGIN,
+    );
+
+    let mut shaded = image.clone();
+    imgproc::rectangle(
+        &mut shaded,
+        rect,
+        Scalar::all(0.),
+        imgproc::FILLED,
+        imgproc::LINE_8,
+        0,
+    )?;
+    let mut display = Mat::default();
+    opencv::core::add_weighted(&shaded, 0.7, image, 0.3, 0., &mut display, -1)?;
+    for (i, line) in lines.iter().enumerate() {
+        imgproc::put_text(
+            &mut display,
+            line,
+            Point::new(origin.x + MARGIN, origin.y + LINE_HEIGHT * (i as i32 + 1)),
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            font_scale,
+            Scalar::all(255.),
+            1,
+            imgproc::LINE_8,
+            false,
+        )?;
+    }
+    Ok(display)
+}
+
+/// Fill the bottom of `image` in proportion to the remaining countdown steps.
+fn draw_countdown_bar(
+    image: &mut Mat,
+    steps_total: usize,
+    steps_remaining: usize,
+    color: Scalar,
+) -> Result<()> {
+    const BAR_HEIGHT: i32 = 20;
+    let fraction = steps_remaining.min(steps_total) as f64 / steps_total.max(1) as f64;
+    let width = (f64::from(image.cols()) * fraction) as i32;
+    let y = image.rows() - BAR_HEIGHT;
+    imgproc::rectangle(
+        image,
+        Rect::new(0, y, width, BAR_HEIGHT),
+        color,
+        imgproc::FILLED,
+        imgproc::LINE_8,
+        0,
+    )?;
+    Ok(())
+}
+
+/// Fade from the previous blend image to the current one.
+struct BlendTransition {
+    from: Option<AlphaImage>,
+    started: Instant,
+    duration: Duration,
+}
+
+impl BlendTransition {
+    fn progress(&self) -> f32 {
+        (self.started.elapsed().as_secs_f32() / self.duration.as_secs_f32()).min(1.)
+    }
+
+    fn is_done(&self) -> bool {
+        self.progress() >= 1.
+    }
+
+    fn blend(&self, to: Option<&AlphaImage>, size: opencv::core::Size) -> Result<AlphaImage> {
+        AlphaImage::lerp(self.from.as_ref(), to, self.progress(), size)
+    }
+}
+
+/// Rolling average of the time between display updates.
+#[derive(Default)]
+struct FpsCounter {
+    timestamps: VecDeque<Instant>,
+}
+
+impl FpsCounter {
+    const FRAMES: usize = 30;
+
+    fn tick(&mut self) -> Option<f64> {
+        if self.timestamps.len() > Self::FRAMES {
+            self.timestamps.pop_front();
+        }
+        self.timestamps.push_back(Instant::now());
+        let elapsed = self
+            .timestamps
+            .back()?
+            .duration_since(*self.timestamps.front()?)
+            .as_secs_f64();
+        (elapsed > 0.).then(|| (self.timestamps.len() - 1) as f64 / elapsed)
+    }
+}
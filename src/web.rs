@@ -1,16 +1,90 @@
-use actix_web::{dev::Server, get, web::Data, App, HttpResponse, HttpServer, Responder};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use actix_cors::Cors;
+use actix_web::{
+    body::EitherBody,
+    delete,
+    dev::{forward_ready, Server, Service, ServiceRequest, ServiceResponse, Transform},
+    get,
+    http::header,
+    middleware::DefaultHeaders,
+    post, put, web,
+    web::Bytes,
+    web::Data,
+    App, HttpRequest, HttpResponse, HttpServer, Responder,
+};
 use anyhow::Result;
-use tokio::sync::broadcast;
-use tracing::warn;
+use chrono::{DateTime, FixedOffset};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use futures::{stream, StreamExt};
+use opencv::{
+    core::Vector,
+    imgcodecs,
+    prelude::{Mat, MatTraitConst},
+};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tracing::{info, warn};
+
+use crate::alpha_image::AlphaImage;
+use crate::app_event::AppEventSender;
+use crate::args::{TriggerParams, WebParams};
+use crate::auto_trigger::{SharedTriggerParams, TriggerState};
+use crate::error::CameraBotError;
+use crate::face_detector::FaceDetector;
+use crate::metrics::{SharedSnapshotMetrics, SnapshotMetrics};
+use crate::opencv_info::{OpenCvConfig, OpenCvMetrics};
+use crate::schedules::{self, Schedule, SharedSchedules};
+use crate::snapshot_cache::{SharedSnapshotCache, SnapshotCache};
+use crate::snapshot_db::{SharedSnapshotDb, SnapshotDb};
+use crate::snapshot_repo::{SharedSnapshotRepo, SnapshotRepo, TriggerSource};
+use crate::stats::{SharedStats, Stats};
+use crate::zoom::{SharedZoom, Zoom};
 
+type CaptureControlType = crate::capture_thread::Command;
+type CoordinatorControlType = crate::ControlMsg;
 type TriggerType = crate::auto_trigger::EventMsg;
+type TriggerControlType = crate::auto_trigger::ControlMsg;
+type UiControlType = crate::ui_thread::ControlMsg;
+
+/// Shared handles made available to the REST handlers.
+#[derive(Clone)]
+pub struct AppData {
+    pub trigger_event_sender: broadcast::Sender<TriggerType>,
+    pub trigger_control_sender: mpsc::Sender<TriggerControlType>,
+    pub trigger_state: watch::Receiver<TriggerState>,
+    pub trigger_params: SharedTriggerParams,
+    pub capture_event_sender: broadcast::Sender<Mat>,
+    pub capture_control_sender: mpsc::Sender<CaptureControlType>,
+    pub face_detector: Option<Arc<Mutex<FaceDetector>>>,
+    pub ui_control_sender: mpsc::Sender<UiControlType>,
+    pub overlays: Vec<AlphaImage>,
+    pub stats: SharedStats,
+    pub snapshot_cache: SharedSnapshotCache,
+    pub zoom: SharedZoom,
+    pub repo: SharedSnapshotRepo,
+    pub db: Option<SharedSnapshotDb>,
+    pub metrics: Option<SharedSnapshotMetrics>,
+    pub schedules: SharedSchedules,
+    pub coordinator_control_sender: mpsc::Sender<CoordinatorControlType>,
+    pub app_event_sender: AppEventSender,
+    /// Countdown steps the trigger timing must leave room for, see [`TriggerParams::validate`].
+    pub countdown_steps: usize,
+    /// With `--preview-only` the trigger timing can't be changed, which would enable it.
+    pub preview_only: bool,
+}
 
 pub fn spawn(
+    params: WebParams,
     mut exit_receiver: broadcast::Receiver<bool>,
-    trigger_event_sender: broadcast::Sender<TriggerType>,
-) -> tokio::task::JoinHandle<Result<()>> {
+    app_data: AppData,
+) -> tokio::task::JoinHandle<Result<(), CameraBotError>> {
     tokio::spawn(async move {
-        let server = web_server(trigger_event_sender);
+        let server = web_server(params, app_data)?;
         tokio::select! {
             err = server => {
                 warn!(?err, "Rest service exited");
@@ -23,20 +97,1276 @@ pub fn spawn(
     })
 }
 
-fn web_server(trigger_event_sender: broadcast::Sender<TriggerType>) -> Server {
+fn web_server(params: WebParams, app_data: AppData) -> Result<Server, CameraBotError> {
+    let auth = BasicAuth::new(params.web_user.as_deref(), params.web_password.as_deref());
+    if allows_any_origin(&params) {
+        warn!("CORS allows any origin, this is insecure");
+    }
+    let address = (params.web_host.clone(), params.web_port);
+    Ok(HttpServer::new(move || {
+        let AppData {
+            trigger_event_sender,
+            trigger_control_sender,
+            trigger_state,
+            trigger_params,
+            capture_event_sender,
+            capture_control_sender,
+            face_detector,
+            ui_control_sender,
+            overlays,
+            stats,
+            snapshot_cache,
+            zoom,
+            repo,
+            db,
+            metrics,
+            schedules,
+            coordinator_control_sender,
+            app_event_sender,
+            countdown_steps,
+            preview_only,
+        } = app_data.clone();
+        let mut app = App::new()
+            .wrap(auth.clone())
+            .wrap(DefaultHeaders::new().add(("Content-Security-Policy", params.csp_policy.clone())))
+            .wrap(cors(&params))
+            .app_data(Data::new(trigger_event_sender))
+            .app_data(Data::new(trigger_control_sender))
+            .app_data(Data::new(trigger_state))
+            .app_data(Data::from(trigger_params))
+            .app_data(Data::new(capture_event_sender))
+            .app_data(Data::new(capture_control_sender))
+            .app_data(Data::new(ui_control_sender))
+            .app_data(Data::new(overlays))
+            .app_data(Data::from(stats))
+            .app_data(Data::from(snapshot_cache))
+            .app_data(Data::from(zoom))
+            .app_data(Data::from(repo))
+            .app_data(Data::from(schedules))
+            .app_data(Data::new(coordinator_control_sender))
+            .app_data(Data::new(app_event_sender))
+            .app_data(Data::new(CountdownSteps(countdown_steps)))
+            .app_data(Data::new(PreviewOnly(preview_only)));
+        if let Some(face_detector) = face_detector {
+            app = app.app_data(Data::from(face_detector));
+        }
+        if let Some(db) = db {
+            app = app.app_data(Data::from(db));
+        }
+        if let Some(metrics) = metrics {
+            app = app.app_data(Data::from(metrics));
+        }
+        app.service(trigger)
+            .service(wait_for_trigger)
+            .service(abort_countdown)
+            .service(get_trigger_state)
+            .service(stop_trigger)
+            .service(events_sse)
+            .service(events_ws)
+            .service(dashboard)
+            .service(dashboard_js)
+            .service(dashboard_css)
+            .service(stats_handler)
+            .service(capture_stats)
+            .service(camera_info)
+            .service(opencv_metrics)
+            .service(prometheus_metrics)
+            .service(opencv_config)
+            .service(trigger_config)
+            .service(update_trigger_config)
+            .service(take_snapshot)
+            .service(latest_snapshot)
+            .service(list_snapshots)
+            .service(snapshot_history)
+            .service(snapshot)
+            .service(delete_snapshot)
+            .service(reset_repo)
+            .service(set_output_path)
+            .service(db_snapshots)
+            .service(list_schedules)
+            .service(add_schedule)
+            .service(delete_schedule)
+            .service(faces)
+            .service(push_overlay)
+            .service(pop_overlay)
+            .service(clear_overlays)
+            .service(add_layer)
+            .service(remove_layer)
+            .service(set_zoom)
+            .service(pause_display)
+            .service(resume_display)
+    })
+    .bind(address)
+    .map_err(CameraBotError::WebServer)?
+    .workers(1)
+    .run())
+}
+
+fn allows_any_origin(params: &WebParams) -> bool {
+    params.cors_origins.iter().any(|origin| origin == "*")
+}
+
+fn cors(params: &WebParams) -> Cors {
+    let cors = Cors::default()
+        .allow_any_method()
+        .allow_any_header()
+        .max_age(params.cors_max_age);
+    if allows_any_origin(params) {
+        cors.allow_any_origin()
+    } else {
+        params
+            .cors_origins
+            .iter()
+            .fold(cors, |cors, origin| cors.allowed_origin(origin))
+    }
+}
+
+/// Middleware requiring HTTP basic authentication, a no-op when no credentials are given.
+#[derive(Clone)]
+struct BasicAuth {
+    credentials: Option<Arc<Vec<u8>>>,
+}
+
+impl BasicAuth {
+    fn new(user: Option<&str>, password: Option<&str>) -> Self {
+        Self {
+            credentials: user
+                .zip(password)
+                .map(|(user, password)| Arc::new(format!("{}:{}", user, password).into_bytes())),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for BasicAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = BasicAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(BasicAuthMiddleware {
+            service,
+            credentials: self.credentials.clone(),
+        }))
+    }
+}
+
+struct BasicAuthMiddleware<S> {
+    service: S,
+    credentials: Option<Arc<Vec<u8>>>,
+}
+
+impl<S> BasicAuthMiddleware<S> {
+    fn is_authorized(&self, request: &ServiceRequest) -> bool {
+        let expected = match &self.credentials {
+            Some(expected) => expected,
+            None => return true,
+        };
+        request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Basic "))
+            .and_then(|encoded| base64::decode(encoded.trim()).ok())
+            .is_some_and(|credentials| bool::from(credentials.ct_eq(expected)))
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for BasicAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        if self.is_authorized(&request) {
+            let response = self.service.call(request);
+            Box::pin(async move { response.await.map(ServiceResponse::map_into_left_body) })
+        } else {
+            let response = HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, r#"Basic realm="camera-bot""#))
+                .finish()
+                .map_into_right_body();
+            Box::pin(ready(Ok(request.into_response(response))))
+        }
+    }
+}
+
+/// Serve every request with `200 OK`, printing it to stdout as JSON.
+pub async fn dry_run(params: WebParams) -> Result<()> {
+    let address = (params.web_host.clone(), params.web_port);
     HttpServer::new(move || {
-        let data: Data<broadcast::Sender<TriggerType>> = Data::new(trigger_event_sender.clone());
-        App::new().app_data(data).service(trigger)
+        App::new()
+            .wrap(DefaultHeaders::new().add(("Content-Security-Policy", params.csp_policy.clone())))
+            .default_service(web::to(print_request))
     })
-    .bind(("0.0.0.0", 8080))
-    .unwrap()
+    .bind(address)?
     .workers(1)
     .run()
+    .await?;
+    Ok(())
+}
+
+#[allow(clippy::unused_async)]
+async fn print_request(request: HttpRequest, body: Bytes) -> impl Responder {
+    let request = serde_json::json!({
+        "method": request.method().as_str(),
+        "path": request.path(),
+        "query": request.query_string(),
+        "body": String::from_utf8_lossy(&body),
+    });
+    println!("{}", request);
+    HttpResponse::Ok().finish()
 }
 
 #[get("/trigger")]
 #[allow(clippy::unused_async)]
 async fn trigger(sender: Data<broadcast::Sender<TriggerType>>) -> impl Responder {
-    sender.send(TriggerType::Trigger).unwrap();
-    HttpResponse::Ok().body("Camera triggered")
+    match sender.send(TriggerType::Trigger {
+        schedule_index: None,
+    }) {
+        Ok(_) => HttpResponse::Ok().body("Camera triggered"),
+        // no receivers left, e.g. while shutting down
+        Err(_) => HttpResponse::ServiceUnavailable()
+            .json(serde_json::json!({ "error": "no active receivers" })),
+    }
+}
+
+/// Longest a `/trigger/wait` request may wait, in seconds.
+const MAX_TRIGGER_WAIT: u64 = 300;
+
+#[derive(Deserialize)]
+struct TriggerWaitQuery {
+    /// Seconds to wait for a trigger.
+    #[serde(default = "default_trigger_wait")]
+    timeout: u64,
+}
+
+fn default_trigger_wait() -> u64 {
+    30
+}
+
+/// Long-poll for the next trigger, for clients unable to use the websocket or SSE events.
+#[get("/trigger/wait")]
+async fn wait_for_trigger(
+    query: web::Query<TriggerWaitQuery>,
+    sender: Data<broadcast::Sender<TriggerType>>,
+) -> impl Responder {
+    if query.timeout > MAX_TRIGGER_WAIT {
+        return HttpResponse::BadRequest().body(format!("timeout must be <= {}", MAX_TRIGGER_WAIT));
+    }
+    let mut receiver = sender.subscribe();
+    let triggered = async {
+        loop {
+            match receiver.recv().await {
+                Ok(TriggerType::Trigger { schedule_index }) => break Some(schedule_index),
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break None,
+            }
+        }
+    };
+    match tokio::time::timeout(Duration::from_secs(query.timeout), triggered).await {
+        Ok(Some(schedule_index)) => HttpResponse::Ok().json(serde_json::json!({
+            "triggered_at": chrono::Local::now().to_rfc3339(),
+            "source": TriggerSource::Trigger,
+            "schedule_index": schedule_index,
+        })),
+        Ok(None) => HttpResponse::ServiceUnavailable().body("Trigger not available"),
+        Err(_) => HttpResponse::RequestTimeout().body("Not triggered"),
+    }
+}
+
+/// The state of the auto-trigger, e.g. for a status indicator.
+#[get("/trigger/state")]
+#[allow(clippy::unused_async)]
+async fn get_trigger_state(receiver: Data<watch::Receiver<TriggerState>>) -> impl Responder {
+    let state = *receiver.borrow();
+    HttpResponse::Ok().json(serde_json::json!({
+        "state": state.name(),
+        "countdown_remaining": state.countdown_remaining(),
+    }))
+}
+
+#[delete("/trigger")]
+async fn abort_countdown(sender: Data<mpsc::Sender<TriggerControlType>>) -> impl Responder {
+    match sender.send(TriggerControlType::AbortCountdown).await {
+        Ok(_) => HttpResponse::Ok().body("Countdown aborted"),
+        Err(_) => HttpResponse::ServiceUnavailable().body("Trigger not available"),
+    }
+}
+
+/// Stop the auto-trigger immediately, also when no countdown is running. Unlike aborting the
+/// countdown with `DELETE /trigger`, the trigger stays stopped until it's resumed.
+#[post("/trigger/abort")]
+async fn stop_trigger(sender: Data<mpsc::Sender<TriggerControlType>>) -> impl Responder {
+    match sender.send(TriggerControlType::Stop).await {
+        Ok(_) => HttpResponse::Ok().body("Trigger stopped"),
+        Err(_) => HttpResponse::ServiceUnavailable().body("Trigger not available"),
+    }
+}
+
+#[get("/events/sse")]
+#[allow(clippy::unused_async)]
+async fn events_sse(sender: Data<broadcast::Sender<TriggerType>>) -> impl Responder {
+    let events = stream::unfold(sender.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(msg) => {
+                    let data = serde_json::to_string(&msg).unwrap_or_default();
+                    let event = Bytes::from(format!("data: {}\n\n", data));
+                    break Some((Ok::<_, actix_web::Error>(event), receiver));
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "sse client lagging behind");
+                }
+                Err(broadcast::error::RecvError::Closed) => break None,
+            }
+        }
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events)
+}
+
+#[get("/events")]
+#[allow(clippy::unused_async)]
+async fn events_ws(
+    request: HttpRequest,
+    body: web::Payload,
+    sender: Data<AppEventSender>,
+) -> actix_web::Result<HttpResponse> {
+    let (response, mut session, mut messages) = actix_ws::handle(&request, body)?;
+    let mut receiver = sender.subscribe();
+    actix_web::rt::spawn(async move {
+        loop {
+            tokio::select! {
+                event = receiver.recv() => match event {
+                    Ok(event) => {
+                        let text = serde_json::to_string(&event).unwrap_or_default();
+                        if session.text(text).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "websocket client lagging behind");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                msg = messages.next() => match msg {
+                    Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(actix_ws::Message::Close(_)) | Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+        session.close(None).await.ok();
+    });
+    Ok(response)
+}
+
+/// Single page showing the state of the booth, refreshing itself.
+///
+/// The script and styles are served separately, the default `--csp-policy` blocks inline ones.
+const DASHBOARD: &str = include_str!("../assets/dashboard.html");
+const DASHBOARD_JS: &str = include_str!("../assets/dashboard.js");
+const DASHBOARD_CSS: &str = include_str!("../assets/dashboard.css");
+
+#[get("/dashboard")]
+#[allow(clippy::unused_async)]
+async fn dashboard() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(DASHBOARD)
+}
+
+#[get("/dashboard.js")]
+#[allow(clippy::unused_async)]
+async fn dashboard_js() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/javascript; charset=utf-8")
+        .body(DASHBOARD_JS)
+}
+
+#[get("/dashboard.css")]
+#[allow(clippy::unused_async)]
+async fn dashboard_css() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/css; charset=utf-8")
+        .body(DASHBOARD_CSS)
+}
+
+#[get("/stats")]
+#[allow(clippy::unused_async)]
+async fn stats_handler(stats: Data<Mutex<Stats>>) -> impl Responder {
+    let stats = stats.lock().unwrap().clone();
+    HttpResponse::Ok().json(stats)
+}
+
+#[get("/capture/stats")]
+async fn capture_stats(sender: Data<mpsc::Sender<CaptureControlType>>) -> impl Responder {
+    let (stats_sender, stats_receiver) = oneshot::channel();
+    if sender
+        .send(CaptureControlType::GetStats(stats_sender))
+        .await
+        .is_err()
+    {
+        return HttpResponse::ServiceUnavailable().body("Capture not available");
+    }
+    match tokio::time::timeout(Duration::from_secs(1), stats_receiver).await {
+        Ok(Ok(stats)) => HttpResponse::Ok().json(stats),
+        _ => HttpResponse::GatewayTimeout().body("Capture did not respond"),
+    }
+}
+
+#[get("/camera/info")]
+async fn camera_info(sender: Data<mpsc::Sender<CaptureControlType>>) -> impl Responder {
+    let (info_sender, info_receiver) = oneshot::channel();
+    if sender
+        .send(CaptureControlType::GetInfo(info_sender))
+        .await
+        .is_err()
+    {
+        return HttpResponse::ServiceUnavailable().body("Capture not available");
+    }
+    match tokio::time::timeout(Duration::from_secs(1), info_receiver).await {
+        Ok(Ok(info)) => HttpResponse::Ok().json(info),
+        _ => HttpResponse::GatewayTimeout().body("Camera info not available"),
+    }
+}
+
+/// The snapshot latencies for Prometheus to scrape, with `--metrics`.
+#[get("/metrics")]
+#[allow(clippy::unused_async)]
+async fn prometheus_metrics(metrics: Option<Data<Mutex<SnapshotMetrics>>>) -> impl Responder {
+    match metrics {
+        Some(metrics) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(metrics.lock().unwrap().to_prometheus()),
+        None => HttpResponse::NotFound().body("Metrics not enabled, see --metrics"),
+    }
+}
+
+#[get("/metrics/opencv")]
+#[allow(clippy::unused_async)]
+async fn opencv_metrics() -> impl Responder {
+    match OpenCvMetrics::read() {
+        Ok(metrics) => HttpResponse::Ok().json(metrics),
+        Err(err) => {
+            warn!(?err, "failed reading opencv metrics");
+            HttpResponse::InternalServerError().body("Failed reading OpenCV metrics")
+        }
+    }
+}
+
+#[get("/config/opencv")]
+#[allow(clippy::unused_async)]
+async fn opencv_config() -> impl Responder {
+    match OpenCvConfig::read() {
+        Ok(config) => HttpResponse::Ok().json(config),
+        Err(err) => {
+            warn!(?err, "failed reading opencv config");
+            HttpResponse::InternalServerError().body("Failed reading OpenCV config")
+        }
+    }
+}
+
+/// Trigger parameters as exposed by `/config/trigger`, durations in milliseconds.
+#[derive(Serialize)]
+struct TriggerConfig {
+    timeout_ms: Option<u128>,
+    timeout_between_ms: u128,
+    trigger_jitter_ms: u128,
+    trigger_jitter_seed: Option<u64>,
+    trigger_schedule_ms: Vec<u128>,
+    max_triggers: Option<usize>,
+}
+
+impl From<&TriggerParams> for TriggerConfig {
+    fn from(params: &TriggerParams) -> Self {
+        Self {
+            timeout_ms: params.timeout.map(|timeout| timeout.as_millis()),
+            timeout_between_ms: params.timeout_between.as_millis(),
+            trigger_jitter_ms: params.trigger_jitter.as_millis(),
+            trigger_jitter_seed: params.trigger_jitter_seed,
+            trigger_schedule_ms: params
+                .trigger_schedule
+                .iter()
+                .map(Duration::as_millis)
+                .collect(),
+            max_triggers: params.max_triggers,
+        }
+    }
+}
+
+/// Trigger parameters changeable through `PUT /config/trigger`, omitted ones are kept.
+#[derive(Deserialize)]
+struct TriggerConfigUpdate {
+    timeout_ms: Option<u64>,
+    timeout_between_ms: Option<u64>,
+}
+
+#[get("/config/trigger")]
+#[allow(clippy::unused_async)]
+async fn trigger_config(params: Data<RwLock<TriggerParams>>) -> impl Responder {
+    HttpResponse::Ok().json(TriggerConfig::from(&*params.read().unwrap()))
+}
+
+/// Number of countdown steps, to validate trigger timing changes against.
+struct CountdownSteps(usize);
+
+/// Whether `--preview-only` disabled the trigger.
+struct PreviewOnly(bool);
+
+#[put("/config/trigger")]
+#[allow(clippy::unused_async)]
+async fn update_trigger_config(
+    body: web::Json<TriggerConfigUpdate>,
+    params: Data<RwLock<TriggerParams>>,
+    countdown_steps: Data<CountdownSteps>,
+    preview_only: Option<Data<PreviewOnly>>,
+) -> impl Responder {
+    if preview_only.is_some_and(|preview_only| preview_only.0) {
+        return HttpResponse::Conflict().body("Trigger disabled by --preview-only");
+    }
+    let update = body.into_inner();
+    if update.timeout_ms == Some(0) {
+        return HttpResponse::BadRequest().body("timeout_ms must be > 0");
+    }
+    if update.timeout_between_ms == Some(0) {
+        return HttpResponse::BadRequest().body("timeout_between_ms must be > 0");
+    }
+
+    let mut params = params.write().unwrap();
+    let mut updated = params.clone();
+    if let Some(timeout) = update.timeout_ms {
+        updated.timeout = Some(Duration::from_millis(timeout));
+    }
+    if let Some(timeout_between) = update.timeout_between_ms {
+        updated.timeout_between = Duration::from_millis(timeout_between);
+    }
+    if let Err(err) = updated.validate(countdown_steps.0) {
+        return HttpResponse::BadRequest().body(err.to_string());
+    }
+    *params = updated;
+    info!(?params, "trigger parameters updated");
+    HttpResponse::Ok().json(TriggerConfig::from(&*params))
+}
+
+#[derive(Deserialize)]
+struct SnapshotRequest {
+    overlay_url: Option<String>,
+    overlay_base64: Option<String>,
+}
+
+#[post("/snapshot")]
+async fn take_snapshot(
+    body: web::Json<SnapshotRequest>,
+    sender: Data<mpsc::Sender<CoordinatorControlType>>,
+) -> impl Responder {
+    let png = match body.into_inner() {
+        SnapshotRequest {
+            overlay_url: Some(url),
+            ..
+        } => match fetch_overlay(&url).await {
+            Ok(png) => png,
+            Err(err) => {
+                warn!(?err, url, "failed fetching overlay");
+                return HttpResponse::BadGateway().body("Failed fetching overlay");
+            }
+        },
+        SnapshotRequest {
+            overlay_base64: Some(encoded),
+            ..
+        } => match base64::decode(encoded) {
+            Ok(png) => png,
+            Err(_) => return HttpResponse::BadRequest().body("Invalid base64 overlay"),
+        },
+        _ => return HttpResponse::BadRequest().body("Missing overlay_url or overlay_base64"),
+    };
+    let overlay = match AlphaImage::from_png_bytes(&png) {
+        Ok(overlay) => overlay,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid overlay image"),
+    };
+
+    let (done_sender, done_receiver) = oneshot::channel();
+    if sender
+        .send(CoordinatorControlType::SnapshotWithOverlay(
+            overlay,
+            done_sender,
+        ))
+        .await
+        .is_err()
+    {
+        return HttpResponse::ServiceUnavailable().body("Camera not available");
+    }
+    match done_receiver.await {
+        Ok(()) => HttpResponse::Ok().body("Snapshot taken"),
+        Err(_) => HttpResponse::ServiceUnavailable().body("Camera not available"),
+    }
+}
+
+async fn fetch_overlay(url: &str) -> Result<Vec<u8>> {
+    let response = reqwest::get(url).await?.error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Image format a snapshot may be converted to when served.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ImageFormat {
+    #[serde(alias = "jpeg")]
+    Jpg,
+    Png,
+    Webp,
+}
+
+impl ImageFormat {
+    const fn extension(self) -> &'static str {
+        match self {
+            Self::Jpg => ".jpg",
+            Self::Png => ".png",
+            Self::Webp => ".webp",
+        }
+    }
+
+    const fn content_type(self) -> &'static str {
+        match self {
+            Self::Jpg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Webp => "image/webp",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct FormatQuery {
+    format: Option<ImageFormat>,
+}
+
+#[get("/snapshot/latest")]
+async fn latest_snapshot(
+    query: web::Query<FormatQuery>,
+    cache: Data<Mutex<SnapshotCache>>,
+) -> impl Responder {
+    let latest = cache.lock().unwrap().latest().map(Path::to_path_buf);
+    match latest {
+        Some(path) => match query
+            .format
+            .filter(|format| format.content_type() != image_content_type(&path))
+        {
+            Some(format) => serve_converted(&cache, &path, format).await,
+            None => serve_snapshot(&cache, &path).await,
+        },
+        None => HttpResponse::NotFound().body("No snapshot taken yet"),
+    }
+}
+
+#[get("/snapshots")]
+#[allow(clippy::unused_async)]
+async fn list_snapshots(repo: Data<Mutex<SnapshotRepo>>) -> impl Responder {
+    let snapshots = repo.lock().unwrap().list();
+    match snapshots {
+        Ok(snapshots) => HttpResponse::Ok().json(snapshots),
+        Err(err) => {
+            warn!(?err, "failed listing snapshots");
+            HttpResponse::InternalServerError().body("Failed listing snapshots")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    limit: Option<usize>,
+}
+
+#[get("/snapshots/history")]
+#[allow(clippy::unused_async)]
+async fn snapshot_history(
+    query: web::Query<HistoryQuery>,
+    repo: Data<Mutex<SnapshotRepo>>,
+) -> impl Responder {
+    let history = repo.lock().unwrap().history(query.limit.unwrap_or(10));
+    HttpResponse::Ok().json(history)
+}
+
+#[get("/snapshots/{filename}")]
+async fn snapshot(
+    filename: web::Path<String>,
+    repo: Data<Mutex<SnapshotRepo>>,
+    cache: Data<Mutex<SnapshotCache>>,
+) -> impl Responder {
+    let filename = filename.into_inner();
+    if !is_valid_filename(&filename) {
+        return HttpResponse::BadRequest().body("Invalid filename");
+    }
+    // the output folder may be changed by `POST /repo/output-path`
+    let path = repo.lock().unwrap().path().join(filename);
+    serve_snapshot(&cache, &path).await
+}
+
+#[derive(Serialize)]
+struct RepoState {
+    path: PathBuf,
+    counter: usize,
+}
+
+impl RepoState {
+    fn of(repo: &SnapshotRepo) -> Self {
+        Self {
+            path: repo.path().to_path_buf(),
+            counter: repo.counter(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct ResetRequest {
+    /// New name of the current output folder, which is then recreated empty.
+    rename_to: Option<PathBuf>,
+}
+
+#[post("/repo/reset")]
+#[allow(clippy::unused_async)]
+async fn reset_repo(
+    body: Option<web::Json<ResetRequest>>,
+    repo: Data<Mutex<SnapshotRepo>>,
+) -> impl Responder {
+    let request = body.map(web::Json::into_inner).unwrap_or_default();
+    let mut repo = repo.lock().unwrap();
+    match repo.reset(request.rename_to.as_deref()) {
+        Ok(()) => HttpResponse::Ok().json(RepoState::of(&repo)),
+        Err(err) => {
+            warn!(?err, "failed resetting repository");
+            HttpResponse::InternalServerError().body("Failed resetting repository")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OutputPathRequest {
+    path: PathBuf,
+}
+
+#[post("/repo/output-path")]
+#[allow(clippy::unused_async)]
+async fn set_output_path(
+    body: web::Json<OutputPathRequest>,
+    repo: Data<Mutex<SnapshotRepo>>,
+) -> impl Responder {
+    let mut repo = repo.lock().unwrap();
+    match repo.set_path(body.into_inner().path) {
+        Ok(()) => HttpResponse::Ok().json(RepoState::of(&repo)),
+        Err(err) => {
+            warn!(?err, "failed changing output folder");
+            HttpResponse::InternalServerError().body("Failed changing output folder")
+        }
+    }
+}
+
+#[delete("/snapshots/{filename}")]
+#[allow(clippy::unused_async)]
+async fn delete_snapshot(
+    filename: web::Path<String>,
+    repo: Data<Mutex<SnapshotRepo>>,
+    cache: Data<Mutex<SnapshotCache>>,
+) -> impl Responder {
+    let filename = filename.into_inner();
+    if !is_valid_filename(&filename) {
+        return HttpResponse::BadRequest().body("Invalid filename");
+    }
+    let deleted = repo.lock().unwrap().delete(&filename);
+    match deleted {
+        Ok(path) => {
+            cache.lock().unwrap().remove(&path);
+            HttpResponse::Ok().body(format!("Snapshot {} deleted", filename))
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            HttpResponse::NotFound().body("Snapshot not found")
+        }
+        Err(err) => {
+            warn!(?err, "failed deleting snapshot");
+            HttpResponse::InternalServerError().body("Failed deleting snapshot")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct DbQuery {
+    from: Option<DateTime<FixedOffset>>,
+    to: Option<DateTime<FixedOffset>>,
+}
+
+#[get("/db/snapshots")]
+#[allow(clippy::unused_async)]
+async fn db_snapshots(
+    query: web::Query<DbQuery>,
+    db: Option<Data<Mutex<SnapshotDb>>>,
+) -> impl Responder {
+    let db = match db {
+        Some(db) => db,
+        None => return HttpResponse::ServiceUnavailable().body("No database configured"),
+    };
+    let snapshots = db.lock().unwrap().query(query.from, query.to);
+    match snapshots {
+        Ok(snapshots) => HttpResponse::Ok().json(snapshots),
+        Err(err) => {
+            warn!(?err, "failed querying database");
+            HttpResponse::InternalServerError().body("Failed querying database")
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ScheduleEntry<'a> {
+    id: &'a str,
+    #[serde(flatten)]
+    schedule: &'a Schedule,
+}
+
+#[get("/schedules")]
+#[allow(clippy::unused_async)]
+async fn list_schedules(schedules: Data<RwLock<HashMap<String, Schedule>>>) -> impl Responder {
+    let schedules = schedules.read().unwrap();
+    let entries: Vec<_> = schedules
+        .iter()
+        .map(|(id, schedule)| ScheduleEntry { id, schedule })
+        .collect();
+    HttpResponse::Ok().json(entries)
+}
+
+/// Add a schedule, stored in the database if configured.
+#[post("/schedules")]
+#[allow(clippy::unused_async)]
+async fn add_schedule(
+    body: web::Json<Schedule>,
+    schedules: Data<RwLock<HashMap<String, Schedule>>>,
+    db: Option<Data<Mutex<SnapshotDb>>>,
+) -> impl Responder {
+    let schedule = body.into_inner();
+    if let Err(err) = schedule.validate() {
+        return HttpResponse::BadRequest().body(err.to_string());
+    }
+    let id = schedules::new_id();
+    if let Some(db) = db {
+        if let Err(err) = db.lock().unwrap().insert_schedule(&id, &schedule) {
+            warn!(?err, "failed storing schedule");
+            return HttpResponse::InternalServerError().body("Failed storing schedule");
+        }
+    }
+    info!(id, ?schedule, "schedule added");
+    let response = HttpResponse::Created().json(ScheduleEntry {
+        id: &id,
+        schedule: &schedule,
+    });
+    schedules.write().unwrap().insert(id, schedule);
+    response
+}
+
+#[delete("/schedules/{id}")]
+#[allow(clippy::unused_async)]
+async fn delete_schedule(
+    id: web::Path<String>,
+    schedules: Data<RwLock<HashMap<String, Schedule>>>,
+    db: Option<Data<Mutex<SnapshotDb>>>,
+) -> impl Responder {
+    let id = id.into_inner();
+    if schedules.write().unwrap().remove(&id).is_none() {
+        return HttpResponse::NotFound().body("Schedule not found");
+    }
+    if let Some(db) = db {
+        if let Err(err) = db.lock().unwrap().delete_schedule(&id) {
+            warn!(?err, "failed deleting schedule from database");
+        }
+    }
+    info!(id, "schedule deleted");
+    HttpResponse::Ok().body(format!("Schedule {} deleted", id))
+}
+
+/// Only plain filenames are accepted, never paths leading out of the output folder.
+fn is_valid_filename(filename: &str) -> bool {
+    !(filename.contains(['/', '\\']) || filename.starts_with('.'))
+}
+
+async fn serve_snapshot(cache: &Mutex<SnapshotCache>, path: &Path) -> HttpResponse {
+    let cached = cache.lock().unwrap().get(path);
+    let content = match cached {
+        Some(content) => content,
+        None => match tokio::fs::read(path).await {
+            Ok(content) => Bytes::from(content),
+            Err(_) => return HttpResponse::NotFound().body("Snapshot not found"),
+        },
+    };
+    HttpResponse::Ok()
+        .content_type(image_content_type(path))
+        .body(content)
+}
+
+async fn serve_converted(
+    cache: &Mutex<SnapshotCache>,
+    path: &Path,
+    format: ImageFormat,
+) -> HttpResponse {
+    let cached = cache
+        .lock()
+        .unwrap()
+        .get_converted(path, format.extension());
+    let content = match cached {
+        Some(content) => content,
+        None => {
+            let source = path.to_path_buf();
+            let converted =
+                tokio::task::spawn_blocking(move || convert_image(&source, format.extension()))
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|converted| converted);
+            match converted {
+                Ok(content) => {
+                    let content = Bytes::from(content);
+                    cache.lock().unwrap().insert_converted(
+                        path.to_path_buf(),
+                        format.extension(),
+                        content.clone(),
+                    );
+                    content
+                }
+                Err(err) => {
+                    warn!(?err, ?path, ?format, "failed converting snapshot");
+                    return HttpResponse::InternalServerError().body("Failed converting snapshot");
+                }
+            }
+        }
+    };
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .body(content)
+}
+
+/// Decode the image at `path` and encode it in the format of `extension`.
+fn convert_image(path: &Path, extension: &str) -> Result<Vec<u8>> {
+    let image = imgcodecs::imread(&path.display().to_string(), imgcodecs::IMREAD_UNCHANGED)?;
+    if image.empty() {
+        anyhow::bail!("unable to read {}", path.display());
+    }
+    let mut encoded = Vector::<u8>::new();
+    imgcodecs::imencode(extension, &image, &mut encoded, &Vector::new())?;
+    Ok(encoded.to_vec())
+}
+
+fn image_content_type(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase)
+        .as_deref()
+    {
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        Some("bmp") => "image/bmp",
+        Some("tif" | "tiff") => "image/tiff",
+        _ => "image/jpeg",
+    }
+}
+
+#[derive(Serialize)]
+struct FaceBox {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+#[derive(Serialize)]
+struct Faces {
+    count: usize,
+    faces: Vec<FaceBox>,
+}
+
+#[get("/faces")]
+async fn faces(
+    frames: Data<broadcast::Sender<Mat>>,
+    face_detector: Option<Data<Mutex<FaceDetector>>>,
+) -> impl Responder {
+    let face_detector = match face_detector {
+        Some(face_detector) => face_detector,
+        None => return HttpResponse::ServiceUnavailable().body("Face detection not available"),
+    };
+    let mut frames = frames.subscribe();
+    let frame = match tokio::time::timeout(Duration::from_secs(1), frames.recv()).await {
+        Ok(Ok(frame)) => frame,
+        // the capture stalled, or the camera is gone
+        Ok(Err(_)) | Err(_) => {
+            return HttpResponse::ServiceUnavailable().body("No frame available")
+        }
+    };
+    match web::block(move || face_detector.lock().unwrap().faces(&frame)).await {
+        Ok(Ok(rects)) => HttpResponse::Ok().json(Faces {
+            count: rects.len(),
+            faces: rects
+                .iter()
+                .map(|r| FaceBox {
+                    x: r.x,
+                    y: r.y,
+                    width: r.width,
+                    height: r.height,
+                })
+                .collect(),
+        }),
+        Ok(Err(err)) => {
+            warn!(?err, "face detection failed");
+            HttpResponse::InternalServerError().body("Face detection failed")
+        }
+        Err(err) => {
+            warn!(?err, "face detection failed");
+            HttpResponse::InternalServerError().body("Face detection failed")
+        }
+    }
+}
+
+#[post("/overlay/stack/{index}")]
+async fn push_overlay(
+    index: web::Path<usize>,
+    overlays: Data<Vec<AlphaImage>>,
+    sender: Data<mpsc::Sender<UiControlType>>,
+) -> impl Responder {
+    let index = index.into_inner();
+    match overlays.get(index) {
+        Some(overlay) => match sender
+            .send(UiControlType::PushOverlay(overlay.clone()))
+            .await
+        {
+            Ok(_) => HttpResponse::Ok().body(format!("Overlay {} pushed", index)),
+            Err(_) => HttpResponse::ServiceUnavailable().body("UI not available"),
+        },
+        None => HttpResponse::NotFound().body(format!("No overlay with index {}", index)),
+    }
+}
+
+#[delete("/overlay/stack")]
+async fn pop_overlay(sender: Data<mpsc::Sender<UiControlType>>) -> impl Responder {
+    match sender.send(UiControlType::PopOverlay).await {
+        Ok(_) => HttpResponse::Ok().body("Overlay popped"),
+        Err(_) => HttpResponse::ServiceUnavailable().body("UI not available"),
+    }
+}
+
+#[delete("/overlay/stack/all")]
+async fn clear_overlays(sender: Data<mpsc::Sender<UiControlType>>) -> impl Responder {
+    match sender.send(UiControlType::ClearOverlays).await {
+        Ok(_) => HttpResponse::Ok().body("Overlays cleared"),
+        Err(_) => HttpResponse::ServiceUnavailable().body("UI not available"),
+    }
+}
+
+#[post("/display/pause")]
+async fn pause_display(sender: Data<mpsc::Sender<UiControlType>>) -> impl Responder {
+    match sender.send(UiControlType::Pause).await {
+        Ok(_) => HttpResponse::Ok().body("Display paused"),
+        Err(_) => HttpResponse::ServiceUnavailable().body("UI not available"),
+    }
+}
+
+#[post("/display/resume")]
+async fn resume_display(sender: Data<mpsc::Sender<UiControlType>>) -> impl Responder {
+    match sender.send(UiControlType::Live).await {
+        Ok(_) => HttpResponse::Ok().body("Display resumed"),
+        Err(_) => HttpResponse::ServiceUnavailable().body("UI not available"),
+    }
+}
+
+#[post("/overlay/layers/{z}")]
+async fn add_layer(
+    z: web::Path<i32>,
+    body: Bytes,
+    sender: Data<mpsc::Sender<UiControlType>>,
+) -> impl Responder {
+    let z = z.into_inner();
+    let layer = match AlphaImage::from_png_bytes(&body) {
+        Ok(layer) => layer,
+        Err(err) => return HttpResponse::BadRequest().body(format!("Invalid image: {}", err)),
+    };
+    match sender.send(UiControlType::AddLayer(z, layer)).await {
+        Ok(_) => HttpResponse::Ok().body(format!("Layer {} added", z)),
+        Err(_) => HttpResponse::ServiceUnavailable().body("UI not available"),
+    }
+}
+
+#[delete("/overlay/layers/{z}")]
+async fn remove_layer(
+    z: web::Path<i32>,
+    sender: Data<mpsc::Sender<UiControlType>>,
+) -> impl Responder {
+    let z = z.into_inner();
+    match sender.send(UiControlType::RemoveLayer(z)).await {
+        Ok(_) => HttpResponse::Ok().body(format!("Layer {} removed", z)),
+        Err(_) => HttpResponse::ServiceUnavailable().body("UI not available"),
+    }
+}
+
+#[post("/zoom")]
+async fn set_zoom(
+    body: web::Json<Zoom>,
+    zoom: Data<Mutex<Zoom>>,
+    sender: Data<mpsc::Sender<UiControlType>>,
+) -> impl Responder {
+    let new_zoom = body.into_inner();
+    if let Err(err) = new_zoom.validate() {
+        return HttpResponse::BadRequest().body(err.to_string());
+    }
+    *zoom.lock().unwrap() = new_zoom;
+    let Zoom { factor, cx, cy } = new_zoom;
+    match sender.send(UiControlType::SetZoom { factor, cx, cy }).await {
+        Ok(_) => HttpResponse::Ok().json(new_zoom),
+        Err(_) => HttpResponse::ServiceUnavailable().body("UI not available"),
+    }
+}
+
+#[actix_web::test]
+async fn trigger_state_reports_countdown() {
+    use actix_web::test;
+
+    let (_sender, receiver) = watch::channel(TriggerState::Countdown { remaining: 2 });
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(receiver))
+            .service(get_trigger_state),
+    )
+    .await;
+    let request = test::TestRequest::get().uri("/trigger/state").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(
+        body,
+        serde_json::json!({ "state": "Countdown", "countdown_remaining": 2 })
+    );
+}
+
+#[actix_web::test]
+async fn trigger_without_receivers_is_unavailable() {
+    use actix_web::{http::StatusCode, test};
+
+    let (sender, _) = broadcast::channel::<TriggerType>(1);
+    let app = test::init_service(App::new().app_data(Data::new(sender)).service(trigger)).await;
+    let response =
+        test::call_service(&app, test::TestRequest::get().uri("/trigger").to_request()).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[actix_web::test]
+async fn trigger_wait_times_out() {
+    use actix_web::{http::StatusCode, test};
+
+    let (sender, _) = broadcast::channel::<TriggerType>(1);
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(sender))
+            .service(wait_for_trigger),
+    )
+    .await;
+    let request = test::TestRequest::get()
+        .uri("/trigger/wait?timeout=0")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+}
+
+#[actix_web::test]
+async fn dashboard_has_no_inline_script() {
+    use actix_web::test;
+
+    let app = test::init_service(
+        App::new()
+            .service(dashboard)
+            .service(dashboard_js)
+            .service(dashboard_css),
+    )
+    .await;
+    let request = test::TestRequest::get().uri("/dashboard").to_request();
+    let page = test::call_and_read_body(&app, request).await;
+    let page = std::str::from_utf8(&page).unwrap();
+    assert!(page.contains("<script src=\"dashboard.js\"></script>"));
+    assert!(!page.contains("<style>"));
+    for uri in ["/dashboard.js", "/dashboard.css"] {
+        let request = test::TestRequest::get().uri(uri).to_request();
+        assert!(test::call_service(&app, request)
+            .await
+            .status()
+            .is_success());
+    }
+}
+
+#[actix_web::test]
+async fn trigger_config_update_is_validated() {
+    use actix_web::{http::StatusCode, test};
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[clap(flatten)]
+        params: TriggerParams,
+    }
+
+    let params = Cli::parse_from(["app", "--timeout", "10s"]).params;
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(RwLock::new(params)))
+            .app_data(Data::new(CountdownSteps(3)))
+            .service(update_trigger_config),
+    )
+    .await;
+    for (update, status) in [
+        (
+            serde_json::json!({ "timeout_between_ms": 10_000 }),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            serde_json::json!({ "timeout_ms": 3_000 }),
+            StatusCode::BAD_REQUEST,
+        ),
+        (serde_json::json!({ "timeout_ms": 5_000 }), StatusCode::OK),
+    ] {
+        let request = test::TestRequest::put()
+            .uri("/config/trigger")
+            .set_json(update)
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), status);
+    }
+}
+
+#[actix_web::test]
+async fn schedule_without_countdown_is_rejected() {
+    use actix_web::{http::StatusCode, test};
+
+    let schedules = SharedSchedules::default();
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::from(schedules.clone()))
+            .service(add_schedule),
+    )
+    .await;
+    for (countdown_steps, status) in [(0, StatusCode::BAD_REQUEST), (3, StatusCode::CREATED)] {
+        let request = test::TestRequest::post()
+            .uri("/schedules")
+            .set_json(
+                serde_json::json!({ "cron": "0 */5 * * * *", "countdown_steps": countdown_steps }),
+            )
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), status);
+    }
+    assert_eq!(schedules.read().unwrap().len(), 1);
 }
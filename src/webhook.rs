@@ -0,0 +1,79 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use actix_web::web::Bytes;
+use anyhow::Result;
+use serde::Serialize;
+use tracing::{debug, instrument, warn};
+
+#[derive(Debug, Serialize)]
+struct Payload {
+    timestamp: String,
+    filename: String,
+    counter: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+}
+
+/// Notifies an external HTTP endpoint about saved snapshots.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    client: reqwest::Client,
+    url: String,
+    include_image: bool,
+}
+
+impl Webhook {
+    pub fn new(url: String, timeout: Duration, include_image: bool) -> Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(timeout).build()?,
+            url,
+            include_image,
+        })
+    }
+
+    /// POST the snapshot details in a background task, retrying once on failure.
+    ///
+    /// `encoded` is the content of the snapshot file, which is read from disk when not given.
+    pub fn notify(&self, filename: &Path, counter: usize, encoded: Option<Bytes>) {
+        tokio::spawn(self.clone().post(filename.to_path_buf(), counter, encoded));
+    }
+
+    #[instrument(skip(self, encoded))]
+    async fn post(self, filename: PathBuf, counter: usize, encoded: Option<Bytes>) {
+        let image = match encoded {
+            _ if !self.include_image => None,
+            Some(bytes) => Some(base64::encode(bytes)),
+            None => match tokio::fs::read(&filename).await {
+                Ok(bytes) => Some(base64::encode(bytes)),
+                Err(err) => {
+                    warn!(?err, "failed reading snapshot for webhook");
+                    None
+                }
+            },
+        };
+        let payload = Payload {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            filename: filename.display().to_string(),
+            counter,
+            image,
+        };
+
+        for attempt in 1..=2 {
+            match self
+                .client
+                .post(&self.url)
+                .json(&payload)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+            {
+                Ok(_) => {
+                    debug!("webhook delivered");
+                    return;
+                }
+                Err(err) => warn!(?err, attempt, "webhook failed"),
+            }
+        }
+    }
+}
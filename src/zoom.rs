@@ -0,0 +1,68 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use opencv::{
+    core::{Rect, Size},
+    imgproc,
+    prelude::{Mat, MatTraitConstManual},
+};
+use serde::{Deserialize, Serialize};
+
+/// Digital zoom into a region of the camera frame.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Zoom {
+    /// Zoom factor, 1.0 shows the entire frame.
+    pub factor: f32,
+    /// Horizontal centre of the zoomed region, normalized to 0-1.
+    pub cx: f32,
+    /// Vertical centre of the zoomed region, normalized to 0-1.
+    pub cy: f32,
+}
+
+pub type SharedZoom = Arc<Mutex<Zoom>>;
+
+impl Zoom {
+    pub fn validate(&self) -> Result<()> {
+        if self.factor.is_nan() || self.factor < 1.0 {
+            anyhow::bail!("zoom factor must be >= 1.0");
+        }
+        if !(0.0..=1.0).contains(&self.cx) || !(0.0..=1.0).contains(&self.cy) {
+            anyhow::bail!("zoom centre must be within 0-1");
+        }
+        Ok(())
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.factor <= 1.0
+    }
+
+    /// Crop the zoomed region from `frame` and scale it back to the size of `frame`.
+    pub fn apply(&self, frame: &Mat) -> Result<Mat> {
+        let size = frame.size()?;
+        let crop = self.crop_rect(size);
+        let mut zoomed = Mat::default();
+        imgproc::resize(
+            &Mat::roi(frame, crop)?,
+            &mut zoomed,
+            size,
+            0.,
+            0.,
+            imgproc::INTER_LINEAR,
+        )?;
+        Ok(zoomed)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn crop_rect(&self, size: Size) -> Rect {
+        let width = ((size.width as f32 / self.factor).round() as i32).clamp(1, size.width);
+        let height = ((size.height as f32 / self.factor).round() as i32).clamp(1, size.height);
+        let x = (self.cx * size.width as f32 - width as f32 / 2.).round() as i32;
+        let y = (self.cy * size.height as f32 - height as f32 / 2.).round() as i32;
+        Rect::new(
+            x.clamp(0, size.width - width),
+            y.clamp(0, size.height - height),
+            width,
+            height,
+        )
+    }
+}
@@ -0,0 +1,103 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use camera_bot::Client;
+
+/// Method and path of a request received by the [`MockServer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Received {
+    method: String,
+    path: String,
+}
+
+/// Stand-in for the REST API of camera-bot, recording the requests it receives.
+struct MockServer {
+    addr: SocketAddr,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl MockServer {
+    /// Serve canned responses to the endpoints used by the client, on a free local port.
+    fn start() -> std::io::Result<Self> {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recorded = received.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::from(recorded.clone()))
+                .default_service(web::to(respond))
+        })
+        .bind(("127.0.0.1", 0))?
+        .workers(1);
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+        Ok(Self { addr, received })
+    }
+
+    fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+#[allow(clippy::unused_async)]
+async fn respond(request: HttpRequest, received: web::Data<Mutex<Vec<Received>>>) -> HttpResponse {
+    received.lock().unwrap().push(Received {
+        method: request.method().to_string(),
+        path: request.path().to_string(),
+    });
+    match request.path() {
+        "/trigger" => HttpResponse::Ok().body("Camera triggered"),
+        "/stats" => HttpResponse::Ok().json(serde_json::json!({
+            "camera_name": "booth",
+            "exposure": null,
+            "fps": 29.5,
+            "started_at": null,
+        })),
+        "/snapshots" => HttpResponse::Ok().json(serde_json::json!([{
+            "filename": "mugshot_0001.jpg",
+            "timestamp": "2022-05-01T12:00:00+02:00",
+            "size_bytes": 1234,
+        }])),
+        _ => HttpResponse::NotFound().finish(),
+    }
+}
+
+fn get(path: &str) -> Received {
+    Received {
+        method: "GET".to_string(),
+        path: path.to_string(),
+    }
+}
+
+#[actix_web::test]
+async fn client_requests_match_the_api() {
+    let server = MockServer::start().unwrap();
+    let client = Client::new(server.url());
+
+    client.trigger().await.unwrap();
+    let stats = client.get_stats().await.unwrap();
+    assert_eq!(stats.camera_name, "booth");
+    assert_eq!(stats.fps, Some(29.5));
+    let snapshots = client.list_snapshots().await.unwrap();
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].filename, "mugshot_0001.jpg");
+    assert_eq!(snapshots[0].size_bytes, 1234);
+
+    assert_eq!(
+        server.received(),
+        [get("/trigger"), get("/stats"), get("/snapshots")]
+    );
+}
+
+#[actix_web::test]
+async fn client_reports_error_status() {
+    let server = MockServer::start().unwrap();
+    let client = Client::new(format!("{}/missing/", server.url()));
+
+    assert!(client.trigger().await.is_err());
+    assert_eq!(server.received(), [get("/missing/trigger")]);
+}